@@ -0,0 +1,305 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+use tui::{
+    layout::Rect,
+    style::{Color, Style},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph},
+    Frame,
+};
+
+use super::State;
+use crate::prelude::*;
+use crate::Luma;
+
+/// The most lines that are remembered.
+const MAX_HISTORY: usize = 200;
+/// The most completions shown at once.
+const MAX_SHOWN: usize = 8;
+
+/// The text being typed after `:` and the lines typed before.
+#[derive(Debug, Default)]
+pub struct CmdLine {
+    pub text: String,
+    /// Position of the cursor in chars.
+    pub cursor: usize,
+    history: VecDeque<String>,
+    /// The line of the history being shown, the text typed is kept in `saved`.
+    browsing: Option<usize>,
+    saved: String,
+    /// Ways to finish the word under the cursor and the one that is in the
+    /// text now.
+    pub completions: Vec<String>,
+    pub completion: Option<usize>,
+    /// The text that was completed.
+    base: String,
+}
+
+/// Where the history is kept between sessions.
+pub fn history_path() -> PathBuf {
+    crate::config::data_dir().join("history")
+}
+
+impl CmdLine {
+    /// Reads the history from the file, a missing file is an empty history.
+    pub fn load(&mut self, path: &Path) {
+        match fs::read_to_string(path) {
+            Ok(text) => {
+                self.history = text.lines().map(str::to_owned).collect();
+                while self.history.len() > MAX_HISTORY {
+                    self.history.pop_front();
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("could not read history {}: {}", path.display(), e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut text = String::new();
+        for l in &self.history {
+            text.push_str(l);
+            text.push('\n');
+        }
+        fs::write(path, text)
+    }
+
+    /// Takes the text to run it and remembers it.
+    pub fn take(&mut self) -> String {
+        let text = std::mem::take(&mut self.text);
+        self.reset();
+        let line = text.trim();
+        if !line.is_empty() && self.history.back().map(String::as_str) != Some(line) {
+            if self.history.len() == MAX_HISTORY {
+                self.history.pop_front();
+            }
+            self.history.push_back(line.to_owned());
+        }
+        text
+    }
+
+    /// Clears the line to start typing again.
+    pub fn reset(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+        self.browsing = None;
+        self.saved.clear();
+        self.clear_completions();
+    }
+
+    fn byte(&self, char: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(char)
+            .map_or(self.text.len(), |(i, _)| i)
+    }
+
+    pub fn insert(&mut self, c: char) {
+        let i = self.byte(self.cursor);
+        self.text.insert(i, c);
+        self.cursor += 1;
+        self.clear_completions();
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            let i = self.byte(self.cursor);
+            self.text.remove(i);
+        }
+        self.clear_completions();
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < self.text.chars().count() {
+            let i = self.byte(self.cursor);
+            self.text.remove(i);
+        }
+        self.clear_completions();
+    }
+
+    /// Removes the word before the cursor.
+    pub fn delete_word(&mut self) {
+        let end = self.byte(self.cursor);
+        let start = self.text[..end]
+            .trim_end()
+            .rfind(char::is_whitespace)
+            .map_or(0, |i| i + 1);
+        self.cursor -= self.text[start..end].chars().count();
+        self.text.replace_range(start..end, "");
+        self.clear_completions();
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.text.chars().count());
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.text.chars().count();
+    }
+
+    /// Shows an older line from the history.
+    pub fn older(&mut self) {
+        let i = match self.browsing {
+            None if self.history.is_empty() => return,
+            None => {
+                self.saved = self.text.clone();
+                self.history.len() - 1
+            }
+            Some(i) => i.saturating_sub(1),
+        };
+        self.browsing = Some(i);
+        self.show(self.history[i].clone());
+    }
+
+    /// Shows a newer line from the history, or what was being typed.
+    pub fn newer(&mut self) {
+        let Some(i) = self.browsing else {
+            return;
+        };
+        if i + 1 < self.history.len() {
+            self.browsing = Some(i + 1);
+            self.show(self.history[i + 1].clone());
+        } else {
+            self.browsing = None;
+            let saved = std::mem::take(&mut self.saved);
+            self.show(saved);
+        }
+    }
+
+    fn show(&mut self, text: String) {
+        self.text = text;
+        self.end();
+        self.clear_completions();
+    }
+
+    /// Puts the next completion in place of the last word, or the previous
+    /// one if `back` is set. `find` is used to get them the first time.
+    pub fn complete(&mut self, back: bool, find: impl FnOnce(&str) -> Vec<String>) {
+        if self.completion.is_none() && self.completions.is_empty() {
+            self.base = self.text.clone();
+            self.completions = find(&self.text);
+        }
+        let len = self.completions.len();
+        if len == 0 {
+            return;
+        }
+
+        let i = match (self.completion, back) {
+            (None, false) => 0,
+            (None, true) => len - 1,
+            (Some(i), false) => (i + 1) % len,
+            (Some(i), true) => (i + len - 1) % len,
+        };
+        self.completion = Some(i);
+
+        // the completion replaces the word that was being typed
+        let start = self.base.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        self.text = format!("{}{}", &self.base[..start], self.completions[i]);
+        self.end();
+    }
+
+    fn clear_completions(&mut self) {
+        self.completions.clear();
+        self.completion = None;
+    }
+}
+
+pub fn draw(f: &mut Frame<'_>, luma: &Luma, stat: &State) {
+    super::normal::draw(f, luma, stat);
+
+    // the command line goes over the help bar
+    let size = f.size();
+    let line = Rect::new(size.x, size.bottom().saturating_sub(1), size.width, 1);
+    let cmd = &stat.cmdline;
+
+    f.render_widget(Clear, line);
+    f.render_widget(Paragraph::new(format!(":{}", cmd.text)), line);
+    let x = line.x + 1 + cmd.text.chars().take(cmd.cursor).count() as u16;
+    f.set_cursor(x.min(line.right().saturating_sub(1)), line.y);
+
+    if cmd.completions.is_empty() {
+        return;
+    }
+
+    // keep the selected completion in view
+    let sel = cmd.completion.unwrap_or(0);
+    let skip = sel.saturating_sub(MAX_SHOWN - 1);
+    let shown: Vec<_> = cmd.completions.iter().skip(skip).take(MAX_SHOWN).collect();
+
+    let width = shown.iter().map(|c| c.chars().count()).max().unwrap_or(0) as u16 + 2;
+    let height = shown.len() as u16 + 2;
+    if line.y < height {
+        return;
+    }
+    let area = Rect::new(line.x, line.y - height, width.min(line.width), height);
+
+    let items = shown.iter().enumerate().map(|(i, c)| {
+        let style = if Some(i + skip) == cmd.completion {
+            Style::default().bg(Color::Yellow).fg(Color::Black)
+        } else {
+            Style::default()
+        };
+        ListItem::new(c.as_str()).style(style)
+    });
+    f.render_widget(Clear, area);
+    f.render_widget(
+        List::new(items).block(Block::new().borders(Borders::ALL)),
+        area,
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::CmdLine;
+
+    #[test]
+    fn history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("luma/history");
+
+        let mut line = CmdLine::default();
+        for c in "sort name".chars() {
+            line.insert(c);
+        }
+        assert_eq!(line.take(), "sort name");
+        line.insert('w');
+        line.take();
+        line.save(&path).unwrap();
+
+        let mut line = CmdLine::default();
+        line.load(&path);
+        line.insert('x');
+        line.older();
+        assert_eq!(line.text, "w");
+        line.older();
+        line.older();
+        assert_eq!(line.text, "sort name");
+        line.newer();
+        line.newer();
+        assert_eq!(line.text, "x");
+
+        line.reset();
+        for c in "tab m".chars() {
+            line.insert(c);
+        }
+        let find = |_: &str| vec!["music".to_owned(), "movies".to_owned()];
+        line.complete(false, find);
+        assert_eq!(line.text, "tab music");
+        line.complete(false, find);
+        assert_eq!(line.text, "tab movies");
+        line.complete(true, find);
+        assert_eq!(line.text, "tab music");
+    }
+}
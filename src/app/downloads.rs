@@ -0,0 +1,75 @@
+use tui::{
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+use super::State;
+
+use crate::download::{Downloads, Status};
+use crate::Luma;
+
+/// Width of the progress bar in cells.
+const BAR: usize = 20;
+
+pub fn draw(f: &mut Frame<'_>, luma: &Luma, stat: &State, dls: &Downloads) {
+    super::normal::draw(f, luma, stat);
+    let fbox = super::delete::float_box(f.size());
+
+    f.render_widget(Clear, fbox);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Downloads (x: cancel, r: retry, c: clear finished, q: close)");
+
+    if dls.list.is_empty() {
+        let p = Paragraph::new("Nothing is being downloaded. Press f on a link to fetch it.")
+            .block(block)
+            .alignment(tui::layout::Alignment::Center);
+        f.render_widget(p, fbox);
+        return;
+    }
+
+    let items = dls.list.iter().map(|d| {
+        let (got, fraction) = d.progress();
+        let (state, color) = match d.status() {
+            Status::Queued => ("queued".to_owned(), Color::DarkGray),
+            Status::Running => (size(got), Color::Yellow),
+            Status::Done(_) => ("done".to_owned(), Color::Green),
+            Status::Failed(e) => (e, Color::Red),
+            Status::Cancelled => ("cancelled".to_owned(), Color::DarkGray),
+        };
+
+        let filled = fraction.map_or(0, |p| (p.clamp(0.0, 1.0) * BAR as f64) as usize);
+        let percent = fraction.map_or("   ?".to_owned(), |p| format!("{:>3.0}%", p * 100.0));
+        ListItem::new(Line::from(vec![
+            Span::styled("█".repeat(filled), Style::default().fg(color)),
+            Span::styled(
+                "░".repeat(BAR - filled),
+                Style::default().fg(Color::DarkGray),
+            ),
+            Span::raw(format!(" {} ", percent)),
+            Span::raw(format!("{:<32} ", d.link.name)),
+            Span::styled(state, Style::default().fg(color)),
+        ]))
+    });
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().bg(Color::Red));
+    let mut list_stat = ListState::default().with_selected(Some(stat.dl_selected));
+    f.render_stateful_widget(list, fbox, &mut list_stat);
+}
+
+/// Formats a number of bytes for people.
+pub fn size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut n = bytes as f64;
+    let mut unit = 0;
+    while n >= 1024.0 && unit < UNITS.len() - 1 {
+        n /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", n, UNITS[unit])
+}
@@ -0,0 +1,171 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::AppError;
+use crate::prelude::*;
+use crate::state::Validate;
+
+/// Prefix of the lines written to the top of the file to report errors.
+const ERR_PREFIX: &str = "#!";
+
+/// Where the result of an edit goes once the editor exits.
+#[derive(Debug, Clone, Copy)]
+pub enum Target {
+    /// Replace the link at this position.
+    Link { tabb: usize, index: usize },
+    /// Add a link to the end of the tab.
+    NewLink { tabb: usize },
+    /// Rename the tab.
+    TabName { tabb: usize },
+    /// Add a tab with the name.
+    NewTab,
+    /// Write the tags to the file of the link at this position.
+    Tags { tabb: usize, index: usize },
+}
+
+/// An item that is written to a file for the user to change in the editor.
+#[derive(Debug)]
+pub struct Edit {
+    pub target: Target,
+    file: tempfile::NamedTempFile,
+}
+
+/// The outcome of reading back a file from the editor.
+#[derive(Debug, PartialEq)]
+pub enum Edited<T> {
+    /// The value parsed and is valid.
+    Done(T),
+    /// The user emptied the file.
+    Abort,
+    /// The value could not be used for these reasons.
+    Invalid(Vec<String>),
+}
+
+impl Edit {
+    pub fn new<T: Serialize>(target: Target, item: &T) -> Result<Self, AppError> {
+        let file = tempfile::Builder::new()
+            .prefix("luma-")
+            .suffix(".yaml")
+            .tempfile()
+            .change_context(AppError::Edit)
+            .attach_printable("could not create temp file")?;
+
+        log::debug!("tempfile path: {:?}", file.path());
+
+        let text = yaml::to_string(item)
+            .change_context(AppError::Edit)
+            .attach_printable("could not serialize item")?;
+
+        fs::write(file.path(), text)
+            .change_context(AppError::Edit)
+            .attach_printable("could not write temp file")?;
+
+        Ok(Self { target, file })
+    }
+
+    pub fn path(&self) -> &str {
+        self.file.path().to_str().unwrap()
+    }
+
+    /// Reads back the file and gives the value to `f` if it can be used.
+    ///
+    /// When it can't, the errors are written to the top of the file and true
+    /// is returned so the editor can be opened again. Emptying the file
+    /// aborts the edit without calling `f`.
+    pub fn apply<T: DeserializeOwned + Validate>(
+        &self,
+        f: impl FnOnce(T),
+    ) -> Result<bool, AppError> {
+        let text = fs::read_to_string(self.file.path())
+            .change_context(AppError::Edit)
+            .attach_printable("could not read temp file")?;
+
+        match parse_edit(&text) {
+            Edited::Done(v) => f(v),
+            Edited::Abort => log::info!("edit aborted"),
+            Edited::Invalid(errs) => {
+                log::debug!("edit was invalid: {:?}", errs);
+                fs::write(self.file.path(), annotate(&text, &errs))
+                    .change_context(AppError::Edit)
+                    .attach_printable("could not write temp file")?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+pub fn parse_edit<T: DeserializeOwned + Validate>(text: &str) -> Edited<T> {
+    let body = strip_errors(text);
+
+    let blank = body.lines().all(|l| {
+        let l = l.trim();
+        l.is_empty() || l.starts_with('#')
+    });
+    if blank {
+        return Edited::Abort;
+    }
+
+    match yaml::from_str::<T>(body) {
+        Ok(v) => {
+            let errs = v.validate();
+            if errs.is_empty() {
+                Edited::Done(v)
+            } else {
+                Edited::Invalid(errs)
+            }
+        }
+        Err(e) => Edited::Invalid(vec![e.to_string()]),
+    }
+}
+
+/// Removes the error lines written by [`annotate`] from the top of the text.
+fn strip_errors(text: &str) -> &str {
+    let mut rest = text;
+    while rest.starts_with(ERR_PREFIX) {
+        rest = rest.split_once('\n').map(|(_, r)| r).unwrap_or("");
+    }
+    rest
+}
+
+/// Puts the errors at the top of the text as comments.
+fn annotate(text: &str, errs: &[String]) -> String {
+    let mut buf = format!("{} the edit could not be saved:\n", ERR_PREFIX);
+    for e in errs {
+        for l in e.lines() {
+            buf.push_str(&format!("{}   {}\n", ERR_PREFIX, l));
+        }
+    }
+    buf.push_str(&format!(
+        "{} fix them and save, or delete everything to cancel. line numbers don't count these lines.\n",
+        ERR_PREFIX
+    ));
+    buf.push_str(strip_errors(text));
+    buf
+}
+
+#[cfg(test)]
+mod test {
+    use super::{annotate, parse_edit, Edited};
+    use crate::state::Link;
+
+    #[test]
+    fn edit_errors() {
+        let bad = "name: ''\nlink: not a url\n";
+        let Edited::Invalid(errs) = parse_edit::<Link>(bad) else {
+            panic!("empty name and bad url should be rejected");
+        };
+        assert_eq!(errs.len(), 2);
+
+        // the errors are replaced, not stacked, when the file comes back again
+        let text = annotate(&annotate(bad, &errs), &["other".to_owned()]);
+        assert!(text.ends_with(bad));
+        assert!(!text.contains("name must not be empty"));
+
+        let fixed = annotate("name: x\nlink: https://example.com\n", &errs);
+        assert!(matches!(parse_edit::<Link>(&fixed), Edited::Done(l) if l.name == "x"));
+
+        assert_eq!(parse_edit::<Link>(&annotate("\n", &errs)), Edited::Abort);
+        assert!(matches!(parse_edit::<String>("[oops"), Edited::Invalid(_)));
+    }
+}
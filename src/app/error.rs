@@ -0,0 +1,86 @@
+use resu::{AttachmentKind, FrameKind};
+use tui::{
+    style::{Color, Style},
+    text::{Line, Text},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+
+use super::State;
+
+use crate::prelude::*;
+
+pub fn draw(f: &mut Frame<'_>, luma: &Luma, stat: &State, report: &[String]) {
+    super::normal::draw(f, luma, stat);
+    let fbox = super::delete::float_box(f.size());
+
+    f.render_widget(Clear, fbox);
+
+    let text = Text::from(
+        report
+            .iter()
+            .enumerate()
+            .map(|(i, l)| {
+                // the first line is what went wrong, the rest is why
+                if i == 0 {
+                    Line::styled(l.as_str(), Style::default().fg(Color::Red))
+                } else {
+                    Line::raw(l.as_str())
+                }
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    let p = Paragraph::new(text).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Error (press any key to continue)")
+            .border_style(Style::default().fg(Color::Red)),
+    );
+
+    f.render_widget(p, fbox);
+}
+
+/// Turns the report into lines of text, each context followed by the
+/// attachments that were given with it.
+pub fn lines<C>(report: &Report<C>) -> Vec<String> {
+    let mut buf = Vec::new();
+    // frames go from newest to oldest so attachments come before their context
+    let mut attachments = Vec::new();
+    for frame in report.frames() {
+        match frame.kind() {
+            FrameKind::Context(c) => {
+                buf.push(c.to_string());
+                buf.append(&mut attachments);
+            }
+            FrameKind::Attachment(AttachmentKind::Printable(a)) => {
+                attachments.push(format!("  - {}", a))
+            }
+            FrameKind::Attachment(_) => {}
+        }
+    }
+    buf
+}
+
+#[cfg(test)]
+mod test {
+    use crate::app::AppError;
+    use crate::prelude::*;
+
+    #[test]
+    fn report_lines() {
+        let r = Report::new(io::Error::other("disk on fire"))
+            .attach_printable("could not write temp file")
+            .change_context(AppError::Edit);
+
+        let lines = super::lines(&r);
+        assert_eq!(
+            lines,
+            [
+                "failed to edit the link",
+                "disk on fire",
+                "  - could not write temp file",
+            ]
+        );
+    }
+}
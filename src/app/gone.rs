@@ -0,0 +1,42 @@
+use tui::{
+    layout::Rect,
+    style::{Color, Style},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+
+use super::State;
+
+use crate::Luma;
+
+/// Asks what to open when the page of a link with a snapshot is down.
+pub fn draw(f: &mut Frame<'_>, luma: &Luma, stat: &State, why: &str) {
+    super::normal::draw(f, luma, stat);
+    let size = f.size();
+    let width = size.width.min(60);
+    let fbox = Rect {
+        x: size.x + (size.width - width) / 2,
+        y: size.y + size.height.saturating_sub(6) / 2,
+        width,
+        height: size.height.min(6),
+    };
+
+    f.render_widget(Clear, fbox);
+
+    let text = format!(
+        "{}\n\na: open archived copy, o: open anyway, n: cancel",
+        why
+    );
+    let p = Paragraph::new(text)
+        .style(Style::default().fg(Color::Yellow))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Page is gone")
+                .border_style(Style::default().fg(Color::Yellow)),
+        )
+        .wrap(Wrap { trim: true })
+        .alignment(tui::layout::Alignment::Center);
+
+    f.render_widget(p, fbox);
+}
@@ -0,0 +1,169 @@
+//! Shows the head of text files in the preview, highlighted by their
+//! extension.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{FontStyle, Theme, ThemeSet},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+};
+
+use crate::prelude::*;
+
+/// Only this much of a file is shown.
+const MAX_SIZE: u64 = 64 * 1024;
+const THEME: &str = "base16-ocean.dark";
+
+/// The file shown last, read again when it changes.
+#[derive(Debug, PartialEq)]
+struct Key {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+/// Highlighted files, the syntaxes are only loaded once a file is shown.
+#[derive(Debug, Default)]
+pub struct Files {
+    syntaxes: Option<(SyntaxSet, Theme)>,
+    last: Option<(Key, Option<Text<'static>>)>,
+}
+
+impl Files {
+    /// The highlighted head of the file, `None` if it isn't text.
+    pub fn get(&mut self, path: &Path) -> Option<Text<'static>> {
+        let meta = fs::metadata(path).ok().filter(|m| m.is_file())?;
+        let key = Key {
+            path: path.to_owned(),
+            modified: meta.modified().ok(),
+            len: meta.len(),
+        };
+        if let Some((k, text)) = &self.last {
+            if *k == key {
+                return text.clone();
+            }
+        }
+
+        let text = read(path).ok().flatten().map(|(head, cut)| {
+            let (syntaxes, theme) = self.syntaxes.get_or_insert_with(|| {
+                let mut themes = ThemeSet::load_defaults();
+                let theme = themes.themes.remove(THEME).unwrap_or_default();
+                (SyntaxSet::load_defaults_newlines(), theme)
+            });
+            let mut text = highlight(syntaxes, theme, path, &head);
+            if cut {
+                text.lines.push(Line::styled(
+                    format!("… cut off after {} KiB", MAX_SIZE / 1024),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            text
+        });
+        self.last = Some((key, text.clone()));
+        text
+    }
+}
+
+/// The head of the file and if there is more, `None` if it isn't text.
+fn read(path: &Path) -> io::Result<Option<(String, bool)>> {
+    let f = fs::File::open(path)?;
+    let mut buf = Vec::new();
+    io::Read::read_to_end(&mut io::Read::take(f, MAX_SIZE + 1), &mut buf)?;
+    let cut = buf.len() as u64 > MAX_SIZE;
+    buf.truncate(MAX_SIZE as usize);
+
+    if buf.contains(&0) {
+        return Ok(None);
+    }
+    let text = match String::from_utf8(buf) {
+        Ok(t) => t,
+        // the cut can land in the middle of a character
+        Err(e) if cut && e.utf8_error().error_len().is_none() => {
+            let valid = e.utf8_error().valid_up_to();
+            let mut buf = e.into_bytes();
+            buf.truncate(valid);
+            String::from_utf8(buf).expect("checked to be valid")
+        }
+        Err(_) => return Ok(None),
+    };
+    Ok(Some((text, cut)))
+}
+
+/// Colors the text with the syntax of the file's extension, or of its first
+/// line for scripts.
+fn highlight(syntaxes: &SyntaxSet, theme: &Theme, path: &Path, text: &str) -> Text<'static> {
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+    let syntax = syntaxes
+        .find_syntax_by_extension(&ext)
+        .or_else(|| syntaxes.find_syntax_by_first_line(text))
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+
+    let mut h = HighlightLines::new(syntax, theme);
+    let mut lines = Vec::new();
+    for line in LinesWithEndings::from(text) {
+        let Ok(regions) = h.highlight_line(line, syntaxes) else {
+            lines.push(Line::raw(line.trim_end().replace('\t', "    ")));
+            continue;
+        };
+        let spans: Vec<Span<'static>> = regions
+            .into_iter()
+            .map(|(style, s)| {
+                let s = s.trim_end_matches(['\r', '\n']).replace('\t', "    ");
+                let fg = style.foreground;
+                let mut st = Style::default().fg(Color::Rgb(fg.r, fg.g, fg.b));
+                for (font, m) in [
+                    (FontStyle::BOLD, Modifier::BOLD),
+                    (FontStyle::ITALIC, Modifier::ITALIC),
+                    (FontStyle::UNDERLINE, Modifier::UNDERLINED),
+                ] {
+                    if style.font_style.contains(font) {
+                        st = st.add_modifier(m);
+                    }
+                }
+                Span::styled(s, st)
+            })
+            .filter(|s| !s.content.is_empty())
+            .collect();
+        lines.push(Line::from(spans));
+    }
+    Text::from(lines)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Files, MAX_SIZE};
+    use crate::prelude::*;
+
+    #[test]
+    fn files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut files = Files::default();
+
+        let code = dir.path().join("main.rs");
+        fs::write(&code, "fn main() {\n\tlet x = 1;\n}\n").unwrap();
+        let text = files.get(&code).unwrap();
+        let lines: Vec<String> = text.lines.iter().map(|l| l.to_string()).collect();
+        assert_eq!(lines, ["fn main() {", "    let x = 1;", "}"]);
+        assert!(text.lines[0].spans.len() > 1);
+
+        let big = dir.path().join("big.txt");
+        fs::write(&big, "é".repeat(MAX_SIZE as usize)).unwrap();
+        let text = files.get(&big).unwrap();
+        assert_eq!(
+            text.lines.last().unwrap().to_string(),
+            "… cut off after 64 KiB"
+        );
+
+        let bin = dir.path().join("song.mp3");
+        fs::write(&bin, b"ID3\0\0").unwrap();
+        assert_eq!(files.get(&bin), None);
+        assert_eq!(files.get(dir.path()), None);
+    }
+}
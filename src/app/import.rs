@@ -0,0 +1,55 @@
+use tui::{
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+    Frame,
+};
+
+use super::State;
+
+use crate::Luma;
+
+pub fn draw(f: &mut Frame<'_>, luma: &Luma, stat: &State) {
+    super::normal::draw(f, luma, stat);
+    let fbox = super::delete::float_box(f.size());
+
+    f.render_widget(Clear, fbox);
+
+    let kept = stat.candidates.iter().filter(|c| c.keep).count();
+    let block = Block::default().borders(Borders::ALL).title(format!(
+        "Import {}/{} (space: keep, a/n: all/none, h/l: tab, enter: add, q: cancel)",
+        kept,
+        stat.candidates.len()
+    ));
+
+    let items = stat.candidates.iter().map(|c| {
+        let (mark, color) = if c.keep {
+            ("[x] ", Color::Green)
+        } else {
+            ("[ ] ", Color::DarkGray)
+        };
+        let tab = luma.tabs.get(c.tabb).map_or("?", |t| t.0.as_str());
+        // links the tab already has start out dropped
+        let have = luma
+            .tabs
+            .get(c.tabb)
+            .is_some_and(|t| t.1.iter().any(|l| l.link == c.link.link));
+
+        let mut spans = vec![
+            Span::styled(mark, Style::default().fg(color)),
+            Span::styled(format!("{:<10} ", tab), Style::default().fg(Color::Yellow)),
+            Span::raw(format!("{} ", c.link.name)),
+            Span::styled(c.link.link.clone(), Style::default().fg(Color::DarkGray)),
+        ];
+        if have {
+            spans.push(Span::styled(" (have)", Style::default().fg(Color::Red)));
+        }
+        ListItem::new(Line::from(spans))
+    });
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().bg(Color::Red));
+    let mut list_stat = ListState::default().with_selected(Some(stat.cand_selected));
+    f.render_stateful_widget(list, fbox, &mut list_stat);
+}
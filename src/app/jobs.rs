@@ -0,0 +1,50 @@
+use tui::{
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph},
+    Frame,
+};
+
+use super::State;
+
+use crate::job::Job;
+use crate::Luma;
+
+pub fn draw(f: &mut Frame<'_>, luma: &Luma, stat: &State, jobs: &[Job]) {
+    super::normal::draw(f, luma, stat);
+    let fbox = super::delete::float_box(f.size());
+
+    f.render_widget(Clear, fbox);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Jobs (c: clear finished, q: close)");
+
+    if jobs.is_empty() {
+        let p = Paragraph::new("Nothing has been opened yet.")
+            .block(block)
+            .alignment(tui::layout::Alignment::Center);
+        f.render_widget(p, fbox);
+        return;
+    }
+
+    // newest first
+    let items = jobs.iter().rev().map(|j| {
+        let (state, color) = match j.done {
+            None => (
+                format!("running {}s", j.started.elapsed().as_secs()),
+                Color::Yellow,
+            ),
+            Some((s, took)) if s.success() => {
+                (format!("done in {}s", took.as_secs()), Color::Green)
+            }
+            Some((s, _)) => (s.to_string(), Color::Red),
+        };
+        ListItem::new(Line::from(vec![
+            Span::styled(format!("{:<24}", state), Style::default().fg(color)),
+            Span::raw(j.name.as_str()),
+        ]))
+    });
+
+    f.render_widget(List::new(items).block(block), fbox);
+}
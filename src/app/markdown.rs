@@ -0,0 +1,255 @@
+//! Turns Markdown into styled text for the terminal.
+
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+};
+
+/// Renders the Markdown. Lines are left long, the paragraph showing them
+/// wraps them.
+pub fn render(md: &str) -> Text<'static> {
+    let mut r = Renderer::default();
+    let opts = Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    for event in Parser::new_ext(md, opts) {
+        r.event(event);
+    }
+    r.flush();
+    // blocks leave a blank line after themselves
+    while r.lines.last().is_some_and(|l| l.spans.is_empty()) {
+        r.lines.pop();
+    }
+    Text::from(r.lines)
+}
+
+#[derive(Default)]
+struct Renderer {
+    lines: Vec<Line<'static>>,
+    line: Vec<Span<'static>>,
+    /// Styles of the inline tags that are open.
+    styles: Vec<Style>,
+    /// Number of the next item of each open list, `None` for bullets.
+    lists: Vec<Option<u64>>,
+    /// What the lines start with in block quotes and list items.
+    prefix: Vec<String>,
+    /// The prefix of the first line of an item has its bullet.
+    bullet: Option<String>,
+    in_code: bool,
+    link: Option<String>,
+}
+
+impl Renderer {
+    fn style(&self) -> Style {
+        self.styles
+            .iter()
+            .fold(Style::default(), |acc, s| acc.patch(*s))
+    }
+
+    fn push(&mut self, text: String, style: Style) {
+        if self.line.is_empty() {
+            let prefix = self.bullet.take().unwrap_or_else(|| self.prefix.concat());
+            if !prefix.is_empty() {
+                self.line
+                    .push(Span::styled(prefix, Style::default().fg(Color::DarkGray)));
+            }
+        }
+        self.line.push(Span::styled(text, style));
+    }
+
+    fn flush(&mut self) {
+        if !self.line.is_empty() {
+            self.lines.push(Line::from(std::mem::take(&mut self.line)));
+        }
+    }
+
+    fn blank(&mut self) {
+        self.flush();
+        if self.lines.last().is_some_and(|l| !l.spans.is_empty()) {
+            self.lines.push(Line::default());
+        }
+    }
+
+    fn event(&mut self, event: Event<'_>) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(t) if self.in_code => {
+                let style = Style::default().fg(Color::Gray);
+                for l in t.lines() {
+                    self.push(format!("  {}", l), style);
+                    self.flush();
+                }
+            }
+            Event::Text(t) => self.push(t.into_string(), self.style()),
+            Event::Code(c) => self.push(c.into_string(), Style::default().fg(Color::Yellow)),
+            Event::SoftBreak => self.push(" ".to_owned(), self.style()),
+            Event::HardBreak => self.flush(),
+            Event::Rule => {
+                self.blank();
+                self.push("─".repeat(20), Style::default().fg(Color::DarkGray));
+                self.blank();
+            }
+            Event::TaskListMarker(done) => {
+                let mark = if done { "[x] " } else { "[ ] " };
+                self.push(mark.to_owned(), Style::default().fg(Color::Green));
+            }
+            Event::Html(h) | Event::InlineHtml(h) => self.push(h.into_string(), self.style()),
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag<'_>) {
+        match tag {
+            Tag::Heading { level, .. } => {
+                self.blank();
+                let color = match level {
+                    HeadingLevel::H1 => Color::Yellow,
+                    HeadingLevel::H2 => Color::Cyan,
+                    _ => Color::Blue,
+                };
+                let hashes = "#".repeat(level as usize);
+                let style = Style::default().fg(color).add_modifier(Modifier::BOLD);
+                self.push(format!("{} ", hashes), style);
+                self.styles.push(style);
+            }
+            // items keep their first paragraph on the bullet's line
+            Tag::Paragraph if self.bullet.is_none() => self.flush(),
+            Tag::BlockQuote(_) => {
+                self.blank();
+                self.prefix.push("│ ".to_owned());
+                self.styles
+                    .push(Style::default().add_modifier(Modifier::ITALIC));
+            }
+            Tag::CodeBlock(_) => {
+                self.blank();
+                self.in_code = true;
+            }
+            Tag::List(start) => {
+                if self.lists.is_empty() {
+                    self.blank();
+                } else {
+                    self.flush();
+                }
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.flush();
+                let bullet = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "• ".to_owned(),
+                };
+                self.bullet = Some(format!("{}{}", self.prefix.concat(), bullet));
+                self.prefix.push(" ".repeat(bullet.chars().count()));
+            }
+            Tag::Emphasis => self
+                .styles
+                .push(Style::default().add_modifier(Modifier::ITALIC)),
+            Tag::Strong => self
+                .styles
+                .push(Style::default().add_modifier(Modifier::BOLD)),
+            Tag::Strikethrough => self
+                .styles
+                .push(Style::default().add_modifier(Modifier::CROSSED_OUT)),
+            Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => {
+                self.link = Some(dest_url.into_string());
+                self.styles.push(
+                    Style::default()
+                        .fg(Color::Blue)
+                        .add_modifier(Modifier::UNDERLINED),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Heading(_) => {
+                self.styles.pop();
+                self.blank();
+            }
+            TagEnd::Paragraph => {
+                if self.lists.is_empty() {
+                    self.blank();
+                } else {
+                    self.flush();
+                }
+            }
+            TagEnd::BlockQuote(_) => {
+                self.flush();
+                self.prefix.pop();
+                self.styles.pop();
+                self.blank();
+            }
+            TagEnd::CodeBlock => {
+                self.in_code = false;
+                self.blank();
+            }
+            TagEnd::List(_) => {
+                self.flush();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.blank();
+                }
+            }
+            TagEnd::Item => {
+                // an empty item still shows its bullet
+                if let Some(b) = self.bullet.take() {
+                    self.line
+                        .push(Span::styled(b, Style::default().fg(Color::DarkGray)));
+                }
+                self.flush();
+                self.prefix.pop();
+            }
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough => {
+                self.styles.pop();
+            }
+            TagEnd::Link | TagEnd::Image => {
+                self.styles.pop();
+                // the url is shown when the text doesn't already say it
+                if let Some(url) = self.link.take() {
+                    let text: String = self.line.iter().map(|s| s.content.as_ref()).collect();
+                    if !text.ends_with(&url) {
+                        self.push(format!(" <{}>", url), Style::default().fg(Color::DarkGray));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::render;
+
+    #[test]
+    fn blocks() {
+        let md = "# Title\n\nsome *text* with `code`\n\n- one\n- two\n  1. a\n\n> quoted\n\n```\nlet x;\n```\n";
+        let text = render(md);
+        let lines: Vec<String> = text
+            .lines
+            .iter()
+            .map(|l| l.spans.iter().map(|s| s.content.as_ref()).collect())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "# Title",
+                "",
+                "some text with code",
+                "",
+                "• one",
+                "• two",
+                "  1. a",
+                "",
+                "│ quoted",
+                "",
+                "  let x;",
+            ]
+        );
+    }
+}
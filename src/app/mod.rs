@@ -1,14 +1,52 @@
+mod command;
 mod delete;
+mod downloads;
+mod edit;
+mod error;
+mod gone;
+mod highlight;
+mod import;
+mod jobs;
+mod markdown;
 mod normal;
+mod picture;
+mod reader;
+mod search;
+mod term;
+mod toast;
 
-use serde::de::DeserializeOwned;
-use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
+use crate::archive::{Archives, Done};
+use crate::command::{Command, Filter};
+use crate::config::Options;
+use crate::download::{Backend, Downloads, Status};
 use crate::event::Event;
+use crate::feed::{self, Feeds};
+use crate::hook::{Flow, Hook, Hooks};
+use crate::import::{Candidate, ImportDir};
 use crate::input::Msg;
+use crate::job::{Capture, Job};
+use crate::lua::{Binding, Ctx, Script};
+use crate::mpd;
+use crate::mpv::{self, Mpv, Playing};
+use crate::note;
 use crate::prelude::*;
+use crate::reader::{Copies, Saved};
+use crate::search::{Hit, Index};
+use crate::tag::{self, Tags};
+use crate::thumb::Thumbs;
+
+use crate::state::{Link, OpenCommand, Validate};
+use crate::term::Term;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
-use crate::state::Link;
+pub use self::command::{history_path, CmdLine};
+use self::edit::{Edit, Target};
+pub use self::toast::{Level, Toasts};
 
 #[derive(Debug)]
 pub struct App<B: tui::backend::Backend + io::Write> {
@@ -19,7 +57,33 @@ pub struct App<B: tui::backend::Backend + io::Write> {
     /// State of the applications
     pub state: State,
     /// A list or process that are loosly tied to the app
-    task: Vec<std::process::Child>,
+    task: Vec<Job>,
+    /// The edit waiting on the program in the terminal pane
+    edit: Option<Edit>,
+    /// The config and user scripts
+    lua: Script,
+    /// Executables run around changes to the links
+    hooks: Hooks,
+    /// Where the links are saved
+    path: PathBuf,
+    /// Links being fetched into files
+    downloads: Downloads,
+    /// Thumbnails being made for the preview
+    thumbs: Thumbs,
+    /// Readable copies of pages being saved
+    copies: Copies,
+    /// Snapshots being taken and links being checked before opening
+    archives: Archives,
+    /// The search index, read once it is first searched
+    index: Option<Index>,
+    /// Feeds being fetched and when they were all last refreshed
+    feeds: Feeds,
+    feeds_at: Option<Instant>,
+    /// mpv, once something was played in it
+    mpv: Option<Mpv>,
+    /// The connection to MPD, once it was used, and what it is playing
+    mpd: Option<mpd::Client>,
+    mpd_watch: Option<mpd::Watch>,
 }
 
 #[derive(Debug, Default)]
@@ -36,6 +100,91 @@ pub struct State {
     pub ofst: usize,
     /// Mode of display that the terminal is in
     pub mode: Mode,
+    /// A program running in a pane next to the list
+    pub term: Option<Term>,
+    /// Messages for the user that go away on their own
+    pub toasts: Toasts,
+    /// If there are changes to [`Luma`] that haven't been saved
+    pub dirty: bool,
+    /// Options from the config
+    pub options: Options,
+    /// The line typed in command mode
+    pub cmdline: CmdLine,
+    /// Only the links that match are shown
+    pub filter: Option<Filter>,
+    /// If the links should not be saved on exit
+    pub discard: bool,
+    /// The download selected in the downloads pane
+    pub dl_selected: usize,
+    /// Links found in a file that are being looked over
+    pub candidates: Vec<Candidate>,
+    pub cand_selected: usize,
+    /// What was searched for and what was found, best first
+    pub query: String,
+    pub hits: Vec<Hit>,
+    pub hit_selected: usize,
+    /// What mpv is playing
+    pub playing: Option<Playing>,
+    /// How far the preview is scrolled and the tab and link it was scrolled
+    /// on, it starts at the top again for other links
+    prev_scroll: u16,
+    prev_at: (usize, usize),
+    /// How far the preview can scroll, known once it is drawn
+    pub prev_max: std::cell::Cell<u16>,
+    /// The picture in the preview, made while drawing
+    pub picture: std::cell::RefCell<picture::Preview>,
+    /// The text file in the preview, read while drawing
+    pub files: std::cell::RefCell<highlight::Files>,
+    /// The notes that were read for the preview and the filter
+    pub notes: std::cell::RefCell<crate::note::Notes>,
+    /// The saved copy of a page being read
+    pub reader: Option<reader::Reader>,
+}
+
+impl State {
+    /// Indexes of the links that pass the filter.
+    pub fn visible(&self, links: &[Link]) -> Vec<usize> {
+        (0..links.len())
+            .filter(|&i| {
+                self.filter
+                    .as_ref()
+                    .is_none_or(|f| f.matches(&links[i], |l| self.note(l)))
+            })
+            .collect()
+    }
+
+    /// How far the preview of the selected link is scrolled.
+    pub fn prev_scroll(&self) -> u16 {
+        if self.prev_at == (self.tabb, self.selected) {
+            self.prev_scroll
+        } else {
+            0
+        }
+    }
+
+    /// Scrolls the preview down by the rows, or up if it is negative.
+    pub fn scroll_preview(&mut self, by: i16) {
+        let at = self.prev_scroll().saturating_add_signed(by);
+        self.prev_scroll = at.min(self.prev_max.get());
+        self.prev_at = (self.tabb, self.selected);
+        self.draw = true;
+    }
+
+    /// The picture to show for the link, if pictures are on and it has one.
+    pub fn picture(&self, link: &Link) -> Option<(PathBuf, picture::Protocol)> {
+        let protocol = picture::Protocol::new(&self.options.images)?;
+        Some((crate::thumb::find(&crate::thumb::dir(), link)?, protocol))
+    }
+
+    /// The text of the link's note.
+    pub fn note(&self, link: &Link) -> Option<String> {
+        self.read_note(link.note.as_ref()?)
+    }
+
+    /// The text of the note at the path in the vault.
+    pub fn read_note(&self, note: &str) -> Option<String> {
+        self.notes.borrow_mut().get(&self.options.vault(), note)
+    }
 }
 
 #[derive(Default, Debug)]
@@ -45,6 +194,43 @@ pub enum Mode {
     // Prompt(PromptData),
     /// The app should prompt the user if they want to delete a thing
     Delete(Item),
+    /// Input goes to the program in the terminal pane
+    Term,
+    /// Shows the processes started by openers
+    Jobs,
+    /// Shows the lines of an error report
+    Error(Vec<String>),
+    /// Typing a command after `:`
+    Command,
+    /// Shows the progress of downloads
+    Downloads,
+    /// Picks which links found in a file get added
+    Import,
+    /// Reading the saved copy of a page
+    Reader,
+    /// Asks what to open when the page of a link is down, with why
+    Gone(String),
+    /// Shows what a search found
+    Search,
+}
+
+impl Mode {
+    /// The name shown in the status line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Normal => "NORMAL",
+            Mode::Delete(_) => "DELETE",
+            Mode::Term => "TERMINAL",
+            Mode::Jobs => "JOBS",
+            Mode::Error(_) => "ERROR",
+            Mode::Command => "COMMAND",
+            Mode::Downloads => "DOWNLOADS",
+            Mode::Import => "IMPORT",
+            Mode::Reader => "READER",
+            Mode::Gone(_) => "GONE",
+            Mode::Search => "SEARCH",
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -57,6 +243,11 @@ pub enum Item {
 pub enum AppError {
     Draw,
     Edit,
+    Term,
+    Script,
+    Hook,
+    Save,
+    Import,
 }
 
 impl fmt::Display for AppError {
@@ -64,30 +255,71 @@ impl fmt::Display for AppError {
         match self {
             AppError::Draw => f.write_str("failed to draw the screen"),
             AppError::Edit => f.write_str("failed to edit the link"),
+            AppError::Term => f.write_str("failed to run program in the terminal pane"),
+            AppError::Script => f.write_str("lua script failed"),
+            AppError::Hook => f.write_str("hook failed"),
+            AppError::Save => f.write_str("failed to save the links"),
+            AppError::Import => f.write_str("failed to import links"),
         }
     }
 }
 impl Context for AppError {}
 
 impl App<tui::backend::CrosstermBackend<fs::File>> {
-    pub fn new(luma: Luma, stdout: fs::File) -> Self {
-        Self {
-            // Creates the terminal to render the tui to.
-            term: tui::Terminal::new(tui::backend::CrosstermBackend::new(stdout)).unwrap(),
+    pub fn new(luma: Luma, path: PathBuf, stdout: fs::File) -> Result<Self, AppError> {
+        // Creates the terminal to render the tui to.
+        let term = tui::Terminal::new(tui::backend::CrosstermBackend::new(stdout))
+            .change_context(AppError::Draw)?;
+        let lua = Script::new().change_context(AppError::Script)?;
+        Ok(Self {
+            term,
             luma,
             state: State::default(),
             task: Vec::new(),
-        }
+            edit: None,
+            lua,
+            hooks: Hooks::new(crate::hook::dir()),
+            path,
+            downloads: Downloads::default(),
+            thumbs: Thumbs::default(),
+            copies: Copies::default(),
+            archives: Archives::default(),
+            index: None,
+            feeds: Feeds::default(),
+            feeds_at: None,
+            mpv: None,
+            mpd: None,
+            mpd_watch: None,
+        })
     }
 }
 
 impl<B: tui::backend::Backend + io::Write> App<B> {
     pub fn draw(&mut self) -> Result<(), AppError> {
-        self.term
-            .draw(|f| match self.state.mode {
-                Mode::Normal => normal::draw(f, &self.luma, &self.state),
-                Mode::Delete(_) => delete::draw(f, &self.luma, &self.state),
-            })
+        let normal = matches!(self.state.mode, Mode::Normal);
+        if normal && self.state.term.is_none() && self.state.options.images != "none" {
+            let tab = self.luma.tabs.get(self.state.tabb);
+            if let Some(link) = self.shown_at().and_then(|s| tab.and_then(|t| t.1.get(s))) {
+                self.thumbs.fetch(&crate::thumb::dir(), link);
+            }
+        }
+
+        if let (Mode::Reader, Some(r)) = (&self.state.mode, &mut self.state.reader) {
+            let size = self.term.size().change_context(AppError::Draw)?;
+            r.fit(reader::area(size));
+        }
+
+        self.frame()?;
+        // pictures the terminal drew stay until the cells under them are
+        // drawn again, and popups go over the preview
+        if self.state.picture.get_mut().stale(normal) {
+            self.term.clear().change_context(AppError::Draw)?;
+            self.frame()?;
+        }
+        self.state
+            .picture
+            .get_mut()
+            .show(self.term.backend_mut(), normal)
             .change_context(AppError::Draw)?;
 
         log::debug!("frame finished");
@@ -95,6 +327,27 @@ impl<B: tui::backend::Backend + io::Write> App<B> {
         Ok(())
     }
 
+    fn frame(&mut self) -> Result<(), AppError> {
+        self.term
+            .draw(|f| {
+                match &self.state.mode {
+                    Mode::Normal | Mode::Term => normal::draw(f, &self.luma, &self.state),
+                    Mode::Delete(_) => delete::draw(f, &self.luma, &self.state),
+                    Mode::Jobs => jobs::draw(f, &self.luma, &self.state, &self.task),
+                    Mode::Error(r) => error::draw(f, &self.luma, &self.state, r),
+                    Mode::Command => command::draw(f, &self.luma, &self.state),
+                    Mode::Downloads => downloads::draw(f, &self.luma, &self.state, &self.downloads),
+                    Mode::Import => import::draw(f, &self.luma, &self.state),
+                    Mode::Reader => reader::draw(f, &self.state),
+                    Mode::Gone(why) => gone::draw(f, &self.luma, &self.state, why),
+                    Mode::Search => search::draw(f, &self.luma, &self.state),
+                }
+                toast::draw(f, normal::toast_area(f.size()), &self.state.toasts);
+            })
+            .change_context(AppError::Draw)?;
+        Ok(())
+    }
+
     pub fn redraw(&mut self) -> Result<(), AppError> {
         self.term
             .draw(|f| f.render_widget(tui::widgets::Clear, f.size()))
@@ -107,177 +360,1995 @@ impl<B: tui::backend::Backend + io::Write> App<B> {
         // testing only
         match event.into() {
             Event::Input(k) => {
+                // keys bound by scripts come before the built in ones
+                if matches!(self.state.mode, Mode::Normal) {
+                    match self.lua.keymap(k) {
+                        Some(Binding::Action(a)) => {
+                            if let Some(msg) = Msg::from_name(&a, &self.state) {
+                                self.handle(msg)?;
+                            }
+                            return Ok(());
+                        }
+                        Some(Binding::Function) => return self.script(|s| s.call_key(k)),
+                        None => {}
+                    }
+                    let action = self
+                        .state
+                        .options
+                        .actions
+                        .iter()
+                        .find(|a| a.key() == Some(k));
+                    if let Some(a) = action {
+                        return self.handle(Msg::Action(a.name.clone()));
+                    }
+                }
+
                 let msg = match &self.state.mode {
                     Mode::Normal => crate::input::normal::handle(k, &mut self.state),
-                    Mode::Delete(item) => crate::input::delete::handle(k, &item),
+                    Mode::Delete(item) => crate::input::delete::handle(k, item),
+                    Mode::Jobs => crate::input::jobs::handle(k),
+                    Mode::Error(_) => Some(Msg::ChangeMode(Mode::Normal)),
+                    Mode::Command => crate::input::command::handle(k, &mut self.state),
+                    Mode::Downloads => {
+                        let msg = crate::input::downloads::handle(k, &mut self.state);
+                        let last = self.downloads.list.len().saturating_sub(1);
+                        self.state.dl_selected = self.state.dl_selected.min(last);
+                        msg
+                    }
+                    Mode::Import => crate::input::import::handle(k, &mut self.state),
+                    Mode::Reader => crate::input::reader::handle(k, &mut self.state),
+                    Mode::Gone(_) => crate::input::gone::handle(k),
+                    Mode::Search => crate::input::search::handle(k, &mut self.state),
+                    Mode::Term => match &self.state.term {
+                        Some(t) => crate::input::term::handle(k, &t.parser()),
+                        None => None,
+                    },
                 };
                 if let Some(msg) = msg {
                     log::info!("msg: {:?}", msg);
                     self.handle(msg)?;
                 }
             }
-            Event::Click(_) => {}
-            Event::Resize(_, _) => self.state.draw = true,
-            Event::GainedFocus(_) => {}
+            Event::Resize => self.state.draw = true,
+            Event::Other => {}
+        }
+        Ok(())
+    }
+
+    /// Shows the report to the user so the app can keep running.
+    pub fn report(&mut self, report: Report<AppError>) {
+        log::error!("{:?}", report);
+        // a program in the pane still has the input
+        if !matches!(self.state.mode, Mode::Term) {
+            self.state.mode = Mode::Error(error::lines(&report));
+        } else {
+            self.state.toasts.push(Level::Error, report.to_string());
+        }
+        self.state.draw = true;
+    }
+
+    /// How long to wait for an event before calling [`App::tick`].
+    pub fn timeout(&self) -> Duration {
+        let tick = if self.state.term.is_some() {
+            // output from the pane should show up without waiting on input
+            Duration::from_millis(20)
+        } else if self.downloads.busy()
+            || self.thumbs.busy()
+            || self.copies.busy()
+            || self.archives.busy()
+            || self.feeds.busy()
+            || self.mpv.as_ref().is_some_and(Mpv::busy)
+            || (self.mpd.is_some() && self.state.playing.is_some())
+        {
+            Duration::from_millis(250)
+        } else {
+            Duration::from_secs(3)
+        };
+        match self.state.toasts.next_expiry(Instant::now()) {
+            Some(t) => tick.min(t),
+            None => tick,
+        }
+    }
+
+    /// Work that is done each time through the event loop.
+    pub fn tick(&mut self) -> Result<(), AppError> {
+        if self.state.toasts.expire(Instant::now()) {
+            self.state.draw = true;
+        }
+
+        for (name, status) in crate::job::reap(&mut self.task) {
+            let msg = format!("`{}` failed with {}", name, status);
+            self.state.toasts.push(Level::Error, msg);
+            self.state.draw = true;
+        }
+        let outs: Vec<_> = self.task.iter_mut().filter_map(Job::output).collect();
+        for (to, out) in outs {
+            self.finish_capture(to, out)?;
+        }
+        if self.thumbs.tick() {
+            self.state.draw = true;
+        }
+        for saved in self.copies.tick() {
+            self.finish_copy(saved)?;
+        }
+        for done in self.archives.tick() {
+            self.finish_archive(done)?;
+        }
+        let every = Duration::from_secs(self.state.options.feed_refresh * 60);
+        if !every.is_zero() && self.feeds_at.is_none_or(|t| t.elapsed() >= every) {
+            self.feeds_at = Some(Instant::now());
+            for (tab, subs) in &self.luma.feeds {
+                subs.iter().for_each(|s| self.feeds.fetch(tab, &s.url));
+            }
+        }
+        for (tab, url, res) in self.feeds.tick() {
+            self.finish_feed(&tab, &url, res)?;
+        }
+        let mut playing = None;
+        if let Some(mpv) = &mut self.mpv {
+            let played = mpv.tick();
+            playing = mpv.playing().cloned();
+            for (tabb, link) in played {
+                self.set_played(tabb, &link);
+            }
+        }
+        // mpv is shown over MPD when both are playing
+        if let Some(watch) = &mut self.mpd_watch {
+            match watch.tick() {
+                Ok(()) if playing.is_none() => playing = watch.playing.clone(),
+                Ok(()) => {}
+                Err(e) => {
+                    self.mpd = None;
+                    self.mpd_watch = None;
+                    let msg = format!("lost MPD: {}", e);
+                    self.state.toasts.push(Level::Error, msg);
+                }
+            }
+        }
+        if playing != self.state.playing {
+            self.state.playing = playing;
+            self.state.draw = true;
+        }
+        let limit = self.state.options.download_jobs;
+        for (tab, link, status) in self.downloads.tick(limit) {
+            self.finish_download(&tab, link, status)?;
+        }
+        if matches!(self.state.mode, Mode::Jobs)
+            || (matches!(self.state.mode, Mode::Downloads) && self.downloads.busy())
+        {
+            // keep the running times and progress up to date
+            self.state.draw = true;
+        }
+
+        let Some(term) = &mut self.state.term else {
+            return Ok(());
+        };
+
+        if term.take_dirty() {
+            self.state.draw = true;
+        }
+
+        if let Some(status) = term.try_wait() {
+            log::debug!("{} exited with {}", term.name, status);
+            self.state.term = None;
+            if matches!(self.state.mode, Mode::Term) {
+                self.state.mode = Mode::Normal;
+            }
+            self.state.draw = true;
+
+            if let Some(edit) = self.edit.take() {
+                if status.success() {
+                    self.finish_edit(edit)?;
+                } else {
+                    let msg = format!("editor exited with {}, edit cancelled", status);
+                    self.state.toasts.push(Level::Info, msg);
+                }
+            }
         }
         Ok(())
     }
 
     pub fn handle(&mut self, msg: Msg) -> Result<(), AppError> {
         match msg {
-            Msg::Quit => self.state.quit = true,
+            Msg::Quit => {
+                self.state.quit = true;
+                self.script(|s| s.fire("quit", ()))?;
+            }
             Msg::Edit => self.edit()?,
+            Msg::Tag => self.tag()?,
+            Msg::ScrollPreview(by) => self.state.scroll_preview(by),
+            Msg::Note(tab) => self.edit_note(tab)?,
+            Msg::Read => self.read()?,
+            Msg::SaveReadable => self.save_copy(false),
+            Msg::CloseReader => {
+                if let Some(r) = self.state.reader.take() {
+                    let at = r.line();
+                    let index = self.find_link(r.tabb, &r.link);
+                    if let Some(l) = index.map(|i| &mut self.luma.tabs[r.tabb].1[i]) {
+                        if l.read_at != at {
+                            l.read_at = at;
+                            self.state.dirty = true;
+                        }
+                    }
+                }
+                self.state.mode = Mode::Normal;
+                self.state.draw = true;
+            }
             Msg::MoveDown(s) => {
-                self.state.selected = self.state.selected.saturating_add(s);
-                self.state.selected = self.state.selected.min(
-                    self.luma
-                        .tabs
-                        .get(self.state.tabb)
-                        .unwrap()
-                        .1
-                        .len()
-                        .saturating_sub(1),
-                );
+                let visible = self.visible();
+                if let Some(last) = visible.len().checked_sub(1) {
+                    let sel = self.state.selected;
+                    let at = visible.iter().position(|&i| i >= sel).unwrap_or(last);
+                    self.state.selected = visible[at.saturating_add(s).min(last)];
+                }
                 self.state.draw = true;
             }
             Msg::MoveUp(s) => {
-                self.state.selected = self.state.selected.saturating_sub(s);
+                let visible = self.visible();
+                if !visible.is_empty() {
+                    let sel = self.state.selected;
+                    let at = visible.iter().rposition(|&i| i <= sel).unwrap_or(0);
+                    self.state.selected = visible[at.saturating_sub(s)];
+                }
                 self.state.draw = true;
             }
             Msg::ChangeMode(m) => {
+                if matches!(m, Mode::Command) {
+                    self.state.cmdline.reset();
+                }
                 self.state.mode = m;
                 self.state.draw = true;
             }
             Msg::Open => {
-                if let Some(s) = self.luma.get_selected(&self.state) {
-                    match crate::prelude::LINK_OPENER.run(s.link.as_str()) {
-                        Ok(c) => self.task.push(c),
-                        Err(e) => log::warn!("failed to open link: {:?}", e),
+                if self.shown().is_some() {
+                    self.set_unread(self.state.tabb, self.state.selected, false);
+                }
+                if let Some(link) = self.shown().cloned() {
+                    // links with a snapshot are checked first, the snapshot
+                    // is offered if the page is down
+                    if link.archive.is_some() && is_web(&link.link) {
+                        let msg = format!("checking {} is up", link.name);
+                        self.state.toasts.push(Level::Info, msg);
+                        self.state.draw = true;
+                        self.archives.check(self.state.tabb, link);
+                    } else {
+                        self.open_link(link)?;
+                    }
+                }
+            }
+            Msg::OpenLive => {
+                self.state.mode = Mode::Normal;
+                self.state.draw = true;
+                if let Some(link) = self.shown().cloned() {
+                    self.open_link(link)?;
+                }
+            }
+            Msg::OpenArchive => self.open_archive()?,
+            Msg::Prompt(text) => {
+                self.state.cmdline.reset();
+                text.chars().for_each(|c| self.state.cmdline.insert(c));
+                self.state.mode = Mode::Command;
+                self.state.draw = true;
+            }
+            Msg::GoToHit => self.go_to_hit(),
+            Msg::RefreshFeeds => {
+                let tab = self.luma.tabs.get(self.state.tabb).map(|t| t.0.clone());
+                let urls = tab.as_ref().and_then(|t| self.luma.feeds.get(t));
+                let msg = match (tab.as_ref(), urls) {
+                    (Some(tab), Some(urls)) if !urls.is_empty() => {
+                        urls.iter().for_each(|s| self.feeds.fetch(tab, &s.url));
+                        format!("refreshing {} feeds", urls.len())
+                    }
+                    _ => "the tab has no feeds, add one with :feed add".to_owned(),
+                };
+                self.state.toasts.push(Level::Info, msg);
+                self.state.draw = true;
+            }
+            Msg::MarkAllRead => {
+                if let Some((_, links)) = self.luma.tabs.get_mut(self.state.tabb) {
+                    for l in links.iter_mut().filter(|l| l.unread) {
+                        l.unread = false;
+                        self.state.dirty = true;
                     }
                 }
+                self.state.draw = true;
+            }
+            Msg::Play => self.play(false)?,
+            Msg::Enqueue => self.play(true)?,
+            Msg::Pause => self.control(Mpv::pause, mpd::Client::pause),
+            Msg::Next => self.control(Mpv::next, mpd::Client::next),
+            Msg::Seek(secs) => self.control(|m| m.seek(secs), |c| c.seek(secs)),
+            Msg::TogglePlayed => {
+                let tabb = self.state.tabb;
+                let sel = self.shown_at();
+                let tab = self.luma.tabs.get_mut(tabb);
+                if let Some(l) = sel.and_then(|s| tab.and_then(|t| t.1.get_mut(s))) {
+                    l.played = !l.played;
+                    self.state.dirty = true;
+                    self.state.draw = true;
+                }
+            }
+            Msg::ToggleRead => {
+                if let Some(l) = self.shown() {
+                    let unread = !l.unread;
+                    self.set_unread(self.state.tabb, self.state.selected, unread);
+                }
+            }
+            Msg::Archive => {
+                let sel = self.state.selected;
+                if self.shown().is_some() {
+                    self.archive(&[sel]);
+                }
+            }
+            Msg::Action(name) => self.action(&name)?,
+            Msg::Command(line) => {
+                self.state.mode = Mode::Normal;
+                self.state.draw = true;
+                if let Err(e) = self.state.cmdline.save(&history_path()) {
+                    log::warn!("could not save command history: {}", e);
+                }
+                match crate::command::parse(&line) {
+                    Ok(cmd) => self.command(cmd)?,
+                    Err(e) => self.state.toasts.push(Level::Error, e),
+                }
+            }
+            Msg::Complete(back) => {
+                let tabs: Vec<&str> = self.luma.tabs.iter().map(|t| t.0.as_str()).collect();
+                let actions: Vec<&str> = (self.state.options.actions.iter())
+                    .map(|a| a.name.as_str())
+                    .collect();
+                let find = |line: &str| crate::command::complete(line, &tabs, &actions);
+                self.state.cmdline.complete(back, find);
+                self.state.draw = true;
+            }
+            Msg::Rescan => {
+                let Some((tab, _)) = self.luma.tabs.get(self.state.tabb) else {
+                    return Ok(());
+                };
+                let dirs: Vec<_> = self
+                    .luma
+                    .dirs
+                    .iter()
+                    .filter(|d| d.tab == *tab)
+                    .cloned()
+                    .collect();
+                if dirs.is_empty() {
+                    let msg = format!("no directories were imported into {}", tab);
+                    self.state.toasts.push(Level::Warn, msg);
+                    self.state.draw = true;
+                } else {
+                    self.import(&dirs)?;
+                }
+            }
+            Msg::ImportTab(back) => {
+                let tabs = self.luma.tabs.len();
+                if let Some(c) = self.state.candidates.get_mut(self.state.cand_selected) {
+                    c.tabb = if back {
+                        (c.tabb + tabs - 1) % tabs
+                    } else {
+                        (c.tabb + 1) % tabs
+                    };
+                }
+                self.state.draw = true;
+            }
+            Msg::FinishImport => {
+                let kept: Vec<_> = self.state.candidates.drain(..).filter(|c| c.keep).collect();
+                self.state.mode = Mode::Normal;
+                self.state.draw = true;
+                let msg = format!("imported {} links", kept.len());
+                for c in kept {
+                    self.add_link(c.tabb, c.link)?;
+                }
+                self.state.toasts.push(Level::Info, msg);
+            }
+            Msg::Download => {
+                let sel = self.state.selected;
+                if self.shown().is_some() {
+                    self.download(&[sel])?;
+                }
+            }
+            Msg::CancelDownload => {
+                if let Some(d) = self.downloads.list.get(self.state.dl_selected) {
+                    d.cancel();
+                }
+                self.state.draw = true;
+            }
+            Msg::RetryDownload => {
+                self.downloads.retry(self.state.dl_selected);
+                self.state.draw = true;
+            }
+            Msg::ClearDownloads => {
+                self.downloads.clear();
+                self.state.dl_selected = 0;
+                self.state.draw = true;
+            }
+            Msg::ClearJobs => {
+                self.task.retain(Job::running);
+                self.state.draw = true;
+            }
+            Msg::Forward(bytes) => {
+                if let Some(t) = &self.state.term {
+                    t.write(&bytes).change_context(AppError::Term)?;
+                }
             }
             Msg::Delete => {
-                self.delete();
                 self.state.mode = Mode::Normal;
                 self.state.draw = true;
+                self.delete()?;
             }
+            Msg::Save => self.save()?,
 
             // -------------- changing tabs ------------------
             Msg::SelectTab(t) => {
                 if t == self.state.tabb {
                     return Ok(());
                 }
-                if let Some(tab) = self.luma.tabs.get(t) {
+                if t < self.luma.tabs.len() {
                     self.state.tabb = t;
                     self.state.draw = true;
-
-                    if self.state.selected >= tab.1.len() {
-                        self.state.selected = tab.1.len() - 1;
-                    }
+                    self.select_visible();
                 }
             }
             // -----------------------------------------------
             //
             Msg::Add => {
-                if let Some(tabb) = self.luma.tabs.get_mut(self.state.tabb) {
-                    let mut l = Link::default();
-                    edit_item(&mut l)?;
-                    self.state.draw = true;
-                    tabb.1.push(l);
+                if self.state.tabb < self.luma.tabs.len() {
+                    let target = Target::NewLink {
+                        tabb: self.state.tabb,
+                    };
+                    self.open_editor(Edit::new(target, &Link::default())?)?;
                 }
             }
             Msg::RenameTab => {
-                if let Some(tabb) = self.luma.tabs.get_mut(self.state.tabb) {
-                    let name = &mut tabb.0;
-                    edit_item(name)?;
-                    self.state.draw = true;
+                if let Some(tabb) = self.luma.tabs.get(self.state.tabb) {
+                    let target = Target::TabName {
+                        tabb: self.state.tabb,
+                    };
+                    self.open_editor(Edit::new(target, &tabb.0)?)?;
                 }
             }
-            Msg::DeleteTab => {
-                self.luma.tabs.remove(self.state.tabb);
-                self.state.tabb = self.state.tabb.saturating_sub(1);
-                self.state.draw = true;
-                self.state.mode = Mode::Normal;
-            }
+            Msg::DeleteTab => self.delete_tab()?,
             Msg::AddTab => {
-                let mut name = String::new();
-                edit_item(&mut name)?;
-                self.luma.tabs.push((name, Vec::new()));
-                self.state.tabb = self.luma.tabs.len() - 1;
-                self.state.draw = true;
+                self.open_editor(Edit::new(Target::NewTab, &String::new())?)?;
             }
         }
         Ok(())
     }
 
-    fn delete(&mut self) {
-        if let Some(tabb) = self.luma.tabs.get_mut(self.state.tabb) {
-            if self.state.selected >= tabb.1.len() {
-                return;
-            }
-            tabb.1.remove(self.state.selected);
-            self.state.selected = self.state.selected.saturating_sub(1);
+    fn visible(&self) -> Vec<usize> {
+        match self.luma.tabs.get(self.state.tabb) {
+            Some(t) => self.state.visible(&t.1),
+            None => Vec::new(),
         }
     }
 
-    pub fn edit(&mut self) -> Result<(), AppError> {
-        if let Some(link) = self.luma.get_mut_selected(&self.state) {
-            deinit(self.term.backend_mut());
-            let res = edit_item(link);
-            init(self.term.backend_mut());
+    /// The selected link, if the filter shows it.
+    fn shown(&self) -> Option<&Link> {
+        let shown = self.visible().contains(&self.state.selected);
+        self.luma.get_selected(&self.state).filter(|_| shown)
+    }
 
-            res?;
-            self.redraw()?;
-        }
-        Ok(())
+    /// Where the selected link is, if the filter shows it.
+    fn shown_at(&self) -> Option<usize> {
+        self.shown().map(|_| self.state.selected)
     }
 
-    pub fn finish(self) -> (Luma, tui::Terminal<B>) {
-        (self.luma, self.term)
+    /// Moves the selection to a link that is shown if it is not on one.
+    fn select_visible(&mut self) {
+        let visible = self.visible();
+        if !visible.contains(&self.state.selected) {
+            let sel = self.state.selected;
+            let next = visible.iter().find(|&&i| i >= sel).or(visible.last());
+            self.state.selected = next.copied().unwrap_or(0);
+        }
     }
-}
 
-/// Edits an item
-fn edit_item<T: Serialize + DeserializeOwned>(item: &mut T) -> Result<(), AppError> {
-    let mut f = tempfile::NamedTempFile::new()
-        .change_context(AppError::Edit)
-        .attach_printable("could not create temp file")?;
+    /// Finds a tab from its number, counted from 1, or its name.
+    fn find_tab(&self, tab: &str) -> Option<usize> {
+        match tab.parse::<usize>() {
+            Ok(n) => n.checked_sub(1).filter(|&i| i < self.luma.tabs.len()),
+            Err(_) => self.luma.tabs.iter().position(|t| t.0 == tab),
+        }
+    }
 
-    let p = f.path();
-    let file = p.to_str().unwrap().to_owned();
+    /// Runs a command from the command line.
+    fn command(&mut self, cmd: Command) -> Result<(), AppError> {
+        let no_tab = |s: &mut State, t: &str| {
+            s.toasts
+                .push(Level::Error, format!("there is no tab {}", t));
+        };
 
-    log::debug!("tempfile path: {:?}", file);
+        match cmd {
+            Command::NewTab(name) => self.new_tab(name)?,
+            Command::RenameTab(name) => self.rename_tab(self.state.tabb, name)?,
+            Command::DeleteTab => {
+                if self.state.tabb < self.luma.tabs.len() {
+                    self.handle(Msg::DeleteTab)?;
+                }
+            }
+            Command::SelectTab(t) => match self.find_tab(&t) {
+                Some(i) => self.handle(Msg::SelectTab(i))?,
+                None => no_tab(&mut self.state, &t),
+            },
+            Command::Sort(field, rev) => {
+                let sel = self.state.selected;
+                if let Some((_, links)) = self.luma.tabs.get_mut(self.state.tabb) {
+                    let picked = links.get(sel).cloned();
+                    // links without the field go last either way
+                    links.sort_by_cached_key(|l| {
+                        let v = field.get(l).map(str::to_lowercase);
+                        (v.is_none(), v)
+                    });
+                    if rev {
+                        let some = links.iter().take_while(|l| field.get(l).is_some()).count();
+                        links[..some].reverse();
+                    }
+                    if let Some(p) = picked {
+                        self.state.selected = links.iter().position(|l| *l == p).unwrap_or(0);
+                    }
+                    self.state.dirty = true;
+                }
+            }
+            Command::Move(t) => match self.find_tab(&t) {
+                Some(to) => self.move_link(to)?,
+                None => no_tab(&mut self.state, &t),
+            },
+            Command::Filter(f) => {
+                self.state.filter = f;
+                self.select_visible();
+            }
+            Command::Write => self.save()?,
+            Command::Quit(force) => {
+                self.state.discard = force;
+                self.handle(Msg::Quit)?;
+            }
+            Command::WriteQuit => {
+                self.save()?;
+                self.handle(Msg::Quit)?;
+            }
+            Command::Export(format, path) => {
+                let path = crate::config::expand(&path.to_string_lossy());
+                let Some((name, links)) = self.luma.tabs.get(self.state.tabb) else {
+                    return Ok(());
+                };
+                fs::File::create(&path)
+                    .and_then(|f| crate::export::write(f, format, name, links))
+                    .change_context(AppError::Save)
+                    .attach_printable_lazy(|| format!("could not export to {}", path.display()))?;
+                let msg = format!("exported {} to {}", name, path.display());
+                self.state.toasts.push(Level::Info, msg);
+            }
+            Command::Download(all) => {
+                let which = if all {
+                    self.visible()
+                } else {
+                    self.shown_at().into_iter().collect()
+                };
+                self.download(&which)?;
+            }
+            Command::Search(query) => self.search(query),
+            Command::AddFeed(url) => {
+                if self.luma.tabs.is_empty() {
+                    let Some(tabb) = self.push_tab("feeds".to_owned())? else {
+                        return Ok(());
+                    };
+                    self.state.tabb = tabb;
+                }
+                let tab = self.luma.tabs[self.state.tabb].0.clone();
+                let subs = self.luma.feeds.entry(tab.clone()).or_default();
+                if !subs.iter().any(|s| s.url == url) {
+                    subs.push(feed::Subscription::new(url.clone()));
+                    self.state.dirty = true;
+                }
+                self.feeds.fetch(&tab, &url);
+                let msg = format!("subscribed {} to {}", tab, url);
+                self.state.toasts.push(Level::Info, msg);
+            }
+            Command::RemoveFeed(url) => {
+                let tab = self.luma.tabs.get(self.state.tabb).map(|t| t.0.clone());
+                let urls = tab.and_then(|t| self.luma.feeds.get_mut(&t));
+                match urls.filter(|u| u.iter().any(|s| s.url == url)) {
+                    Some(urls) => {
+                        urls.retain(|s| s.url != url);
+                        self.luma.feeds.retain(|_, u| !u.is_empty());
+                        self.state.dirty = true;
+                    }
+                    None => {
+                        let msg = format!("the tab is not subscribed to {}", url);
+                        self.state.toasts.push(Level::Warn, msg);
+                    }
+                }
+            }
+            Command::ImportOpml(path) => {
+                let path = crate::config::expand(&path.to_string_lossy());
+                let text = fs::read_to_string(&path)
+                    .change_context(AppError::Import)
+                    .attach_printable_lazy(|| format!("could not read {}", path.display()))?;
+                let subs = match feed::read_opml(&text) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        let msg = format!("could not import {}: {}", path.display(), e);
+                        self.state.toasts.push(Level::Error, msg);
+                        return Ok(());
+                    }
+                };
+                let mut added = 0;
+                for (mut tab, url) in subs {
+                    // outlines are tab names even when they look like numbers
+                    if !self.luma.tabs.iter().any(|t| t.0 == tab) {
+                        match self.push_tab(tab)? {
+                            Some(tabb) => tab = self.luma.tabs[tabb].0.clone(),
+                            None => continue,
+                        }
+                    }
+                    let subs = self.luma.feeds.entry(tab.clone()).or_default();
+                    if !subs.iter().any(|s| s.url == url) {
+                        subs.push(feed::Subscription::new(url.clone()));
+                        self.feeds.fetch(&tab, &url);
+                        added += 1;
+                    }
+                }
+                self.state.dirty |= added > 0;
+                let msg = format!("subscribed to {} feeds", added);
+                self.state.toasts.push(Level::Info, msg);
+            }
+            Command::ExportOpml(path) => {
+                let path = crate::config::expand(&path.to_string_lossy());
+                fs::write(&path, feed::write_opml(&self.luma.feeds))
+                    .change_context(AppError::Save)
+                    .attach_printable_lazy(|| format!("could not export to {}", path.display()))?;
+                let msg = format!("exported the feeds to {}", path.display());
+                self.state.toasts.push(Level::Info, msg);
+            }
+            Command::MpdAdd(all) => {
+                let which = if all {
+                    self.visible()
+                } else {
+                    self.shown_at().into_iter().collect()
+                };
+                self.mpd_add(&which);
+            }
+            Command::MpdStatus => {
+                let Some(mpd) = self.mpd() else {
+                    return Ok(());
+                };
+                let msg = match mpd.status() {
+                    Ok(Some(p)) if p.paused => format!("MPD is paused on {}", p.title),
+                    Ok(Some(p)) => format!("MPD is playing {}", p.title),
+                    Ok(None) => "MPD is stopped".to_owned(),
+                    Err(e) => format!("MPD failed: {}", e),
+                };
+                self.state.toasts.push(Level::Info, msg);
+            }
+            Command::MpdLibrary => {
+                let Some(mpd) = self.mpd() else {
+                    return Ok(());
+                };
+                match mpd.library() {
+                    Ok(songs) => {
+                        let added = self.import_songs("library", songs)?;
+                        let msg = format!("imported {} songs from MPD", added);
+                        self.state.toasts.push(Level::Info, msg);
+                    }
+                    Err(e) => {
+                        let msg = format!("could not read MPD's library: {}", e);
+                        self.state.toasts.push(Level::Error, msg);
+                    }
+                }
+            }
+            Command::MpdPlaylists => {
+                let Some(mpd) = self.mpd() else {
+                    return Ok(());
+                };
+                let lists = mpd.playlists().and_then(|names| {
+                    (names.into_iter())
+                        .map(|n| mpd.playlist(&n).map(|s| (n, s)))
+                        .collect::<std::result::Result<Vec<_>, _>>()
+                });
+                match lists {
+                    Ok(lists) => {
+                        let msg = format!("imported {} playlists from MPD", lists.len());
+                        for (name, songs) in lists {
+                            self.import_songs(&name, songs)?;
+                        }
+                        self.state.toasts.push(Level::Info, msg);
+                    }
+                    Err(e) => {
+                        let msg = format!("could not read MPD's playlists: {}", e);
+                        self.state.toasts.push(Level::Error, msg);
+                    }
+                }
+            }
+            Command::Archive(all) => {
+                let which = if all {
+                    self.visible()
+                } else {
+                    self.shown_at().into_iter().collect()
+                };
+                self.archive(&which);
+            }
+            Command::ImportPlaylist(path) => {
+                let path = crate::config::expand(&path.to_string_lossy());
+                let text = fs::read_to_string(&path)
+                    .change_context(AppError::Import)
+                    .attach_printable_lazy(|| format!("could not read {}", path.display()))?;
+                let (name, links) = match crate::playlist::read(&path, &text) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        let msg = format!("could not import {}: {}", path.display(), e);
+                        self.state.toasts.push(Level::Error, msg);
+                        return Ok(());
+                    }
+                };
+                // the playlist gets a tab of its own
+                let mut tab = name.clone();
+                let mut n = 1;
+                while self.luma.tabs.iter().any(|t| t.0 == tab) {
+                    n += 1;
+                    tab = format!("{} {}", name, n);
+                }
+                let Some(tabb) = self.push_tab(tab)? else {
+                    return Ok(());
+                };
+                let tab = self.luma.tabs[tabb].0.clone();
+                self.state.tabb = tabb;
+                self.state.selected = 0;
+                let mut added = 0;
+                for link in links {
+                    if self.add_link(tabb, link)? {
+                        added += 1;
+                    }
+                }
+                let msg = format!("imported {} tracks into {}", added, tab);
+                self.state.toasts.push(Level::Info, msg);
+            }
+            Command::Import(path) => {
+                let path = crate::config::expand(&path.to_string_lossy());
+                let text = fs::read_to_string(&path)
+                    .change_context(AppError::Import)
+                    .attach_printable_lazy(|| format!("could not read {}", path.display()))?;
+                if self.luma.tabs.is_empty() {
+                    let Some(tabb) = self.push_tab("links".to_owned())? else {
+                        return Ok(());
+                    };
+                    self.state.tabb = tabb;
+                }
 
-    yaml::to_writer(&mut f, item).unwrap();
+                let tabb = self.state.tabb;
+                let have = &self.luma.tabs[tabb].1;
+                self.state.candidates = crate::import::urls(&text)
+                    .into_iter()
+                    .map(|link| Candidate {
+                        keep: !have.iter().any(|l| l.link == link.link),
+                        link,
+                        tabb,
+                    })
+                    .collect();
+                self.state.cand_selected = 0;
+                if self.state.candidates.is_empty() {
+                    let msg = format!("no links were found in {}", path.display());
+                    self.state.toasts.push(Level::Warn, msg);
+                } else {
+                    self.state.mode = Mode::Import;
+                }
+            }
+            Command::ImportDir {
+                path,
+                include,
+                exclude,
+            } => {
+                let path = crate::config::expand(&path.to_string_lossy());
+                let path = path.canonicalize().unwrap_or(path);
+                if self.luma.tabs.is_empty() {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    let Some(tabb) = self.push_tab(name.into_owned())? else {
+                        return Ok(());
+                    };
+                    self.state.tabb = tabb;
+                }
+                let dir = ImportDir {
+                    tab: self.luma.tabs[self.state.tabb].0.clone(),
+                    path,
+                    include,
+                    exclude,
+                };
+                let dirs = &mut self.luma.dirs;
+                dirs.retain(|d| d.tab != dir.tab || d.path != dir.path);
+                dirs.push(dir.clone());
+                self.state.dirty = true;
+                self.import(&[dir])?;
+            }
+            Command::Action(name) => match Msg::from_name(&name, &self.state) {
+                Some(msg) => self.handle(msg)?,
+                None => {
+                    let msg = format!("unknown command {:?}", name);
+                    self.state.toasts.push(Level::Error, msg);
+                }
+            },
+        }
+        self.state.draw = true;
+        Ok(())
+    }
 
-    let mut ch = crate::prelude::TEXT_OPENER
-        .spawn(&file)
-        .change_context(AppError::Edit)
-        .attach_printable("could spawn editor")?;
+    fn delete(&mut self) -> Result<(), AppError> {
+        let tabb = self.state.tabb;
+        let Some(link) = self.shown().cloned() else {
+            return Ok(());
+        };
+        if self.pre(Hook::Delete, Some(tabb), link)?.is_none() {
+            return Ok(());
+        }
 
-    let e = ch
-        .wait()
-        .change_context(AppError::Edit)
-        .attach_printable("could not wait for editor")?;
+        // the hooks could have moved things around
+        let Some(links) = self.luma.tabs.get_mut(tabb) else {
+            return Ok(());
+        };
+        if self.state.selected >= links.1.len() {
+            return Ok(());
+        }
+        let link = links.1.remove(self.state.selected);
+        self.state.selected = self.state.selected.saturating_sub(1);
+        self.select_visible();
+        self.state.dirty = true;
 
-    if e.success() {
-        io::Seek::seek(&mut f, io::SeekFrom::Start(0))
-            .change_context(AppError::Edit)
-            .attach_printable("could not seek to start of file")?;
+        self.post(Hook::Delete, Some(tabb), link)
+    }
 
-        let nval = yaml::from_reader(f)
-            .change_context(AppError::Edit)
-            .attach_printable("input was no of valid form")?;
+    /// Deletes the selected tab and its links if the delete hooks let every
+    /// one of them go.
+    fn delete_tab(&mut self) -> Result<(), AppError> {
+        self.state.mode = Mode::Normal;
+        self.state.draw = true;
+        let tabb = self.state.tabb;
+        let Some((_, links)) = self.luma.tabs.get(tabb) else {
+            return Ok(());
+        };
+        for link in links.clone() {
+            if let Flow::Stop(why) = self.run_pre(Hook::Delete, Some(tabb), link)? {
+                let msg = format!("{} stopped by {}", Hook::Delete, why);
+                self.state.toasts.push(Level::Warn, msg);
+                return Ok(());
+            }
+        }
 
-        *item = nval;
+        // the hooks could have moved things around
+        if tabb >= self.luma.tabs.len() {
+            return Ok(());
+        }
+        let links = self.luma.tabs[tabb].1.clone();
+        self.luma.remove_tab(tabb);
+        self.state.tabb = tabb.saturating_sub(1);
+        self.state.dirty = true;
+        for link in links {
+            self.post(Hook::Delete, None, link)?;
+        }
+        Ok(())
     }
-    Ok(())
-}
-
-pub fn init<B: io::Write>(write: &mut B) {
-    crossterm::terminal::enable_raw_mode().unwrap();
-    crossterm::execute!(write, crossterm::terminal::EnterAlternateScreen).unwrap();
-}
+
+    /// Moves the selected link to the end of the tab. It is deleted from its
+    /// tab and added to the other, so the hooks of both are run.
+    fn move_link(&mut self, to: usize) -> Result<(), AppError> {
+        let tabb = self.state.tabb;
+        if to == tabb {
+            return Ok(());
+        }
+        let Some(link) = self.shown().cloned() else {
+            return Ok(());
+        };
+        if self.pre(Hook::Delete, Some(tabb), link.clone())?.is_none() {
+            return Ok(());
+        }
+        let Some(moved) = self.pre(Hook::Add, Some(to), link.clone())? else {
+            return Ok(());
+        };
+
+        // the hooks could have moved things around
+        let Some(index) = self.find_link(tabb, &link) else {
+            return Ok(());
+        };
+        let Some((_, links)) = self.luma.tabs.get_mut(to) else {
+            return Ok(());
+        };
+        links.push(moved.clone());
+        self.luma.tabs[tabb].1.remove(index);
+        self.state.selected = index.saturating_sub(1);
+        self.state.dirty = true;
+        self.select_visible();
+        self.post(Hook::Delete, Some(tabb), link)?;
+        self.post(Hook::Add, Some(to), moved)
+    }
+
+    /// Runs the action from the config on the selected link.
+    fn action(&mut self, name: &str) -> Result<(), AppError> {
+        let Some(action) = self.state.options.action(name).cloned() else {
+            let msg = format!("there is no action named {}", name);
+            self.state.toasts.push(Level::Warn, msg);
+            self.state.draw = true;
+            return Ok(());
+        };
+        let tabb = self.state.tabb;
+        let Some(link) = self.shown().cloned() else {
+            return Ok(());
+        };
+        let Some(cmd) = action.command(&link, &self.luma.tabs[tabb].0) else {
+            return Ok(());
+        };
+
+        let Some(field) = action.capture else {
+            return self.open(&cmd, None);
+        };
+        match cmd.exec(true) {
+            Ok(c) => {
+                let to = Capture { tabb, link, field };
+                self.task.push(Job::capture(cmd.to_string(), c, to));
+            }
+            Err(e) => {
+                log::warn!("failed to run action: {:?}", e);
+                let msg = format!("failed to run {}", cmd);
+                self.state.toasts.push(Level::Error, msg);
+                self.state.draw = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Puts the output of an action into the link it was run on.
+    fn finish_capture(&mut self, to: Capture, out: io::Result<String>) -> Result<(), AppError> {
+        let out = match out {
+            Ok(o) => o,
+            Err(e) => {
+                let msg = format!("could not read the output of an action: {}", e);
+                self.state.toasts.push(Level::Error, msg);
+                self.state.draw = true;
+                return Ok(());
+            }
+        };
+
+        let Some(index) = self.find_link(to.tabb, &to.link) else {
+            let msg = format!("{} changed before its action finished", to.link.name);
+            self.state.toasts.push(Level::Warn, msg);
+            self.state.draw = true;
+            return Ok(());
+        };
+
+        match crate::config::capture(&to.link, &to.field, &out) {
+            Ok(l) => self.replace_link(to.tabb, index, l),
+            Err(errs) => {
+                let msg = format!(
+                    "action gave back an invalid {}: {}",
+                    to.field,
+                    errs.join(", ")
+                );
+                self.state.toasts.push(Level::Error, msg);
+                self.state.draw = true;
+                Ok(())
+            }
+        }
+    }
+
+    /// Finds where a link is now, it could have moved since it was looked at.
+    fn find_link(&self, tabb: usize, link: &Link) -> Option<usize> {
+        let links = &self.luma.tabs.get(tabb)?.1;
+        links.iter().position(|l| l == link)
+    }
+
+    /// Scans the directories and brings the selected tab up to date with them.
+    fn import(&mut self, dirs: &[ImportDir]) -> Result<(), AppError> {
+        let tabb = self.state.tabb;
+        self.state.draw = true;
+        for dir in dirs {
+            let Some((_, links)) = self.luma.tabs.get_mut(tabb) else {
+                return Ok(());
+            };
+            let mut scan = match dir.scan(links) {
+                Ok(s) => s,
+                Err(e) => {
+                    let msg = format!("could not import {}: {}", dir.path.display(), e);
+                    self.state.toasts.push(Level::Error, msg);
+                    continue;
+                }
+            };
+            let msg = format!("{}: {}", dir.path.display(), scan);
+            self.state.toasts.push(Level::Info, msg);
+
+            if !scan.missing.is_empty() || !scan.found.is_empty() {
+                self.state.dirty = true;
+            }
+            // new links go through the hooks like any other added link
+            let added = std::mem::take(&mut scan.new);
+            scan.apply(links);
+            for link in added {
+                self.add_link(tabb, link)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues the links at the indexes of the selected tab to be downloaded.
+    fn download(&mut self, which: &[usize]) -> Result<(), AppError> {
+        let dir = self.state.options.download_dir();
+        let Some((tab, links)) = self.luma.tabs.get(self.state.tabb) else {
+            return Ok(());
+        };
+
+        let mut queued = 0;
+        for link in which.iter().filter_map(|&i| links.get(i)) {
+            if link.link.is_empty() {
+                continue;
+            }
+            let backend = match self.lua.downloader(link).change_context(AppError::Script)? {
+                Some(argv) => Backend::Command(argv),
+                None => Backend::Http,
+            };
+            self.downloads.push(tab, link.clone(), backend, dir.clone());
+            queued += 1;
+        }
+
+        let msg = match queued {
+            0 => "there is nothing to download".to_owned(),
+            1 => format!("downloading to {}", dir.display()),
+            n => format!("downloading {} links to {}", n, dir.display()),
+        };
+        self.state.toasts.push(Level::Info, msg);
+        self.state.draw = true;
+        Ok(())
+    }
+
+    /// Points the link at the file it was downloaded to.
+    fn finish_download(&mut self, tab: &str, link: Link, status: Status) -> Result<(), AppError> {
+        self.state.draw = true;
+        let path = match status {
+            Status::Done(p) => p,
+            Status::Failed(e) => {
+                let msg = format!("could not download {}: {}", link.name, e);
+                self.state.toasts.push(Level::Error, msg);
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+
+        // the tab is found by name as tabs may have moved since
+        let tabb = self.luma.tabs.iter().position(|t| t.0 == tab);
+        let Some((tabb, index)) = tabb.and_then(|t| Some((t, self.find_link(t, &link)?))) else {
+            let msg = format!("{} changed before its download finished", link.name);
+            self.state.toasts.push(Level::Warn, msg);
+            return Ok(());
+        };
+        let msg = format!("downloaded {}", link.name);
+        self.state.toasts.push(Level::Info, msg);
+
+        let new = Link {
+            file: Some(path.to_string_lossy().into_owned()),
+            ..link
+        };
+        self.replace_link(tabb, index, new)
+    }
+
+    /// Adds the entries of the feed the tab doesn't have yet.
+    fn finish_feed(&mut self, tab: &str, url: &str, res: feed::Entries) -> Result<(), AppError> {
+        let entries = match res {
+            Ok(e) => e,
+            Err(e) => {
+                let msg = format!("could not refresh {}: {}", url, e);
+                self.state.toasts.push(Level::Error, msg);
+                self.state.draw = true;
+                return Ok(());
+            }
+        };
+        // the tab may be gone or renamed since, or unsubscribed
+        let Some(tabb) = self.luma.tabs.iter().position(|t| t.0 == tab) else {
+            return Ok(());
+        };
+        let subs = self.luma.feeds.get_mut(tab);
+        let Some(sub) = subs.and_then(|s| s.iter_mut().find(|s| s.url == url)) else {
+            return Ok(());
+        };
+        let before = sub.seen.clone();
+        let new = feed::fresh(&self.luma.tabs[tabb].1, sub, entries);
+        self.state.dirty |= sub.seen != before;
+        if new.is_empty() {
+            return Ok(());
+        }
+        let msg = format!("{} new in {}", new.len(), tab);
+        for link in new {
+            self.add_link(tabb, link)?;
+        }
+        self.state.toasts.push(Level::Info, msg);
+        self.state.draw = true;
+        Ok(())
+    }
+
+    /// Plays the selected link's file if it is downloaded, or its media
+    /// from the web, and marks it played. With mpv it is played now or
+    /// queued, and marked played once it plays to the end.
+    fn play(&mut self, queue: bool) -> Result<(), AppError> {
+        let (tabb, sel) = (self.state.tabb, self.state.selected);
+        let Some(link) = self.shown() else {
+            return Ok(());
+        };
+        let file = link.file.as_ref().filter(|f| Path::new(f).exists());
+        let what = match file {
+            Some(f) => f.clone(),
+            None if !link.media().is_empty() => link.media().to_owned(),
+            None => {
+                let msg = format!("{} has nothing to play", link.name);
+                self.state.toasts.push(Level::Warn, msg);
+                self.state.draw = true;
+                return Ok(());
+            }
+        };
+        let cmd = self.state.options.player();
+        if Mpv::is_mpv(&cmd) {
+            self.set_unread(tabb, sel, false);
+            let Some(link) = self.luma.tabs.get(tabb).and_then(|t| t.1.get(sel)) else {
+                return Ok(());
+            };
+            let mpv = self.mpv.get_or_insert_with(|| Mpv::new(mpv::socket(), cmd));
+            mpv.play(tabb, link.clone(), what, queue);
+            if queue {
+                let msg = format!("queued {}", link.name);
+                self.state.toasts.push(Level::Info, msg);
+                self.state.draw = true;
+            }
+            return Ok(());
+        }
+        if queue {
+            let msg = "only mpv can queue, set the player to it";
+            self.state.toasts.push(Level::Warn, msg.to_owned());
+            self.state.draw = true;
+            return Ok(());
+        }
+        self.open(&cmd, Some(&what))?;
+
+        self.set_unread(tabb, sel, false);
+        if let Some(l) = self.luma.tabs.get_mut(tabb).and_then(|t| t.1.get_mut(sel)) {
+            if !l.played {
+                l.played = true;
+                self.state.dirty = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Marks the link mpv played to the end as played, if it is still there.
+    fn set_played(&mut self, tabb: usize, link: &Link) {
+        let Some(index) = self.find_link(tabb, link) else {
+            return;
+        };
+        if let Some(l) = self
+            .luma
+            .tabs
+            .get_mut(tabb)
+            .and_then(|t| t.1.get_mut(index))
+        {
+            l.played = true;
+            self.state.dirty = true;
+            self.state.draw = true;
+        }
+    }
+
+    /// Tells mpv what to do if it is playing anything, or else MPD.
+    fn control(
+        &mut self,
+        f: impl FnOnce(&mut Mpv),
+        g: impl FnOnce(&mut mpd::Client) -> std::result::Result<(), String>,
+    ) {
+        if let Some(mpv) = self.mpv.as_mut().filter(|m| m.playing().is_some()) {
+            f(mpv);
+            return;
+        }
+        let msg = match &mut self.mpd {
+            Some(mpd) => match g(mpd) {
+                Ok(()) => return,
+                Err(e) => format!("MPD failed: {}", e),
+            },
+            None => "nothing is playing, play something with p".to_owned(),
+        };
+        self.state.toasts.push(Level::Info, msg);
+        self.state.draw = true;
+    }
+
+    /// The connection to MPD, made if there isn't one.
+    fn mpd(&mut self) -> Option<&mut mpd::Client> {
+        if self.mpd.is_none() {
+            let addr = self.state.options.mpd();
+            match mpd::Client::connect(&addr) {
+                Ok(c) => {
+                    self.mpd = Some(c);
+                    self.mpd_watch = Some(mpd::Watch::new(&addr));
+                }
+                Err(e) => {
+                    let msg = format!("could not connect to MPD: {}", e);
+                    self.state.toasts.push(Level::Error, msg);
+                    self.state.draw = true;
+                }
+            }
+        }
+        self.mpd.as_mut()
+    }
+
+    /// Adds the files of the links in the selected tab to MPD's queue.
+    fn mpd_add(&mut self, which: &[usize]) {
+        let music_dir = self.state.options.music_dir();
+        let links: Vec<Link> = match self.luma.tabs.get(self.state.tabb) {
+            Some((_, links)) => which
+                .iter()
+                .filter_map(|&i| links.get(i))
+                .cloned()
+                .collect(),
+            None => return,
+        };
+        let Some(mpd) = self.mpd() else {
+            return;
+        };
+        let local = mpd.is_local();
+        let (mut added, mut failed) = (0, Vec::new());
+        for l in &links {
+            let res = mpd::uri(l, music_dir.as_deref(), local).and_then(|u| mpd.add(&u));
+            match res {
+                Ok(()) => added += 1,
+                Err(e) => failed.push(e),
+            }
+        }
+        // the first error says why, the rest are often the same
+        let msg = match failed.first() {
+            None => format!("added {} to MPD", added),
+            Some(e) => format!("added {} to MPD, {} failed: {}", added, failed.len(), e),
+        };
+        let level = if failed.is_empty() {
+            Level::Info
+        } else {
+            Level::Error
+        };
+        self.state.toasts.push(level, msg);
+        self.state.draw = true;
+    }
+
+    /// Adds the songs to the tab with the name, leaving out the ones it has.
+    fn import_songs(&mut self, tab: &str, songs: Vec<mpd::Song>) -> Result<usize, AppError> {
+        let music_dir = self.state.options.music_dir();
+        let tabb = match self.luma.tabs.iter().position(|t| t.0 == tab) {
+            Some(i) => i,
+            None => match self.push_tab(tab.to_owned())? {
+                Some(i) => i,
+                None => return Ok(0),
+            },
+        };
+        let mut added = 0;
+        for song in songs {
+            let link = song.link(music_dir.as_deref());
+            let same = |l: &Link| match &link.file {
+                Some(_) => l.file == link.file,
+                None => l.link == link.link,
+            };
+            if self.luma.tabs[tabb].1.iter().any(same) {
+                continue;
+            }
+            if self.add_link(tabb, link)? {
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    /// Marks the link as unread or read.
+    fn set_unread(&mut self, tabb: usize, index: usize, unread: bool) {
+        let link = self
+            .luma
+            .tabs
+            .get_mut(tabb)
+            .and_then(|t| t.1.get_mut(index));
+        if let Some(l) = link.filter(|l| l.unread != unread) {
+            l.unread = unread;
+            self.state.dirty = true;
+            self.state.draw = true;
+        }
+    }
+
+    /// Brings the index up to date and shows what it finds for the query.
+    fn search(&mut self, query: String) {
+        if query.trim().is_empty() {
+            if !self.state.hits.is_empty() {
+                self.state.mode = Mode::Search;
+            }
+            return;
+        }
+        let path = crate::search::path(&self.path);
+        let index = self.index.get_or_insert_with(|| Index::load(&path));
+        let vault = self.state.options.vault();
+        let reader = crate::reader::dir(&self.path);
+        let src = crate::search::Sources {
+            vault: &vault,
+            reader: &reader,
+        };
+        if index.update(&self.luma, src) {
+            if let Err(e) = index.save(&path) {
+                log::warn!("could not save the index {}: {}", path.display(), e);
+            }
+        }
+
+        self.state.hits = index.search(&query);
+        self.state.hit_selected = 0;
+        if self.state.hits.is_empty() {
+            let msg = format!("nothing was found for {:?}", query);
+            self.state.toasts.push(Level::Warn, msg);
+        } else {
+            self.state.mode = Mode::Search;
+        }
+        self.state.query = query;
+    }
+
+    /// Goes to the tab and link of the selected search result.
+    fn go_to_hit(&mut self) {
+        self.state.mode = Mode::Normal;
+        self.state.draw = true;
+        let Some(hit) = self.state.hits.get(self.state.hit_selected) else {
+            return;
+        };
+        let (tab, link) = match &hit.target {
+            crate::search::Target::Link { tab, name, link } => (tab, Some((name, link))),
+            crate::search::Target::Tab(tab) => (tab, None),
+        };
+        let Some(tabb) = self.luma.tabs.iter().position(|t| &t.0 == tab) else {
+            let msg = format!("there is no tab {} any more", tab);
+            self.state.toasts.push(Level::Warn, msg);
+            return;
+        };
+        let index = match link {
+            Some((name, link)) => {
+                let links = &self.luma.tabs[tabb].1;
+                match links
+                    .iter()
+                    .position(|l| &l.name == name && &l.link == link)
+                {
+                    Some(i) => i,
+                    None => {
+                        let msg = format!("{} is no longer in {}", name, tab);
+                        self.state.toasts.push(Level::Warn, msg);
+                        return;
+                    }
+                }
+            }
+            None => 0,
+        };
+        self.state.tabb = tabb;
+        self.state.selected = index;
+        // the link may be filtered out, a tab goes to its first shown link
+        if link.is_none() {
+            self.select_visible();
+        } else if !self.visible().contains(&index) {
+            self.state.filter = None;
+        }
+    }
+
+    /// Opens the link with the opener from the config.
+    fn open_link(&mut self, link: Link) -> Result<(), AppError> {
+        let tabb = Some(self.state.tabb);
+        let Some(link) = self.pre(Hook::Open, tabb, link)? else {
+            return Ok(());
+        };
+        let cmd = self
+            .lua
+            .opener(&link)
+            .change_context(AppError::Script)?
+            .unwrap_or_else(|| self.state.options.opener());
+        self.open(&cmd, Some(&link.link))?;
+        self.post(Hook::Open, tabb, link)
+    }
+
+    /// Opens the latest snapshot of the selected link.
+    fn open_archive(&mut self) -> Result<(), AppError> {
+        self.state.mode = Mode::Normal;
+        self.state.draw = true;
+        let Some(link) = self.shown() else {
+            return Ok(());
+        };
+        match link.archive.clone() {
+            Some(path) if Path::new(&path).exists() => {
+                let cmd = self.state.options.opener();
+                self.open(&cmd, Some(&path))
+            }
+            Some(path) => {
+                let msg = format!("the snapshot {} is gone", path);
+                self.state.toasts.push(Level::Error, msg);
+                Ok(())
+            }
+            None => {
+                let msg = format!("{} has no snapshot", link.name);
+                self.state.toasts.push(Level::Warn, msg);
+                Ok(())
+            }
+        }
+    }
+
+    /// Starts taking snapshots of the links in the selected tab.
+    fn archive(&mut self, which: &[usize]) {
+        let tabb = self.state.tabb;
+        let dir = self.state.options.archive_dir(&self.path);
+        let format = self.state.options.archive_format();
+        let Some((_, links)) = self.luma.tabs.get(tabb) else {
+            return;
+        };
+
+        let mut queued = 0;
+        for link in which.iter().filter_map(|&i| links.get(i)) {
+            if is_web(&link.link) {
+                self.archives
+                    .snapshot(tabb, link.clone(), dir.clone(), format);
+                queued += 1;
+            }
+        }
+
+        let msg = match queued {
+            0 => "there is no web page to archive".to_owned(),
+            1 => format!("archiving to {}", dir.display()),
+            n => format!("archiving {} links to {}", n, dir.display()),
+        };
+        self.state.toasts.push(Level::Info, msg);
+        self.state.draw = true;
+    }
+
+    /// Points the link at its new snapshot, or opens the link once it is
+    /// known to be up.
+    fn finish_archive(&mut self, done: Done) -> Result<(), AppError> {
+        self.state.draw = true;
+        match done {
+            Done::Saved(_, link, Err(e)) => {
+                let msg = format!("could not archive {}: {}", link.name, e);
+                self.state.toasts.push(Level::Error, msg);
+            }
+            Done::Saved(tabb, link, Ok((path, date))) => {
+                let Some(index) = self.find_link(tabb, &link) else {
+                    let msg = format!("{} changed before it was archived", link.name);
+                    self.state.toasts.push(Level::Warn, msg);
+                    return Ok(());
+                };
+                let msg = format!("archived {}", link.name);
+                self.state.toasts.push(Level::Info, msg);
+                let new = Link {
+                    archive: Some(path.to_string_lossy().into_owned()),
+                    archived: Some(date),
+                    ..link
+                };
+                self.replace_link(tabb, index, new)?;
+            }
+            Done::Checked(tabb, link, res) => {
+                // only what is still selected is opened
+                let still = self.find_link(tabb, &link) == Some(self.state.selected)
+                    && self.state.tabb == tabb;
+                if !still || !matches!(self.state.mode, Mode::Normal) {
+                    return Ok(());
+                }
+                match res {
+                    Ok(()) => self.open_link(link)?,
+                    Err(e) => {
+                        let why = format!("{} seems to be down: {}", link.name, e);
+                        self.state.mode = Mode::Gone(why);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens the saved copy of the selected link in the reader, or saves one
+    /// to open once it is done.
+    fn read(&mut self) -> Result<(), AppError> {
+        let Some(link) = self.shown().cloned() else {
+            return Ok(());
+        };
+        self.set_unread(self.state.tabb, self.state.selected, false);
+        let dir = crate::reader::dir(&self.path);
+        match link
+            .reader
+            .as_deref()
+            .and_then(|n| crate::reader::read(&dir, n))
+        {
+            Some(md) => {
+                self.state.reader = Some(reader::Reader::new(self.state.tabb, link, &md));
+                self.state.mode = Mode::Reader;
+                self.state.draw = true;
+            }
+            None => self.save_copy(true),
+        }
+        Ok(())
+    }
+
+    /// Starts saving a readable copy of the selected link's page.
+    fn save_copy(&mut self, open: bool) {
+        let Some(link) = self.shown().cloned() else {
+            return;
+        };
+        self.state.draw = true;
+        if !is_web(&link.link) {
+            let msg = format!("{} has no web page to read", link.name);
+            self.state.toasts.push(Level::Warn, msg);
+            return;
+        }
+        let msg = format!("saving a readable copy of {}", link.name);
+        self.state.toasts.push(Level::Info, msg);
+        let dir = crate::reader::dir(&self.path);
+        self.copies.push(self.state.tabb, link, open, dir);
+    }
+
+    /// Points the link at its saved copy, and opens it if it was asked to be.
+    fn finish_copy(&mut self, (tabb, link, open, res): Saved) -> Result<(), AppError> {
+        self.state.draw = true;
+        let name = match res {
+            Ok(n) => n,
+            Err(e) => {
+                let msg = format!("could not save a readable copy of {}: {}", link.name, e);
+                self.state.toasts.push(Level::Error, msg);
+                return Ok(());
+            }
+        };
+        let Some(index) = self.find_link(tabb, &link) else {
+            let msg = format!("{} changed before its copy was saved", link.name);
+            self.state.toasts.push(Level::Warn, msg);
+            return Ok(());
+        };
+        let msg = format!("saved a readable copy of {}", link.name);
+        self.state.toasts.push(Level::Info, msg);
+
+        let new = Link {
+            reader: Some(name),
+            ..link
+        };
+        self.replace_link(tabb, index, new)?;
+
+        let still = (self.state.tabb, self.state.selected) == (tabb, index);
+        if open && still && matches!(self.state.mode, Mode::Normal) {
+            self.read()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the links to the save path.
+    pub fn save(&mut self) -> Result<(), AppError> {
+        // the links are saved as they are, only a hook can stop it
+        let luma = match self.run_pre(Hook::Save, None, self.luma.clone())? {
+            Flow::Go(l) => l,
+            Flow::Stop(why) => {
+                return Err(Report::new(AppError::Save).attach_printable(format!(
+                    "{} stopped by {}",
+                    Hook::Save,
+                    why
+                )))
+            }
+        };
+        self.luma = luma;
+
+        let f = fs::File::create(&self.path)
+            .change_context(AppError::Save)
+            .attach_printable_lazy(|| format!("could not create {}", self.path.display()))?;
+        json::to_writer_pretty(io::BufWriter::new(f), &self.luma).change_context(AppError::Save)?;
+        self.state.dirty = false;
+        self.state.draw = true;
+
+        self.post(Hook::Save, None, self.luma.clone())
+    }
+
+    /// Runs the hooks before a change. Returns the value to use or `None` if
+    /// a hook stopped the change.
+    fn pre<T>(&mut self, hook: Hook, tabb: Option<usize>, value: T) -> Result<Option<T>, AppError>
+    where
+        T: Serialize + DeserializeOwned + Validate,
+    {
+        let name = hook.pre();
+        match self.run_pre(hook, tabb, value)? {
+            Flow::Go(v) => {
+                let errs = v.validate();
+                if errs.is_empty() {
+                    return Ok(Some(v));
+                }
+                let msg = format!("{} gave back an invalid value: {}", name, errs.join(", "));
+                self.state.toasts.push(Level::Error, msg);
+            }
+            Flow::Stop(why) => {
+                let msg = format!("{} stopped by {}", hook, why);
+                self.state.toasts.push(Level::Warn, msg);
+            }
+        }
+        self.state.draw = true;
+        Ok(None)
+    }
+
+    /// Runs the hooks before a change, without checking what they give back.
+    fn run_pre<T>(&mut self, hook: Hook, tabb: Option<usize>, value: T) -> Result<Flow<T>, AppError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let name = hook.pre();
+        let tab = tabb
+            .and_then(|t| self.luma.tabs.get(t))
+            .map(|t| t.0.clone());
+
+        match self.script(|s| s.filter(&name, tab.as_deref(), value))? {
+            Flow::Go(v) => self
+                .hooks
+                .run(&name, tab.as_deref(), v)
+                .change_context(AppError::Hook),
+            stop => Ok(stop),
+        }
+    }
+
+    /// Runs the hooks after a change.
+    fn post<T>(&mut self, hook: Hook, tabb: Option<usize>, value: T) -> Result<(), AppError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let name = hook.post();
+        let tab = tabb
+            .and_then(|t| self.luma.tabs.get(t))
+            .map(|t| t.0.clone());
+
+        let flow = match self.script(|s| s.filter(&name, tab.as_deref(), value))? {
+            Flow::Go(v) => self
+                .hooks
+                .run(&name, tab.as_deref(), v)
+                .change_context(AppError::Hook)?,
+            stop => stop,
+        };
+
+        // it is too late to stop anything so only let the user know
+        if let Flow::Stop(why) = flow {
+            self.state
+                .toasts
+                .push(Level::Warn, format!("{} failed", why));
+            self.state.draw = true;
+        }
+        Ok(())
+    }
+
+    pub fn edit(&mut self) -> Result<(), AppError> {
+        if let Some(link) = self.shown() {
+            let target = Target::Link {
+                tabb: self.state.tabb,
+                index: self.state.selected,
+            };
+            self.open_editor(Edit::new(target, link)?)?;
+        }
+        Ok(())
+    }
+
+    /// Opens the note of the selected link or the tab in the editor, making
+    /// it first if there isn't one.
+    fn edit_note(&mut self, tab: bool) -> Result<(), AppError> {
+        let vault = self.state.options.vault();
+        let (tabb, sel) = (self.state.tabb, self.state.selected);
+        let Some((name, _)) = self.luma.tabs.get(tabb) else {
+            return Ok(());
+        };
+        let link = self.shown().cloned();
+        let (had, title) = match (tab, &link) {
+            (true, _) => (self.luma.notes.get(name).cloned(), name.clone()),
+            (false, Some(l)) => (l.note.clone(), l.name.clone()),
+            (false, None) => return Ok(()),
+        };
+
+        let note = match had {
+            Some(n) if note::path(&vault, &n).exists() => n,
+            _ => {
+                let n = note::create(&vault, name, &title)
+                    .change_context(AppError::Edit)
+                    .attach_printable_lazy(|| {
+                        format!("could not create a note in {}", vault.display())
+                    })?;
+                match link.filter(|_| !tab) {
+                    Some(l) => {
+                        let new = Link {
+                            note: Some(n.clone()),
+                            ..l
+                        };
+                        self.replace_link(tabb, sel, new)?;
+                    }
+                    None => {
+                        self.luma.notes.insert(name.clone(), n.clone());
+                        self.state.dirty = true;
+                    }
+                }
+                n
+            }
+        };
+
+        let path = note::path(&vault, &note);
+        self.open(&self.state.options.editor(), Some(&path.to_string_lossy()))
+    }
+
+    /// Opens the tags of the selected link's file in the editor.
+    fn tag(&mut self) -> Result<(), AppError> {
+        let Some(link) = self.shown() else {
+            return Ok(());
+        };
+        let Some(file) = &link.file else {
+            let msg = format!("{} has no file to tag", link.name);
+            self.state.toasts.push(Level::Warn, msg);
+            self.state.draw = true;
+            return Ok(());
+        };
+
+        let tags = match tag::read(Path::new(file)) {
+            Ok(t) => t,
+            Err(e) => {
+                log::warn!("could not read tags: {:?}", e);
+                let msg = format!("could not read the tags of {}", file);
+                self.state.toasts.push(Level::Error, msg);
+                self.state.draw = true;
+                return Ok(());
+            }
+        };
+        let target = Target::Tags {
+            tabb: self.state.tabb,
+            index: self.state.selected,
+        };
+        self.open_editor(Edit::new(target, &tags)?)
+    }
+
+    /// Runs the command on the argument, in the terminal pane if it needs a
+    /// terminal and in the background otherwise.
+    pub fn open(&mut self, cmd: &OpenCommand, arg: Option<&str>) -> Result<(), AppError> {
+        if !cmd.term {
+            let res = match arg {
+                Some(a) => cmd.run(a),
+                None => cmd.exec(false),
+            };
+            let name = match arg {
+                Some(a) => format!("{} {}", cmd, a),
+                None => cmd.to_string(),
+            };
+            match res {
+                Ok(c) => self.task.push(Job::new(name, c)),
+                Err(e) => {
+                    log::warn!("failed to open link: {:?}", e);
+                    let msg = format!("failed to run {}", name);
+                    self.state.toasts.push(Level::Error, msg);
+                    self.state.draw = true;
+                }
+            }
+            return Ok(());
+        }
+
+        if self.state.term.is_some() {
+            let msg = "a program is already running in the pane";
+            self.state.toasts.push(Level::Warn, msg);
+            self.state.draw = true;
+            return Ok(());
+        }
+
+        let (cols, rows) = crossterm::terminal::size().unwrap_or((80, 24));
+        let term = Term::spawn(cmd, arg, (rows, cols)).change_context(AppError::Term)?;
+
+        self.state.term = Some(term);
+        self.state.mode = Mode::Term;
+        self.state.draw = true;
+        Ok(())
+    }
+
+    fn open_editor(&mut self, edit: Edit) -> Result<(), AppError> {
+        // the edit is finished when the pane exits, so it has to be its own
+        if self.state.term.is_some() {
+            let msg = "a program is already running in the pane, finish it to edit";
+            self.state.toasts.push(Level::Warn, msg);
+            self.state.draw = true;
+            return Ok(());
+        }
+        self.open(&self.state.options.editor(), Some(edit.path()))
+            .change_context(AppError::Edit)?;
+        if self.state.term.is_some() {
+            self.edit = Some(edit);
+        }
+        Ok(())
+    }
+
+    /// Puts the result of the edit into [`Luma`], or opens the editor again if
+    /// it was not valid.
+    fn finish_edit(&mut self, edit: Edit) -> Result<(), AppError> {
+        // links and tab names go through the hooks so they are taken out first
+        let mut link = None;
+        let mut tags = None;
+        let mut name = None;
+        let retry = match edit.target {
+            Target::Link { .. } | Target::NewLink { .. } => edit.apply(|l: Link| link = Some(l))?,
+            Target::Tags { .. } => edit.apply(|t: Tags| tags = Some(t))?,
+            Target::TabName { .. } | Target::NewTab => edit.apply(|n: String| name = Some(n))?,
+        };
+
+        match (edit.target, link) {
+            (Target::Link { tabb, index }, Some(l)) => self.replace_link(tabb, index, l)?,
+            (Target::NewLink { tabb }, Some(l)) => {
+                self.add_link(tabb, l)?;
+            }
+            _ => {}
+        }
+        match (edit.target, name) {
+            (Target::TabName { tabb }, Some(n)) => self.rename_tab(tabb, n)?,
+            (Target::NewTab, Some(n)) => self.new_tab(n)?,
+            _ => {}
+        }
+        if let (Target::Tags { tabb, index }, Some(t)) = (edit.target, tags) {
+            self.finish_tags(tabb, index, t)?;
+        }
+
+        if retry {
+            self.open_editor(edit)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the tags to the link's file and takes its name and artist from
+    /// them.
+    fn finish_tags(&mut self, tabb: usize, index: usize, tags: Tags) -> Result<(), AppError> {
+        let link = self.luma.tabs.get(tabb).and_then(|t| t.1.get(index));
+        let Some(link) = link.cloned() else {
+            return Ok(());
+        };
+        let Some(file) = link.file.clone() else {
+            return Ok(());
+        };
+
+        if let Err(e) = tag::write(Path::new(&file), &tags) {
+            log::warn!("could not write tags: {:?}", e);
+            let msg = format!("could not write the tags of {}", file);
+            self.state.toasts.push(Level::Error, msg);
+            self.state.draw = true;
+            return Ok(());
+        }
+
+        let new = tags.sync(link.clone());
+        if new != link {
+            self.replace_link(tabb, index, new)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the link to the end of the tab if the hooks let it, gives back if
+    /// it was added.
+    fn add_link(&mut self, tabb: usize, link: Link) -> Result<bool, AppError> {
+        let Some(link) = self.pre(Hook::Add, Some(tabb), link)? else {
+            return Ok(false);
+        };
+        let Some(t) = self.luma.tabs.get_mut(tabb) else {
+            return Ok(false);
+        };
+        t.1.push(link.clone());
+        self.state.dirty = true;
+        self.post(Hook::Add, Some(tabb), link)?;
+        Ok(true)
+    }
+
+    /// Adds a tab with the name and selects it, if the hooks let it and no
+    /// tab has the name.
+    fn new_tab(&mut self, name: String) -> Result<(), AppError> {
+        if let Some(tabb) = self.push_tab(name)? {
+            self.state.tabb = tabb;
+            self.state.selected = 0;
+        }
+        Ok(())
+    }
+
+    /// Adds a tab with the name to the end if the hooks let it and no tab has
+    /// the name, gives back where it is.
+    fn push_tab(&mut self, name: String) -> Result<Option<usize>, AppError> {
+        let Some(name) = self.tab_name(None, name)? else {
+            return Ok(None);
+        };
+        self.luma.tabs.push((name.clone(), Vec::new()));
+        let tabb = self.luma.tabs.len() - 1;
+        self.state.dirty = true;
+        self.state.draw = true;
+        self.post(Hook::Tab, Some(tabb), name)?;
+        Ok(Some(tabb))
+    }
+
+    /// Renames the tab if the hooks let it and no other tab has the name.
+    fn rename_tab(&mut self, tabb: usize, name: String) -> Result<(), AppError> {
+        if tabb >= self.luma.tabs.len() {
+            return Ok(());
+        }
+        let Some(name) = self.tab_name(Some(tabb), name)? else {
+            return Ok(());
+        };
+        self.luma.rename_tab(tabb, name.clone());
+        self.state.dirty = true;
+        self.state.draw = true;
+        self.post(Hook::Tab, Some(tabb), name)
+    }
+
+    /// Runs the hooks on the name for the tab, `None` if they stopped it or
+    /// another tab has it.
+    fn tab_name(&mut self, tabb: Option<usize>, name: String) -> Result<Option<String>, AppError> {
+        let Some(name) = self.pre(Hook::Tab, tabb, name)? else {
+            return Ok(None);
+        };
+        let taken =
+            (self.luma.tabs.iter().enumerate()).any(|(i, t)| t.0 == name && Some(i) != tabb);
+        if taken {
+            let msg = format!("there is already a tab {}", name);
+            self.state.toasts.push(Level::Error, msg);
+            self.state.draw = true;
+            return Ok(None);
+        }
+        Ok(Some(name))
+    }
+
+    /// Puts the link in place of the one at the index if the hooks let it.
+    fn replace_link(&mut self, tabb: usize, index: usize, link: Link) -> Result<(), AppError> {
+        let Some(link) = self.pre(Hook::Edit, Some(tabb), link)? else {
+            return Ok(());
+        };
+        let Some(old) = self
+            .luma
+            .tabs
+            .get_mut(tabb)
+            .and_then(|t| t.1.get_mut(index))
+        else {
+            return Ok(());
+        };
+        *old = link.clone();
+        self.state.dirty = true;
+        self.post(Hook::Edit, Some(tabb), link)
+    }
+
+    /// Runs the config script if there is one and applies its options.
+    pub fn load_config(&mut self, path: &Path) -> Result<(), AppError> {
+        if !path.exists() {
+            log::info!("no config at {}", path.display());
+            return Ok(());
+        }
+
+        self.script(|s| s.load(path))
+            .attach_printable_lazy(|| format!("could not load {}", path.display()))?;
+        let options = self.lua.options().change_context(AppError::Script)?;
+        let errs = options.validate();
+        if !errs.is_empty() {
+            let mut r = Report::new(AppError::Script).attach_printable("luma.o is not valid");
+            for e in errs {
+                r = r.attach_printable(e);
+            }
+            return Err(r);
+        }
+        self.state.options = options;
+        self.script(|s| s.fire("start", ()))
+    }
+
+    /// Runs `f` with the links given to the scripts and puts back what they
+    /// changed.
+    fn script<R>(&mut self, f: impl FnOnce(&Script) -> mlua::Result<R>) -> Result<R, AppError> {
+        let ctx = Ctx {
+            luma: std::mem::take(&mut self.luma),
+            tabb: self.state.tabb,
+            selected: self.state.selected,
+            ..Default::default()
+        };
+        let (ctx, res) = self.lua.run(ctx, f);
+
+        self.luma = ctx.luma;
+        self.state.tabb = ctx.tabb.min(self.luma.tabs.len().saturating_sub(1));
+        let len = self.luma.tabs.get(self.state.tabb).map_or(0, |t| t.1.len());
+        self.state.selected = ctx.selected.min(len.saturating_sub(1));
+        self.state.dirty |= ctx.dirty;
+        for (level, msg) in ctx.toasts {
+            self.state.toasts.push(level, msg);
+        }
+        self.state.draw = true;
+
+        res.change_context(AppError::Script)
+    }
+
+    pub fn finish(self) -> (Luma, tui::Terminal<B>) {
+        (self.luma, self.term)
+    }
+}
+
+/// If the link is a web page.
+fn is_web(link: &str) -> bool {
+    link.starts_with("http://") || link.starts_with("https://")
+}
+
+pub fn init<B: io::Write>(write: &mut B) {
+    crossterm::terminal::enable_raw_mode().unwrap();
+    crossterm::execute!(write, crossterm::terminal::EnterAlternateScreen).unwrap();
+}
 
 pub fn deinit<B: io::Write>(b: &mut B) {
     crossterm::execute!(
@@ -355,16 +2426,188 @@ mod test {
         }
     }
 
-    #[test]
-    fn quit() {
-        let mut app = super::App {
+    fn app() -> super::App<TestBackend> {
+        super::App {
             term: tui::Terminal::new(TestBackend::new(10, 10)).unwrap(),
             luma: crate::Luma::default(),
             state: super::State::default(),
             task: Vec::new(),
-        };
+            edit: None,
+            lua: crate::lua::Script::new().unwrap(),
+            hooks: crate::hook::Hooks::default(),
+            path: std::path::PathBuf::new(),
+            downloads: crate::download::Downloads::default(),
+            thumbs: crate::thumb::Thumbs::default(),
+            copies: crate::reader::Copies::default(),
+            archives: crate::archive::Archives::default(),
+            index: None,
+            feeds: crate::feed::Feeds::default(),
+            feeds_at: None,
+            mpv: None,
+            mpd: None,
+            mpd_watch: None,
+        }
+    }
+
+    #[test]
+    fn quit() {
+        let mut app = app();
         app.handle(super::Msg::Quit).unwrap();
 
         assert!(app.state.quit);
     }
+
+    #[test]
+    fn save_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = app();
+        app.path = dir.path().join("links.json");
+        // a link that fails the check, like one from an old file
+        app.luma
+            .tabs
+            .push(("t".into(), vec![crate::state::Link::default()]));
+        app.state.dirty = true;
+
+        app.save().unwrap();
+        assert!(!app.state.dirty);
+        let saved: crate::Luma =
+            json::from_reader(std::fs::File::open(&app.path).unwrap()).unwrap();
+        assert_eq!(saved.tabs, app.luma.tabs);
+    }
+
+    #[test]
+    fn tab_hooks() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("init.lua");
+        std::fs::write(
+            &config,
+            r#"
+            luma.on("pre_delete", function(l) return l.name ~= "keep" end)
+            luma.on("pre_add", function(l, tab) l.color = tab return l end)
+            "#,
+        )
+        .unwrap();
+        let mut app = app();
+        app.load_config(&config).unwrap();
+        let link = |name: &str| crate::state::Link {
+            name: name.into(),
+            link: format!("https://{}.com", name),
+            ..Default::default()
+        };
+        app.luma.tabs = vec![
+            ("a".into(), vec![link("keep"), link("go")]),
+            ("b".into(), Vec::new()),
+        ];
+
+        // a tab goes only if all of its links can
+        app.handle(super::Msg::DeleteTab).unwrap();
+        assert_eq!(app.luma.tabs.len(), 2);
+
+        // moving deletes from one tab and adds to the other
+        app.state.selected = 1;
+        app.command(crate::command::Command::Move("b".into()))
+            .unwrap();
+        assert_eq!(app.luma.tabs[1].1[0].color.as_deref(), Some("b"));
+        app.state.tabb = 1;
+        app.handle(super::Msg::DeleteTab).unwrap();
+        assert_eq!(app.luma.tabs.len(), 1);
+    }
+
+    #[test]
+    fn report() {
+        let mut app = app();
+        let r = resu::Report::new(super::AppError::Edit).attach_printable("oops");
+        app.report(r);
+        assert!(matches!(&app.state.mode, super::Mode::Error(l) if l.len() == 2));
+
+        // any key goes back to normal
+        app.event(crate::event::Event::Input(crate::event::Key::Char('x')))
+            .unwrap();
+        assert!(matches!(app.state.mode, super::Mode::Normal));
+        assert!(!app.state.quit);
+    }
+
+    #[test]
+    fn hidden_selection() {
+        let mut app = app();
+        let link = crate::state::Link {
+            name: "a".into(),
+            link: "https://a.com".into(),
+            unread: true,
+            ..Default::default()
+        };
+        app.luma.tabs = vec![("web".into(), vec![link])];
+        app.command(crate::command::parse("filter zzz").unwrap())
+            .unwrap();
+
+        // the selection stays on a link the filter hides, which is left alone
+        app.handle(super::Msg::TogglePlayed).unwrap();
+        app.handle(super::Msg::ToggleRead).unwrap();
+        let l = &app.luma.tabs[0].1[0];
+        assert!(!l.played);
+        assert!(l.unread);
+        assert!(!app.state.dirty);
+    }
+
+    #[test]
+    fn commands() {
+        use crate::command::parse;
+
+        let mut app = app();
+        let link = |name: &str, color: Option<&str>| crate::state::Link {
+            name: name.into(),
+            link: format!("https://{}.com", name),
+            color: color.map(Into::into),
+            ..Default::default()
+        };
+        app.luma.tabs = vec![(
+            "web".into(),
+            vec![
+                link("b", None),
+                link("c", Some("video")),
+                link("a", Some("video")),
+            ],
+        )];
+
+        for cmd in ["tab new music", "tab 1", "sort name", "filter tag:video"] {
+            app.command(parse(cmd).unwrap()).unwrap();
+        }
+        let names: Vec<_> = app.luma.tabs[0].1.iter().map(|l| &l.name).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+        assert_eq!(app.visible(), [0, 2]);
+
+        app.handle(super::Msg::MoveDown(1)).unwrap();
+        assert_eq!(app.state.selected, 2);
+
+        app.command(parse("mv music").unwrap()).unwrap();
+        assert_eq!(app.luma.tabs[1].1[0].name, "c");
+        assert_eq!(app.state.selected, 0);
+        assert!(app.state.dirty);
+
+        // links the filter hides can't be deleted
+        app.command(parse("filter zzz").unwrap()).unwrap();
+        assert!(app.visible().is_empty());
+        app.handle(super::Msg::Delete).unwrap();
+        assert_eq!(app.luma.tabs[0].1.len(), 2);
+
+        // tab names have to be new and not empty
+        app.command(parse("tab new music").unwrap()).unwrap();
+        app.command(parse("tab rename music").unwrap()).unwrap();
+        app.rename_tab(0, " ".into()).unwrap();
+        let tabs: Vec<_> = app.luma.tabs.iter().map(|t| &t.0).collect();
+        assert_eq!(tabs, ["web", "music"]);
+
+        // an edit can't take over a pane that is in use
+        let sleep = crate::state::OpenCommand::new(&["sleep"])
+            .unwrap()
+            .in_term();
+        let term = crate::term::Term::spawn(&sleep, Some("5"), (5, 20)).unwrap();
+        app.state.term = Some(term);
+        app.handle(super::Msg::Add).unwrap();
+        assert!(app.edit.is_none());
+        app.state.term = None;
+
+        app.command(parse("q!").unwrap()).unwrap();
+        assert!(app.state.quit && app.state.discard);
+    }
 }
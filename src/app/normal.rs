@@ -1,13 +1,13 @@
 use tui::{
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     symbols,
     widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Tabs},
     Frame,
 };
 
 use crate::state::Link;
-use tui::text::Text;
+use tui::text::{Line, Span, Text};
 
 use super::State;
 
@@ -16,16 +16,36 @@ use crate::Luma;
 pub fn draw(f: &mut Frame<'_>, luma: &Luma, state: &State) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .constraints([
+            Constraint::Min(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
         .split(f.size());
 
-    let help_pane = chunks[1];
-    help_barr(f, help_pane);
+    let stat_pane = chunks[1];
+    stat_barr(f, stat_pane, luma, state);
+
+    let help_pane = chunks[2];
+    help_barr(f, help_pane, state);
 
     main_pane(f, chunks[0], luma, state);
 }
 
+/// The part of the screen toasts can be drawn over.
+pub fn toast_area(area: Rect) -> Rect {
+    Rect {
+        height: area.height.saturating_sub(2),
+        ..area
+    }
+}
+
 fn main_pane(f: &mut Frame<'_>, area: Rect, luma: &Luma, state: &State) {
+    if let (true, Some(term)) = (luma.tabs.is_empty(), &state.term) {
+        super::term::draw(f, area, term);
+        return;
+    }
+
     if luma.tabs.is_empty() {
         let p = Paragraph::new("No links to display. Try creating a tab with 'n'")
             .alignment(tui::layout::Alignment::Center)
@@ -61,10 +81,13 @@ fn error_pane(f: &mut Frame<'_>, area: Rect) {
 }
 
 fn disp_pane(f: &mut Frame<'_>, area: Rect, luma: &Luma, state: &State) {
-    if let Some((_, items)) = luma.tabs.get(state.tabb) {
+    if let Some((name, items)) = luma.tabs.get(state.tabb) {
         let div = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(40), Constraint::Min(1)])
+            .constraints([
+                Constraint::Percentage(state.options.list_width()),
+                Constraint::Min(1),
+            ])
             .split(area);
 
         let list_area = div[0];
@@ -80,8 +103,14 @@ fn disp_pane(f: &mut Frame<'_>, area: Rect, luma: &Luma, state: &State) {
         // }
 
         let view_pane = div[1];
-        if let Some(item) = items.get(state.selected) {
-            prev_pane(f, view_pane, item);
+        if let Some(term) = &state.term {
+            super::term::draw(f, view_pane, term);
+        } else if let Some(item) = items
+            .get(state.selected)
+            .filter(|_| state.visible(items).contains(&state.selected))
+        {
+            let tab_note = luma.notes.get(name).map(|n| (name.as_str(), n.as_str()));
+            prev_pane(f, view_pane, item, tab_note, state);
         } else {
             error_pane(f, view_pane);
         }
@@ -90,13 +119,71 @@ fn disp_pane(f: &mut Frame<'_>, area: Rect, luma: &Luma, state: &State) {
     }
 }
 
-fn prev_pane(f: &mut Frame<'_>, area: Rect, link: &Link) {
-    let view = as_paragraph(link).block(
-        Block::new()
-            .border_set(symbols::border::ROUNDED)
-            .borders(Borders::RIGHT | Borders::TOP | Borders::BOTTOM),
-    );
-    f.render_widget(view, area);
+fn prev_pane(
+    f: &mut Frame<'_>,
+    area: Rect,
+    link: &Link,
+    tab_note: Option<(&str, &str)>,
+    state: &State,
+) {
+    let block = Block::new()
+        .border_set(symbols::border::ROUNDED)
+        .borders(Borders::RIGHT | Borders::TOP | Borders::BOTTOM);
+    let mut inner = block.inner(area);
+    f.render_widget(block, area);
+
+    // the picture stays at the top while the text under it scrolls
+    if let Some((path, protocol)) = state.picture(link) {
+        let room = Rect {
+            height: inner.height / 2,
+            ..inner
+        };
+        let used = state
+            .picture
+            .borrow_mut()
+            .draw(f.buffer_mut(), &path, room, protocol);
+        if used > 0 {
+            let skip = (used + 1).min(inner.height);
+            inner.y += skip;
+            inner.height -= skip;
+        }
+    }
+
+    let mut text = fields(link);
+    let mut section = |title: String, lines: Vec<Line<'static>>| {
+        text.lines.push(Line::default());
+        text.lines.push(Line::styled(
+            format!("── {} ──", title),
+            Style::default().fg(Color::DarkGray),
+        ));
+        text.lines.extend(lines);
+    };
+    let md = |md: &str| super::markdown::render(md).lines;
+    if let Some(desc) = &link.desc {
+        section("Description".to_owned(), md(desc));
+    }
+    if let Some(note) = state.note(link) {
+        section("Note".to_owned(), md(&note));
+    }
+    if let Some((tab, note)) = tab_note {
+        if let Some(note) = state.read_note(note) {
+            section(format!("Tab: {}", tab), md(&note));
+        }
+    }
+    if let Some(file) = &link.file {
+        if let Some(head) = state.files.borrow_mut().get(std::path::Path::new(file)) {
+            section("File".to_owned(), head.lines);
+        }
+    }
+
+    // the preview scrolls on its own, as far as its last row
+    let view = Paragraph::new(text).wrap(tui::widgets::Wrap { trim: false });
+    let rows = view.line_count(inner.width);
+    let max = rows.saturating_sub(inner.height as usize) as u16;
+    state.prev_max.set(max);
+
+    let view = view.scroll((state.prev_scroll().min(max), 0));
+    f.render_widget(view, inner);
 }
 
 fn list_pane(f: &mut Frame<'_>, area: Rect, items: &[Link], state: &State) {
@@ -105,7 +192,8 @@ fn list_pane(f: &mut Frame<'_>, area: Rect, items: &[Link], state: &State) {
         bottom_right: symbols::line::ROUNDED.horizontal_up,
         ..symbols::border::ROUNDED
     };
-    let list = List::new(items.iter().map(as_list_item))
+    let visible = state.visible(items);
+    let list = List::new(visible.iter().map(|&i| as_list_item(&items[i])))
         .block(
             Block::new()
                 .border_set(joined_border_set)
@@ -114,51 +202,162 @@ fn list_pane(f: &mut Frame<'_>, area: Rect, items: &[Link], state: &State) {
         .highlight_style(Style::default().bg(Color::Red));
 
     let mut list_stat = ListState::default()
-        .with_selected(Some(state.selected))
+        .with_selected(visible.iter().position(|&i| i == state.selected))
         .with_offset(state.ofst);
 
     f.render_stateful_widget(list, area, &mut list_stat);
 }
 
 fn tabb_barr(f: &mut Frame<'_>, area: Rect, luma: &Luma, state: &State) {
-    let tabs = Tabs::new(luma.tabs.iter().map(|l| l.0.as_str()))
-        .select(state.tabb)
-        .style(Style::default().fg(Color::White))
-        .highlight_style(Style::default().fg(Color::Yellow))
-        .divider(symbols::DOT);
+    // tabs with unread links say how many
+    let tabs = Tabs::new(luma.tabs.iter().map(|(name, links)| {
+        match links.iter().filter(|l| l.unread).count() {
+            0 => name.clone(),
+            n => format!("{} ({})", name, n),
+        }
+    }))
+    .select(state.tabb)
+    .style(Style::default().fg(Color::White))
+    .highlight_style(Style::default().fg(Color::Yellow))
+    .divider(symbols::DOT);
     f.render_widget(tabs, area);
 }
 
-fn help_barr(f: &mut Frame<'_>, area: Rect) {
-    let p = Paragraph::new("Keys: q: quit, j: down, k: up, e: edit, o: open, d: delete, a: add, n: new tab, r: rename tab");
+fn stat_barr(f: &mut Frame<'_>, area: Rect, luma: &Luma, state: &State) {
+    let mode = Span::styled(
+        format!(" {} ", state.mode.name()),
+        Style::default().fg(Color::Black).bg(Color::Yellow),
+    );
+
+    let mut pos = String::new();
+    if let Some((name, links)) = luma.tabs.get(state.tabb) {
+        pos.push_str(&format!(
+            " {} [{}/{}]",
+            name,
+            state.tabb + 1,
+            luma.tabs.len()
+        ));
+        if !links.is_empty() {
+            pos.push_str(&format!(" {}/{}", state.selected + 1, links.len()));
+        }
+    }
+
+    if let Some(filter) = &state.filter {
+        pos.push_str(&format!(" filter: {}", filter.query));
+    }
+
+    let dirty = if state.dirty { " [+]" } else { "" };
+
+    let mut line = Line::from(vec![
+        mode,
+        Span::raw(pos),
+        Span::styled(dirty, Style::default().fg(Color::Red)),
+    ]);
+    if let Some(p) = &state.playing {
+        let icon = if p.paused { "⏸" } else { "▶" };
+        let mut now = format!(" {} {} {}", icon, p.title, clock(p.pos as u64));
+        if p.duration > 0.0 {
+            now.push_str(&format!("/{}", clock(p.duration as u64)));
+        }
+        if p.next > 0 {
+            now.push_str(&format!(" (+{})", p.next));
+        }
+        line.spans
+            .push(Span::styled(now, Style::default().fg(Color::Cyan)));
+    }
+    f.render_widget(
+        Paragraph::new(line).style(Style::default().bg(Color::DarkGray)),
+        area,
+    );
+}
+
+fn help_barr(f: &mut Frame<'_>, area: Rect, state: &State) {
+    let mut help = String::from("Keys: q: quit, j: down, k: up, e: edit, o: open, d: delete, a: add, s: save, n: new tab, r: rename tab, t: tag, N: note, M: tab note, C-e/C-y/C-f/C-b: scroll preview, C-\\: switch to and from the pane, f: download, F: downloads, R: read, W: save readable, A: archive, O: open archive, U: refresh feeds, u: toggle read, X: mark all read, p: play, Q: enqueue, space: pause, >: next, ,/.: seek, P: toggle played, J: jobs, /: search, :: command");
+    for a in &state.options.actions {
+        if let Some(k) = &a.key {
+            help.push_str(&format!(", {}: {}", k, a.name));
+        }
+    }
+    let p = Paragraph::new(help);
     f.render_widget(p, area)
 }
 
 fn as_list_item(link: &Link) -> ListItem<'static> {
     // as list item
     let t = Text::raw(link.name.clone());
-    ListItem::new(t)
+    let item = ListItem::new(t);
     // .style(Style::new().fg(self.color.unwrap_or_default()));
+    if link.missing {
+        item.style(
+            Style::default()
+                .fg(Color::DarkGray)
+                .add_modifier(tui::style::Modifier::CROSSED_OUT),
+        )
+    } else if link.unread {
+        item.style(Style::default().add_modifier(Modifier::BOLD))
+    } else {
+        item
+    }
 }
 
-fn as_paragraph(link: &Link) -> Paragraph<'static> {
-    let name = Some(format!("Name: {}", link.name));
-    let l = Some(format!("Link: {}", link.link));
-    let file = link.file.as_ref().map(|f| format!("File: {}", f));
-    let artist = link.artist.as_ref().map(|a| format!("Artist: {}", a));
-    let desc = link.desc.as_ref().map(|d| format!("Description: {}", d));
-
-    let mut buf = String::new();
+fn fields(link: &Link) -> Text<'static> {
+    let field = |name: &str, value: String, style: Style| {
+        Line::from(vec![
+            Span::styled(
+                format!("{}: ", name),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(value, style),
+        ])
+    };
+    let link_style = Style::default()
+        .fg(Color::Blue)
+        .add_modifier(Modifier::UNDERLINED);
 
-    [name, l, file, artist, desc]
-        .into_iter()
-        .flatten()
-        .for_each(|l| {
-            buf.push_str(&l);
-            buf.push('\n');
-        });
+    let mut lines = vec![field("Name", link.name.clone(), Style::default())];
+    if !link.link.is_empty() {
+        lines.push(field("Link", link.link.clone(), link_style));
+    }
+    if let Some(file) = &link.file {
+        let missing = if link.missing { " (missing)" } else { "" };
+        lines.push(field(
+            "File",
+            format!("{}{}", file, missing),
+            Style::default(),
+        ));
+    }
+    if let Some(a) = &link.artist {
+        lines.push(field("Artist", a.clone(), Style::default()));
+    }
+    if let Some(e) = &link.enclosure {
+        lines.push(field("Media", e.clone(), link_style));
+        let mut about = Vec::new();
+        if link.duration > 0 {
+            about.push(clock(link.duration));
+        }
+        if link.size > 0 {
+            about.push(super::downloads::size(link.size));
+        }
+        about.push(if link.played { "played" } else { "not played" }.to_owned());
+        lines.push(field("Episode", about.join(", "), Style::default()));
+    }
+    if let Some(d) = &link.published {
+        lines.push(field("Published", d.clone(), Style::default()));
+    }
+    if let Some(d) = &link.archived {
+        lines.push(field("Archived", d.clone(), Style::default()));
+    }
+    Text::from(lines)
+}
 
-    Paragraph::new(buf)
+/// Seconds as `1:02:03`, or `2:03` when under an hour.
+fn clock(secs: u64) -> String {
+    let (h, m, s) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
 }
 
 // pub fn prompt(msg: &str) -> Paragraph<'_> {
@@ -0,0 +1,373 @@
+//! Draws pictures in the preview pane.
+//!
+//! Terminals that speak the kitty, sixel or iTerm image protocols are sent
+//! the picture after the frame is drawn, over cells left blank for it. The
+//! rest get it drawn with half blocks, two pixels to a cell.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use image::{imageops::FilterType, DynamicImage, RgbaImage};
+use tui::{buffer::Buffer, layout::Rect, style::Color};
+
+use crate::prelude::*;
+
+/// Images bigger than this aren't read.
+const MAX_FILE: u64 = 32 * 1024 * 1024;
+/// Pixels of a cell when the terminal doesn't say.
+const CELL: (u32, u32) = (8, 16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Kitty,
+    Sixel,
+    Iterm,
+    /// Half blocks colored with the pixels.
+    Blocks,
+}
+
+impl Protocol {
+    /// The protocol named by the `images` option, `auto` guesses it from the
+    /// terminal and `none` shows no pictures.
+    pub fn new(option: &str) -> Option<Protocol> {
+        match option {
+            "none" => None,
+            "kitty" => Some(Protocol::Kitty),
+            "sixel" => Some(Protocol::Sixel),
+            "iterm" => Some(Protocol::Iterm),
+            "blocks" => Some(Protocol::Blocks),
+            _ => Some(Protocol::detect()),
+        }
+    }
+
+    fn detect() -> Protocol {
+        let var = |v: &str| env::var(v).unwrap_or_default().to_lowercase();
+        let (term, program) = (var("TERM"), var("TERM_PROGRAM"));
+        if env::var_os("KITTY_WINDOW_ID").is_some()
+            || term.contains("kitty")
+            || program == "ghostty"
+        {
+            Protocol::Kitty
+        } else if program == "iterm.app" || program == "wezterm" || var("LC_TERMINAL") == "iterm2" {
+            Protocol::Iterm
+        } else if ["foot", "mlterm", "contour", "yaft"]
+            .iter()
+            .any(|t| term.contains(t))
+            || program == "contour"
+        {
+            Protocol::Sixel
+        } else {
+            Protocol::Blocks
+        }
+    }
+}
+
+/// A picture made to fit an area with a protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Key {
+    path: PathBuf,
+    area: Rect,
+    protocol: Protocol,
+}
+
+#[derive(Debug)]
+enum Made {
+    /// Written to the terminal as it is.
+    Escape(Vec<u8>),
+    /// The top and bottom color of each cell, a row at a time.
+    Cells(Vec<(Color, Color)>),
+}
+
+/// The picture of the selected link and what the terminal shows.
+#[derive(Debug, Default)]
+pub struct Preview {
+    /// The last picture made and the cells it takes, kept while the link and
+    /// the layout stay the same. `None` when the file isn't a picture.
+    made: Option<(Key, Option<(Rect, Made)>)>,
+    /// The picture the frame wants sent to the terminal.
+    want: Option<(Key, Rect)>,
+    /// The picture the terminal is showing.
+    shown: Option<(Key, Rect)>,
+}
+
+impl Preview {
+    /// Draws the picture at the top of the area and gives back how many rows
+    /// it takes.
+    pub fn draw(&mut self, buf: &mut Buffer, path: &Path, area: Rect, protocol: Protocol) -> u16 {
+        let key = Key {
+            path: path.to_owned(),
+            area,
+            protocol,
+        };
+        if self.made.as_ref().is_none_or(|(k, _)| *k != key) {
+            let made = make(path, area, protocol)
+                .map_err(|e| log::info!("can't show {}: {}", path.display(), e))
+                .ok();
+            self.made = Some((key.clone(), made));
+        }
+        let Some((_, Some((used, made)))) = &self.made else {
+            return 0;
+        };
+
+        match made {
+            Made::Escape(_) => {
+                // the terminal draws over these
+                buf.set_style(*used, tui::style::Style::reset());
+                for y in used.top()..used.bottom() {
+                    for x in used.left()..used.right() {
+                        buf.get_mut(x, y).set_symbol(" ");
+                    }
+                }
+                self.want = Some((key, *used));
+            }
+            Made::Cells(cells) => {
+                let rows = cells.chunks(usize::from(used.width));
+                for (y, row) in (used.top()..).zip(rows) {
+                    for (x, &(top, bottom)) in (used.left()..).zip(row) {
+                        buf.get_mut(x, y).set_symbol("▀").set_fg(top).set_bg(bottom);
+                    }
+                }
+            }
+        }
+        used.height
+    }
+
+    /// If a picture the terminal drew has to be taken off by drawing the
+    /// whole screen again. Only kitty can take them off by itself.
+    pub fn stale(&mut self, visible: bool) -> bool {
+        let want = self.want.as_ref().filter(|_| visible);
+        let stale = match &self.shown {
+            Some((k, _)) => k.protocol != Protocol::Kitty && self.shown.as_ref() != want,
+            None => false,
+        };
+        if stale {
+            self.shown = None;
+        }
+        stale
+    }
+
+    /// Sends the picture of the last frame to the terminal, unless popups
+    /// are over it.
+    pub fn show(&mut self, out: &mut impl Write, visible: bool) -> io::Result<()> {
+        let want = self.want.take().filter(|_| visible);
+        if want == self.shown {
+            return Ok(());
+        }
+        let kitty = |s: &Option<(Key, Rect)>| {
+            s.as_ref()
+                .is_some_and(|(k, _)| k.protocol == Protocol::Kitty)
+        };
+        if kitty(&self.shown) {
+            out.write_all(b"\x1b_Ga=d,d=A,q=2\x1b\\")?;
+        }
+        if let (Some((key, used)), Some((made_for, Some((_, Made::Escape(bytes)))))) =
+            (&want, &self.made)
+        {
+            if key == made_for {
+                crossterm::queue!(out, crossterm::cursor::MoveTo(used.x, used.y))?;
+                out.write_all(bytes)?;
+            }
+        }
+        self.shown = want;
+        out.flush()
+    }
+}
+
+/// Reads the picture and makes it fit the area.
+fn make(path: &Path, area: Rect, protocol: Protocol) -> std::result::Result<(Rect, Made), String> {
+    let len = fs::metadata(path).map_err(|e| e.to_string())?.len();
+    if len > MAX_FILE {
+        return Err("the file is too big".to_owned());
+    }
+    let img = image::ImageReader::open(path)
+        .and_then(|r| r.with_guessed_format())
+        .map_err(|e| e.to_string())?
+        .decode()
+        .map_err(|e| e.to_string())?;
+
+    if protocol == Protocol::Blocks {
+        let img = fit(&img, u32::from(area.width), u32::from(area.height) * 2).to_rgba8();
+        let (w, h) = (img.width() as u16, img.height().div_ceil(2) as u16);
+        let mut cells = Vec::new();
+        for y in 0..u32::from(h) {
+            for x in 0..img.width() {
+                let top = color(&img, x, y * 2);
+                let bottom = color(&img, x, y * 2 + 1);
+                cells.push((top, bottom));
+            }
+        }
+        return Ok((Rect::new(area.x, area.y, w, h), Made::Cells(cells)));
+    }
+
+    let (cw, ch) = cell_size();
+    let img = fit(
+        &img,
+        u32::from(area.width) * cw,
+        u32::from(area.height) * ch,
+    );
+    let cols = img.width().div_ceil(cw) as u16;
+    let rows = img.height().div_ceil(ch) as u16;
+    let used = Rect::new(area.x, area.y, cols, rows);
+    let bytes = match protocol {
+        Protocol::Kitty => kitty(&png(&img)?, cols, rows),
+        Protocol::Iterm => iterm(&png(&img)?, cols, rows),
+        Protocol::Sixel => sixel(&img.to_rgba8()),
+        Protocol::Blocks => unreachable!("drawn as cells"),
+    };
+    Ok((used, Made::Escape(bytes)))
+}
+
+/// Pixels of a cell, from the size of the window if the terminal gives it.
+fn cell_size() -> (u32, u32) {
+    match crossterm::terminal::window_size() {
+        Ok(s) if s.columns > 0 && s.rows > 0 && s.width > 0 && s.height > 0 => {
+            (u32::from(s.width / s.columns), u32::from(s.height / s.rows))
+        }
+        _ => CELL,
+    }
+}
+
+/// The picture made smaller to fit the box, keeping its shape.
+fn fit(img: &DynamicImage, w: u32, h: u32) -> DynamicImage {
+    if img.width() <= w && img.height() <= h {
+        img.clone()
+    } else {
+        img.resize(w.max(1), h.max(1), FilterType::Triangle)
+    }
+}
+
+fn color(img: &RgbaImage, x: u32, y: u32) -> Color {
+    match img.get_pixel_checked(x, y) {
+        Some(p) if p[3] >= 128 => Color::Rgb(p[0], p[1], p[2]),
+        _ => Color::Reset,
+    }
+}
+
+fn png(img: &DynamicImage) -> std::result::Result<Vec<u8>, String> {
+    let mut buf = io::Cursor::new(Vec::new());
+    img.write_to(&mut buf, image::ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(buf.into_inner())
+}
+
+/// Sends the png in chunks, scaled to the cells without moving the cursor.
+fn kitty(png: &[u8], cols: u16, rows: u16) -> Vec<u8> {
+    let data = base64::engine::general_purpose::STANDARD.encode(png);
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+    let mut out = Vec::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        if i == 0 {
+            let head = format!("\x1b_Ga=T,f=100,c={},r={},C=1,q=2,m={};", cols, rows, more);
+            out.extend_from_slice(head.as_bytes());
+        } else {
+            out.extend_from_slice(format!("\x1b_Gm={};", more).as_bytes());
+        }
+        out.extend_from_slice(chunk);
+        out.extend_from_slice(b"\x1b\\");
+    }
+    out
+}
+
+fn iterm(png: &[u8], cols: u16, rows: u16) -> Vec<u8> {
+    let data = base64::engine::general_purpose::STANDARD.encode(png);
+    format!(
+        "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=1:{}\x07",
+        png.len(),
+        cols,
+        rows,
+        data
+    )
+    .into_bytes()
+}
+
+/// Encodes the picture as sixels with a palette of 6 levels of each color.
+/// See-through pixels are left as they are.
+fn sixel(img: &RgbaImage) -> Vec<u8> {
+    const LEVELS: u32 = 6;
+    let index = |p: &image::Rgba<u8>| -> Option<usize> {
+        if p[3] < 128 {
+            return None;
+        }
+        let l = |c: u8| (u32::from(c) * (LEVELS - 1) + 127) / 255;
+        Some((l(p[0]) * LEVELS * LEVELS + l(p[1]) * LEVELS + l(p[2])) as usize)
+    };
+
+    let (w, h) = img.dimensions();
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", w, h).into_bytes();
+    for i in 0..LEVELS.pow(3) {
+        let pct = |c: u32| c * 100 / (LEVELS - 1);
+        let (r, g, b) = (i / (LEVELS * LEVELS), i / LEVELS % LEVELS, i % LEVELS);
+        out.extend_from_slice(format!("#{};2;{};{};{}", i, pct(r), pct(g), pct(b)).as_bytes());
+    }
+
+    let mut bits = vec![0u8; w as usize];
+    for band in (0..h).step_by(6) {
+        let mut colors: Vec<usize> = (band..(band + 6).min(h))
+            .flat_map(|y| (0..w).filter_map(move |x| index(img.get_pixel(x, y))))
+            .collect();
+        colors.sort_unstable();
+        colors.dedup();
+
+        for (n, &c) in colors.iter().enumerate() {
+            for (x, b) in bits.iter_mut().enumerate() {
+                *b = 0;
+                for dy in 0..6 {
+                    let y = band + dy;
+                    if y < h && index(img.get_pixel(x as u32, y)) == Some(c) {
+                        *b |= 1 << dy;
+                    }
+                }
+            }
+            if n > 0 {
+                out.push(b'$');
+            }
+            out.extend_from_slice(format!("#{}", c).as_bytes());
+            runs(&bits, &mut out);
+        }
+        out.push(b'-');
+    }
+    out.extend_from_slice(b"\x1b\\");
+    out
+}
+
+/// Writes the sixels of a row, runs of the same one shortened with `!`.
+fn runs(bits: &[u8], out: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < bits.len() {
+        let n = bits[i..].iter().take_while(|&&b| b == bits[i]).count();
+        let c = b'?' + bits[i];
+        if n > 3 {
+            out.extend_from_slice(format!("!{}", n).as_bytes());
+            out.push(c);
+        } else {
+            out.extend(std::iter::repeat_n(c, n));
+        }
+        i += n;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgba, RgbaImage};
+
+    use super::sixel;
+
+    #[test]
+    fn sixels() {
+        // a red column and three see through ones, then a blue row under
+        let mut img = RgbaImage::new(4, 7);
+        for y in 0..6 {
+            img.put_pixel(0, y, Rgba([255, 0, 0, 255]));
+        }
+        for x in 0..4 {
+            img.put_pixel(x, 6, Rgba([0, 0, 255, 255]));
+        }
+        let out = String::from_utf8(sixel(&img)).unwrap();
+        assert!(out.starts_with("\x1bP0;1;0q\"1;1;4;7#0;2;0;0;0"));
+        assert!(out.contains("#180;2;100;0;0"));
+        let data = &out[out.find("#215;2;100;100;100").unwrap() + 18..];
+        assert_eq!(data, "#180~???-#5!4@-\x1b\\");
+    }
+}
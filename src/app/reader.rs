@@ -0,0 +1,348 @@
+//! A full screen view for reading the saved copy of a page.
+
+use tui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::Paragraph,
+    Frame,
+};
+use unicode_width::UnicodeWidthStr;
+
+use super::State;
+
+use crate::state::Link;
+
+/// The text is kept narrow enough to read.
+const MAX_WIDTH: u16 = 100;
+
+#[derive(Debug)]
+pub struct Reader {
+    /// The tab and link being read, to keep the place in.
+    pub tabb: usize,
+    pub link: Link,
+    text: Text<'static>,
+    /// The text wrapped to the width it was last drawn at, each row with
+    /// the line of the text it is from.
+    rows: Vec<(usize, Line<'static>)>,
+    width: u16,
+    /// Rows that fit on the screen.
+    height: u16,
+    /// The first row on the screen. Until the text is wrapped it is the line
+    /// to start at.
+    top: usize,
+    /// What is being typed after `/`.
+    pub typing: Option<String>,
+    /// What was searched for, the rows it is found on and which of them was
+    /// gone to last.
+    search: Option<String>,
+    found: Vec<usize>,
+    at: Option<usize>,
+}
+
+impl Reader {
+    /// Reads the Markdown from the line the link was read up to.
+    pub fn new(tabb: usize, link: Link, md: &str) -> Self {
+        Self {
+            tabb,
+            top: link.read_at,
+            link,
+            text: super::markdown::render(md),
+            rows: Vec::new(),
+            width: 0,
+            height: 0,
+            typing: None,
+            search: None,
+            found: Vec::new(),
+            at: None,
+        }
+    }
+
+    /// Wraps the text to the area it is drawn in, keeping the line at the top
+    /// in place.
+    pub fn fit(&mut self, area: Rect) {
+        self.height = area.height;
+        if self.width == area.width {
+            self.scroll(0);
+            return;
+        }
+        let line = self.line();
+        self.width = area.width;
+        self.rows = self
+            .text
+            .lines
+            .iter()
+            .enumerate()
+            .flat_map(|(i, l)| {
+                wrap(l, usize::from(area.width))
+                    .into_iter()
+                    .map(move |r| (i, r))
+            })
+            .collect();
+        self.top = self.rows.iter().position(|r| r.0 >= line).unwrap_or(0);
+        if let Some(q) = self.search.take() {
+            self.find_rows(q);
+        }
+        self.scroll(0);
+    }
+
+    /// The line of the text at the top of the screen.
+    pub fn line(&self) -> usize {
+        if self.width == 0 {
+            return self.top;
+        }
+        self.rows.get(self.top).map_or(0, |r| r.0)
+    }
+
+    /// Rows in a screen.
+    pub fn page(&self) -> isize {
+        self.height.max(1) as isize
+    }
+
+    /// Moves down by the rows, or up if it is negative.
+    pub fn scroll(&mut self, by: isize) {
+        let last = self.rows.len().saturating_sub(usize::from(self.height));
+        self.top = self.top.saturating_add_signed(by).min(last);
+    }
+
+    /// Finds the rows with the text, ignoring case, and goes to the first
+    /// one on the screen or after it.
+    pub fn find(&mut self, query: String) {
+        self.find_rows(query);
+        let first = self.found.iter().position(|&r| r >= self.top);
+        self.go(first.or((!self.found.is_empty()).then_some(0)));
+    }
+
+    fn find_rows(&mut self, query: String) {
+        let q = query.to_lowercase();
+        self.found = (self.rows.iter().enumerate())
+            .filter(|(_, (_, l))| !q.is_empty() && l.to_string().to_lowercase().contains(&q))
+            .map(|(i, _)| i)
+            .collect();
+        self.at = None;
+        self.search = Some(query);
+    }
+
+    fn go(&mut self, at: Option<usize>) {
+        self.at = at;
+        if let Some(&r) = at.and_then(|i| self.found.get(i)) {
+            self.top = r;
+            self.scroll(0);
+        }
+    }
+
+    /// Goes to the next row with what was searched for, or the one before,
+    /// going round at the ends.
+    pub fn next(&mut self, back: bool) {
+        let n = self.found.len();
+        if n == 0 {
+            return;
+        }
+        let at = match self.at {
+            Some(i) if back => (i + n - 1) % n,
+            Some(i) => (i + 1) % n,
+            None => 0,
+        };
+        self.go(Some(at));
+    }
+}
+
+/// Where the text goes on the screen, above the status line.
+pub fn area(size: Rect) -> Rect {
+    let width = size.width.min(MAX_WIDTH);
+    Rect {
+        x: size.x + (size.width - width) / 2,
+        width,
+        height: size.height.saturating_sub(1),
+        ..size
+    }
+}
+
+pub fn draw(f: &mut Frame<'_>, state: &State) {
+    let Some(r) = &state.reader else {
+        return;
+    };
+    let size = f.size();
+    let text = area(size);
+
+    let query = r.search.as_deref().unwrap_or("").to_lowercase();
+    let rows: Vec<Line<'static>> = (r.rows.iter())
+        .skip(r.top)
+        .take(usize::from(text.height))
+        .map(|(_, l)| mark(l, &query))
+        .collect();
+    f.render_widget(Paragraph::new(rows), text);
+
+    let status = match &r.typing {
+        Some(t) => format!("/{}", t),
+        None => {
+            let lines = r.text.lines.len().max(1);
+            let seen = (r.top + usize::from(text.height)).min(r.rows.len());
+            let percent = seen * 100 / r.rows.len().max(1);
+            let mut s = format!(" {}  {}/{}  {}%", r.link.name, r.line() + 1, lines, percent);
+            if let Some(q) = &r.search {
+                let at = r.at.map_or(0, |i| i + 1);
+                s.push_str(&format!("  /{} {}/{}", q, at, r.found.len()));
+            }
+            s
+        }
+    };
+    let bar = Rect {
+        y: size.bottom().saturating_sub(1),
+        height: 1.min(size.height),
+        ..size
+    };
+    let style = Style::default().add_modifier(Modifier::REVERSED);
+    f.render_widget(Paragraph::new(status).style(style), bar);
+}
+
+/// Breaks the line into rows of the width between words, and in words that
+/// don't fit on a row.
+fn wrap(line: &Line<'static>, width: usize) -> Vec<Line<'static>> {
+    let width = width.max(1);
+    let mut rows = Vec::new();
+    let mut row: Vec<Span<'static>> = Vec::new();
+    let mut used = 0;
+
+    // spaces at the end of a row aren't seen
+    let end = |row: &mut Vec<Span<'static>>| {
+        if let Some(last) = row.last_mut() {
+            let len = last.content.trim_end_matches(' ').len();
+            last.content.to_mut().truncate(len);
+        }
+        Line::from(std::mem::take(row))
+    };
+    let push = |row: &mut Vec<Span<'static>>, used: &mut usize, text: &str, style| {
+        *used += text.width();
+        match row.last_mut() {
+            Some(s) if s.style == style => s.content.to_mut().push_str(text),
+            _ => row.push(Span::styled(text.to_owned(), style)),
+        }
+    };
+
+    for span in &line.spans {
+        for word in span.content.split_inclusive(' ') {
+            let bare = word.trim_end_matches(' ');
+            if used + bare.width() > width && used > 0 {
+                rows.push(end(&mut row));
+                used = 0;
+            }
+            if bare.is_empty() && used == 0 && !rows.is_empty() {
+                continue;
+            }
+            if bare.width() <= width {
+                push(&mut row, &mut used, word, span.style);
+                continue;
+            }
+            for c in word.chars() {
+                let w = c.to_string().width();
+                if used + w > width && used > 0 {
+                    rows.push(end(&mut row));
+                    used = 0;
+                }
+                push(&mut row, &mut used, &c.to_string(), span.style);
+            }
+        }
+    }
+    rows.push(end(&mut row));
+    rows
+}
+
+/// The row with what was searched for stood out.
+fn mark(line: &Line<'static>, query: &str) -> Line<'static> {
+    let plain = line.to_string();
+    let text = plain.to_lowercase();
+    // the places found have to line up with the text
+    if query.is_empty() || text.len() != plain.len() {
+        return line.clone();
+    }
+    let found: Vec<(usize, usize)> = text
+        .match_indices(query)
+        .map(|(i, m)| (i, i + m.len()))
+        .collect();
+    if found.is_empty() {
+        return line.clone();
+    }
+
+    let hit = Style::default().fg(Color::Black).bg(Color::Yellow);
+    let mut spans = Vec::new();
+    let mut at = 0;
+    for span in &line.spans {
+        let content = span.content.as_ref();
+        let end = at + content.len();
+        // cut the span where the found text starts and stops
+        let mut cuts = vec![at, end];
+        for &(s, e) in &found {
+            cuts.extend([s, e].into_iter().filter(|&c| c > at && c < end));
+        }
+        cuts.sort_unstable();
+        cuts.dedup();
+        for w in cuts.windows(2) {
+            let style = match found.iter().any(|&(s, e)| w[0] >= s && w[1] <= e) {
+                true => span.style.patch(hit),
+                false => span.style,
+            };
+            spans.push(Span::styled(
+                content[w[0] - at..w[1] - at].to_owned(),
+                style,
+            ));
+        }
+        at = end;
+    }
+    Line::from(spans)
+}
+
+#[cfg(test)]
+mod test {
+    use tui::layout::Rect;
+
+    use super::Reader;
+    use crate::state::Link;
+
+    #[test]
+    fn reading() {
+        let link = Link {
+            name: "post".into(),
+            read_at: 2,
+            ..Default::default()
+        };
+        let md = "# Title\n\nthe first paragraph is long enough to wrap\n\nsecond *part* here\n";
+        let mut r = Reader::new(0, link, md);
+        r.fit(Rect::new(0, 0, 12, 3));
+        let rows: Vec<String> = r.rows.iter().map(|(_, l)| l.to_string()).collect();
+        assert_eq!(
+            rows,
+            [
+                "# Title",
+                "",
+                "the first",
+                "paragraph is",
+                "long enough",
+                "to wrap",
+                "",
+                "second part",
+                "here",
+            ]
+        );
+        // it starts where it was left
+        assert_eq!((r.top, r.line()), (2, 2));
+
+        r.fit(Rect::new(0, 0, 100, 3));
+        assert_eq!((r.top, r.line()), (2, 2));
+        r.scroll(100);
+        assert_eq!(r.line(), 2);
+        r.fit(Rect::new(0, 0, 12, 3));
+
+        // the last rows can't be scrolled to the top
+        r.find("PART".into());
+        assert_eq!((r.top, r.at), (6, Some(0)));
+        r.find("e".into());
+        assert_eq!((r.top, r.at), (6, Some(3)));
+        r.next(true);
+        assert_eq!(r.top, 4);
+        r.next(false);
+        r.next(false);
+        r.next(false);
+        assert_eq!((r.top, r.at), (0, Some(0)));
+    }
+}
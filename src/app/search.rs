@@ -0,0 +1,51 @@
+use tui::{
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+    Frame,
+};
+
+use super::State;
+
+use crate::search::Target;
+use crate::Luma;
+
+pub fn draw(f: &mut Frame<'_>, luma: &Luma, stat: &State) {
+    super::normal::draw(f, luma, stat);
+    let fbox = super::delete::float_box(f.size());
+
+    f.render_widget(Clear, fbox);
+
+    let block = Block::default().borders(Borders::ALL).title(format!(
+        "Search {:?}: {} found (enter: go, /: search again, q: close)",
+        stat.query,
+        stat.hits.len()
+    ));
+
+    let items = stat.hits.iter().map(|h| {
+        let score = Span::styled(
+            format!("{:>5.1} ", h.score),
+            Style::default().fg(Color::DarkGray),
+        );
+        let spans = match &h.target {
+            Target::Link { tab, name, link } => vec![
+                score,
+                Span::styled(format!("{:<10} ", tab), Style::default().fg(Color::Yellow)),
+                Span::raw(format!("{} ", name)),
+                Span::styled(link.clone(), Style::default().fg(Color::DarkGray)),
+            ],
+            Target::Tab(tab) => vec![
+                score,
+                Span::styled(format!("{:<10} ", tab), Style::default().fg(Color::Yellow)),
+                Span::styled("tab note", Style::default().fg(Color::Cyan)),
+            ],
+        };
+        ListItem::new(Line::from(spans))
+    });
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().bg(Color::Red));
+    let mut list_stat = ListState::default().with_selected(Some(stat.hit_selected));
+    f.render_stateful_widget(list, fbox, &mut list_stat);
+}
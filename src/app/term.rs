@@ -0,0 +1,78 @@
+use tui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    symbols,
+    widgets::{Block, Borders},
+    Frame,
+};
+
+use crate::term::Term;
+
+/// Draws the screen of the program running in the pane and resizes its pty to
+/// fit the space it was given.
+pub fn draw(f: &mut Frame<'_>, area: Rect, term: &Term) {
+    let block = Block::new()
+        .border_set(symbols::border::ROUNDED)
+        .borders(Borders::RIGHT | Borders::TOP | Borders::BOTTOM)
+        .border_style(Style::default().fg(Color::Yellow))
+        .title(term.name.as_str());
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    if inner.width == 0 || inner.height == 0 {
+        return;
+    }
+    term.resize(inner.height, inner.width);
+
+    let parser = term.parser();
+    let screen = parser.screen();
+    let buf = f.buffer_mut();
+
+    for row in 0..inner.height {
+        for col in 0..inner.width {
+            let Some(cell) = screen.cell(row, col) else {
+                continue;
+            };
+            if cell.is_wide_continuation() {
+                continue;
+            }
+
+            let mut style = Style::default()
+                .fg(color(cell.fgcolor()))
+                .bg(color(cell.bgcolor()));
+            if cell.bold() {
+                style = style.add_modifier(Modifier::BOLD);
+            }
+            if cell.italic() {
+                style = style.add_modifier(Modifier::ITALIC);
+            }
+            if cell.underline() {
+                style = style.add_modifier(Modifier::UNDERLINED);
+            }
+            if cell.inverse() {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+
+            let contents = cell.contents();
+            let symbol = if contents.is_empty() { " " } else { &contents };
+            buf.get_mut(inner.x + col, inner.y + row)
+                .set_symbol(symbol)
+                .set_style(style);
+        }
+    }
+
+    if !screen.hide_cursor() {
+        let (row, col) = screen.cursor_position();
+        if row < inner.height && col < inner.width {
+            f.set_cursor(inner.x + col, inner.y + row);
+        }
+    }
+}
+
+fn color(c: vt100::Color) -> Color {
+    match c {
+        vt100::Color::Default => Color::Reset,
+        vt100::Color::Idx(i) => Color::Indexed(i),
+        vt100::Color::Rgb(r, g, b) => Color::Rgb(r, g, b),
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use tui::{
+    layout::Rect,
+    style::{Color, Style},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+
+/// The most toasts that are shown at once, older ones are dropped.
+const MAX_TOASTS: usize = 5;
+const WIDTH: u16 = 48;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    /// How long a toast of this level is shown for.
+    fn timeout(self) -> Duration {
+        match self {
+            Level::Info => Duration::from_secs(3),
+            Level::Warn => Duration::from_secs(6),
+            Level::Error => Duration::from_secs(10),
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Level::Info => Color::Cyan,
+            Level::Warn => Color::Yellow,
+            Level::Error => Color::Red,
+        }
+    }
+}
+
+/// A short message that goes away on its own.
+#[derive(Debug)]
+pub struct Toast {
+    pub level: Level,
+    pub text: String,
+    pub until: Instant,
+}
+
+/// Toasts that are waiting to expire, oldest first.
+#[derive(Debug, Default)]
+pub struct Toasts(VecDeque<Toast>);
+
+impl Toasts {
+    pub fn push(&mut self, level: Level, text: impl Into<String>) {
+        let text = text.into();
+        match level {
+            Level::Info => log::info!("{}", text),
+            Level::Warn => log::warn!("{}", text),
+            Level::Error => log::error!("{}", text),
+        }
+
+        if self.0.len() == MAX_TOASTS {
+            self.0.pop_front();
+        }
+        self.0.push_back(Toast {
+            level,
+            text,
+            until: Instant::now() + level.timeout(),
+        });
+    }
+
+    /// Removes the toasts that have run out of time. Returns if any were.
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let len = self.0.len();
+        self.0.retain(|t| t.until > now);
+        len != self.0.len()
+    }
+
+    /// Time until the next toast expires.
+    pub fn next_expiry(&self, now: Instant) -> Option<Duration> {
+        self.0
+            .iter()
+            .map(|t| t.until.saturating_duration_since(now))
+            .min()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Toast> {
+        self.0.iter()
+    }
+}
+
+/// Stacks the toasts up from the bottom right corner of the area.
+pub fn draw(f: &mut Frame<'_>, area: Rect, toasts: &Toasts) {
+    let width = WIDTH.min(area.width);
+    let mut bottom = area.bottom();
+
+    for t in toasts.iter().rev() {
+        // the text is wrapped to fit inside the borders
+        let lines = t
+            .text
+            .lines()
+            .map(|l| {
+                (l.chars().count() as u16)
+                    .div_ceil(width.saturating_sub(2).max(1))
+                    .max(1)
+            })
+            .sum::<u16>()
+            + 2;
+        if bottom < area.y + lines {
+            break;
+        }
+        bottom -= lines;
+
+        let rect = Rect::new(area.right() - width, bottom, width, lines);
+        let style = Style::default().fg(t.level.color());
+        let p = Paragraph::new(t.text.as_str())
+            .wrap(Wrap { trim: false })
+            .style(style)
+            .block(Block::new().borders(Borders::ALL).border_style(style));
+
+        f.render_widget(Clear, rect);
+        f.render_widget(p, rect);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::{Level, Toasts};
+
+    #[test]
+    fn expire() {
+        let mut toasts = Toasts::default();
+        toasts.push(Level::Info, "a");
+        toasts.push(Level::Error, "b");
+
+        let now = Instant::now();
+        assert!(toasts.next_expiry(now).unwrap() <= Duration::from_secs(3));
+        assert!(!toasts.expire(now));
+        assert!(toasts.expire(now + Duration::from_secs(4)));
+        assert_eq!(toasts.iter().count(), 1);
+        assert!(toasts.expire(now + Duration::from_secs(11)));
+        assert!(toasts.next_expiry(now).is_none());
+    }
+}
@@ -0,0 +1,760 @@
+//! Snapshots of pages, kept in case they go away.
+//!
+//! A snapshot is either one html file with the pictures, stylesheets and
+//! scripts of the page put in it, or a WARC file with the responses for the
+//! page and everything it loads. Links remember their latest snapshot, and
+//! opening a link that has one first checks the page is still up.
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use url::Url;
+
+use crate::prelude::*;
+use crate::state::Link;
+
+/// The most snapshots taken at once.
+const JOBS: usize = 3;
+/// Limits on what is fetched for a snapshot.
+const MAX_PAGE: u64 = 8 * 1024 * 1024;
+const MAX_RESOURCES: usize = 100;
+const MAX_TOTAL: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One html file that needs nothing from the network.
+    Html,
+    /// The responses as they came in a WARC file.
+    Warc,
+}
+
+impl Format {
+    pub fn new(name: &str) -> Option<Format> {
+        match name {
+            "html" => Some(Format::Html),
+            "warc" => Some(Format::Warc),
+            _ => None,
+        }
+    }
+
+    fn ext(self) -> &'static str {
+        match self {
+            Format::Html => "html",
+            Format::Warc => "warc",
+        }
+    }
+}
+
+/// The directory snapshots are kept in for the links saved at the path.
+pub fn dir(links: &Path) -> PathBuf {
+    links.with_extension("archive")
+}
+
+/// The time as `2024-03-18T12:00:00Z`.
+pub fn date(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rest) = (secs / 86400, secs % 86400);
+
+    // days to a civil date, from Howard Hinnant's algorithm
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+/// A response that was fetched.
+#[derive(Debug, Clone)]
+struct Fetched {
+    url: String,
+    status: u16,
+    reason: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Fetched {
+    fn mime(&self) -> String {
+        let ty = self
+            .headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("content-type"));
+        match ty {
+            Some((_, v)) => v.split(';').next().unwrap_or("").trim().to_owned(),
+            None => guess_mime(&self.url).to_owned(),
+        }
+    }
+
+    fn data_uri(&self) -> String {
+        let data = base64::engine::general_purpose::STANDARD.encode(&self.body);
+        format!("data:{};base64,{}", self.mime(), data)
+    }
+}
+
+fn guess_mime(url: &str) -> &'static str {
+    let ext = url.rsplit('/').next().unwrap_or("");
+    let ext = ext.split(['?', '#']).next().unwrap_or("");
+    let ext = ext.rsplit('.').next().unwrap_or("").to_ascii_lowercase();
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
+
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(10))
+        .timeout_read(Duration::from_secs(30))
+        .build()
+}
+
+fn fetch(agent: &ureq::Agent, url: &str, max: u64) -> std::result::Result<Fetched, String> {
+    let resp = match agent.get(url).call() {
+        Ok(r) => r,
+        Err(ureq::Error::Status(code, _)) => return Err(format!("the page answered {}", code)),
+        Err(e) => return Err(e.to_string()),
+    };
+    let headers = resp
+        .headers_names()
+        .into_iter()
+        .filter_map(|n| resp.header(&n).map(|v| (n.clone(), v.to_owned())))
+        .collect();
+    let (url, status, reason) = (
+        resp.get_url().to_owned(),
+        resp.status(),
+        resp.status_text().to_owned(),
+    );
+    let mut body = Vec::new();
+    resp.into_reader()
+        .take(max + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| e.to_string())?;
+    if body.len() as u64 > max {
+        return Err(format!("{} is too big", url));
+    }
+    Ok(Fetched {
+        url,
+        status,
+        reason,
+        headers,
+        body,
+    })
+}
+
+/// Checks that the page is still up, `Err` says why it isn't.
+pub fn check(url: &str) -> std::result::Result<(), String> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(10))
+        .build();
+    let mut res = agent.head(url).call();
+    // not every server knows HEAD
+    if let Err(ureq::Error::Status(405 | 501, _)) = res {
+        res = agent.get(url).call();
+    }
+    match res {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(code, _)) if code == 404 || code == 410 || code >= 500 => {
+            Err(format!("the page answered {}", code))
+        }
+        Err(ureq::Error::Status(..)) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Takes a snapshot of the page into the directory. Gives back its path
+/// and when it was taken.
+pub fn snapshot(
+    url: &str,
+    dir: &Path,
+    format: Format,
+) -> std::result::Result<(PathBuf, String), String> {
+    let agent = agent();
+    let page = fetch(&agent, url, MAX_PAGE)?;
+    let when = SystemTime::now();
+    let date = date(when);
+
+    // everything the page loads, fetched once each
+    let mut fetched: Vec<Fetched> = Vec::new();
+    let mut seen: HashMap<String, Option<usize>> = HashMap::new();
+    let mut total = page.body.len() as u64;
+    let mut get = |url: &Url| -> Option<Fetched> {
+        if let Some(i) = seen.get(url.as_str()) {
+            return i.map(|i| fetched[i].clone());
+        }
+        let ok = matches!(url.scheme(), "http" | "https") && fetched.len() < MAX_RESOURCES;
+        let got = ok
+            .then(|| fetch(&agent, url.as_str(), MAX_TOTAL.saturating_sub(total)))
+            .and_then(|r| {
+                r.map_err(|e| log::info!("not archiving {}: {}", url, e))
+                    .ok()
+            });
+        if let Some(f) = &got {
+            total += f.body.len() as u64;
+            fetched.push(f.clone());
+        }
+        seen.insert(url.to_string(), got.as_ref().map(|_| fetched.len() - 1));
+        got
+    };
+
+    let base = Url::parse(&page.url).map_err(|e| e.to_string())?;
+    let html = String::from_utf8_lossy(&page.body).into_owned();
+    let inlined = inline(&html, base, &mut get);
+    let note = format!("<!-- archived from {} at {} -->\n", page.url, date);
+
+    let out = match format {
+        Format::Html => (note + &inlined).into_bytes(),
+        Format::Warc => warc(&page, &fetched, &date),
+    };
+
+    let mut h = DefaultHasher::new();
+    url.hash(&mut h);
+    let stamp: String = date.chars().filter(char::is_ascii_digit).collect();
+    let name = format!("{:016x}-{}.{}", h.finish(), stamp, format.ext());
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let path = dir.join(name);
+    fs::write(&path, out).map_err(|e| e.to_string())?;
+    Ok((path, date))
+}
+
+/// A tag in a page, with the attributes as they are written.
+struct Tag {
+    name: String,
+    attrs: Vec<(String, Option<String>)>,
+    closed: bool,
+}
+
+impl Tag {
+    fn parse(tag: &str) -> Option<Tag> {
+        let inner = tag.strip_prefix('<')?.strip_suffix('>')?;
+        let (inner, closed) = match inner.strip_suffix('/') {
+            Some(i) => (i, true),
+            None => (inner, false),
+        };
+        let end = inner
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+            .unwrap_or(inner.len());
+        if end == 0 {
+            return None;
+        }
+        let name = inner[..end].to_ascii_lowercase();
+
+        let mut attrs = Vec::new();
+        let mut rest = inner[end..].trim_start();
+        while !rest.is_empty() {
+            let n = rest
+                .find(|c: char| c.is_whitespace() || c == '=')
+                .unwrap_or(rest.len());
+            let key = rest[..n].to_ascii_lowercase();
+            rest = rest[n..].trim_start();
+            let value = match rest.strip_prefix('=') {
+                Some(v) => {
+                    let v = v.trim_start();
+                    let (value, len) = match v.chars().next() {
+                        Some(q @ ('"' | '\'')) => match v[1..].find(q) {
+                            Some(e) => (&v[1..e + 1], e + 2),
+                            None => (&v[1..], v.len()),
+                        },
+                        _ => {
+                            let e = v.find(char::is_whitespace).unwrap_or(v.len());
+                            (&v[..e], e)
+                        }
+                    };
+                    rest = v[len..].trim_start();
+                    Some(value.to_owned())
+                }
+                None => None,
+            };
+            if !key.is_empty() {
+                attrs.push((key, value));
+            }
+        }
+        Some(Tag {
+            name,
+            attrs,
+            closed,
+        })
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        let (_, v) = self.attrs.iter().find(|(k, _)| k == key)?;
+        v.as_deref().map(unescape)
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        let value = value.replace('&', "&amp;").replace('"', "&quot;");
+        match self.attrs.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = Some(value),
+            None => self.attrs.push((key.to_owned(), Some(value))),
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        self.attrs.retain(|(k, _)| k != key);
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{}", self.name)?;
+        for (k, v) in &self.attrs {
+            match v {
+                Some(v) if v.contains('"') => write!(f, " {}='{}'", k, v)?,
+                Some(v) => write!(f, " {}=\"{}\"", k, v)?,
+                None => write!(f, " {}", k)?,
+            }
+        }
+        f.write_str(if self.closed { "/>" } else { ">" })
+    }
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Where the tag ends, skipping `>` in quotes.
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices().skip(1) {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+type Get<'a> = dyn FnMut(&Url) -> Option<Fetched> + 'a;
+
+/// Puts what the page loads into it so it needs nothing from the network.
+/// Links are pointed at the live site.
+fn inline(html: &str, mut base: Url, get: &mut Get<'_>) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    let data = |get: &mut Get<'_>, base: &Url, href: &str| {
+        let url = base.join(href.trim()).ok()?;
+        get(&url).map(|f| f.data_uri())
+    };
+
+    while let Some(i) = rest.find('<') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |e| e + 3);
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+        let Some(end) = tag_end(rest) else {
+            break;
+        };
+        let raw = &rest[..end];
+        rest = &rest[end..];
+        let Some(mut tag) = Tag::parse(raw) else {
+            out.push_str(raw);
+            continue;
+        };
+
+        match tag.name.as_str() {
+            "base" => {
+                if let Some(b) = tag.get("href").and_then(|h| base.join(&h).ok()) {
+                    base = b;
+                }
+                continue;
+            }
+            "meta" => {
+                let csp = tag
+                    .get("http-equiv")
+                    .is_some_and(|h| h.eq_ignore_ascii_case("content-security-policy"));
+                if csp {
+                    continue;
+                }
+            }
+            "img" | "source" | "input" | "video" | "audio" | "track" | "embed" => {
+                for key in ["src", "poster"] {
+                    if let Some(uri) = tag.get(key).and_then(|s| data(get, &base, &s)) {
+                        tag.set(key, &uri);
+                    }
+                }
+                // the picture in src is the one that was kept
+                tag.remove("srcset");
+                tag.remove("loading");
+            }
+            "link" => {
+                let rel = tag.get("rel").unwrap_or_default().to_ascii_lowercase();
+                let href = tag.get("href").and_then(|h| base.join(&h).ok());
+                if let Some(href) = &href {
+                    if rel.split_whitespace().any(|r| r == "stylesheet") {
+                        if let Some(css) = get(href) {
+                            let css = String::from_utf8_lossy(&css.body).into_owned();
+                            out.push_str("<style>");
+                            out.push_str(&inline_css(&css, href, get));
+                            out.push_str("</style>");
+                            continue;
+                        }
+                    } else if rel.contains("icon") {
+                        if let Some(uri) = get(href).map(|f| f.data_uri()) {
+                            tag.set("href", &uri);
+                        }
+                    } else {
+                        tag.set("href", href.as_str());
+                    }
+                }
+            }
+            "a" | "area" => {
+                let href = tag.get("href").filter(|h| !h.starts_with('#'));
+                if let Some(url) = href.and_then(|h| base.join(&h).ok()) {
+                    tag.set("href", url.as_str());
+                }
+            }
+            _ => {}
+        }
+
+        // the insides of scripts and styles aren't html
+        if matches!(tag.name.as_str(), "script" | "style") && !tag.closed {
+            let close = format!("</{}", tag.name);
+            let end = rest.to_ascii_lowercase().find(&close).unwrap_or(rest.len());
+            let (body, after) = rest.split_at(end);
+            rest = after;
+
+            let src = tag.get("src").and_then(|s| base.join(&s).ok());
+            let body = match (tag.name.as_str(), src.and_then(|s| get(&s))) {
+                ("script", Some(js)) => {
+                    tag.remove("src");
+                    tag.remove("integrity");
+                    String::from_utf8_lossy(&js.body).replace("</script", "<\\/script")
+                }
+                ("style", _) => inline_css(body, &base, get),
+                _ => body.to_owned(),
+            };
+            out.push_str(&tag.to_string());
+            out.push_str(&body);
+            continue;
+        }
+        out.push_str(&tag.to_string());
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Puts what the stylesheet loads with `url()` into it.
+fn inline_css(css: &str, base: &Url, get: &mut Get<'_>) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(i) = rest.find("url(") {
+        out.push_str(&rest[..i + 4]);
+        rest = &rest[i + 4..];
+        let Some(end) = rest.find(')') else {
+            break;
+        };
+        let raw = rest[..end].trim();
+        let href = raw.trim_matches(['"', '\'']);
+        let uri = (!href.starts_with("data:"))
+            .then(|| base.join(href).ok())
+            .flatten()
+            .and_then(|u| get(&u))
+            .map(|f| f.data_uri());
+        match uri {
+            Some(u) => out.push_str(&format!("\"{}\"", u)),
+            None => out.push_str(raw),
+        }
+        out.push(')');
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The page and what it loads as WARC response records.
+fn warc(page: &Fetched, resources: &[Fetched], date: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    let info = format!(
+        "software: luma {}\r\nformat: WARC File Format 1.1\r\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    record(
+        &mut out,
+        "warcinfo",
+        None,
+        "application/warc-fields",
+        info.as_bytes(),
+        date,
+    );
+    // the body was unchunked and unzipped, so the headers saying how it was
+    // sent are replaced by its length
+    let sent = ["transfer-encoding", "content-encoding", "content-length"];
+    for f in std::iter::once(page).chain(resources) {
+        let mut block = format!("HTTP/1.1 {} {}\r\n", f.status, f.reason).into_bytes();
+        for (k, v) in &f.headers {
+            if !sent.iter().any(|s| k.eq_ignore_ascii_case(s)) {
+                block.extend_from_slice(format!("{}: {}\r\n", k, v).as_bytes());
+            }
+        }
+        block.extend_from_slice(format!("Content-Length: {}\r\n\r\n", f.body.len()).as_bytes());
+        block.extend_from_slice(&f.body);
+        let ty = "application/http;msgtype=response";
+        record(&mut out, "response", Some(&f.url), ty, &block, date);
+    }
+    out
+}
+
+fn record(out: &mut Vec<u8>, ty: &str, uri: Option<&str>, content: &str, block: &[u8], date: &str) {
+    // ids only have to be unique, so they are made from what is in them
+    let mut h = DefaultHasher::new();
+    (ty, uri, date, out.len()).hash(&mut h);
+    let a = h.finish();
+    block.hash(&mut h);
+    let b = h.finish();
+    let id = format!(
+        "{:08x}-{:04x}-4{:03x}-8{:03x}-{:012x}",
+        a >> 32,
+        (a >> 16) & 0xffff,
+        a & 0xfff,
+        b >> 52,
+        b & 0xffff_ffff_ffff
+    );
+
+    let mut head = format!(
+        "WARC/1.1\r\nWARC-Type: {}\r\nWARC-Record-ID: <urn:uuid:{}>\r\nWARC-Date: {}\r\n",
+        ty, id, date
+    );
+    if let Some(u) = uri {
+        head.push_str(&format!("WARC-Target-URI: {}\r\n", u));
+    }
+    head.push_str(&format!(
+        "Content-Type: {}\r\nContent-Length: {}\r\n\r\n",
+        content,
+        block.len()
+    ));
+    out.extend_from_slice(head.as_bytes());
+    out.extend_from_slice(block);
+    out.extend_from_slice(b"\r\n\r\n");
+}
+
+/// Something that finished in the background.
+#[derive(Debug)]
+pub enum Done {
+    /// A snapshot of the link was taken, with its path and date.
+    Saved(usize, Link, std::result::Result<(PathBuf, String), String>),
+    /// The link was checked, `Err` says why it is down.
+    Checked(usize, Link, std::result::Result<(), String>),
+}
+
+#[derive(Debug)]
+enum Work {
+    Snapshot(JoinHandle<std::result::Result<(PathBuf, String), String>>),
+    Check(JoinHandle<std::result::Result<(), String>>),
+}
+
+/// Snapshots being taken and links being checked.
+#[derive(Debug, Default)]
+pub struct Archives {
+    queued: VecDeque<(usize, Link, PathBuf, Format)>,
+    running: Vec<(usize, Link, Work)>,
+}
+
+impl Archives {
+    /// Queues a snapshot of the link into the directory.
+    pub fn snapshot(&mut self, tabb: usize, link: Link, dir: PathBuf, format: Format) {
+        self.queued.push_back((tabb, link, dir, format));
+    }
+
+    /// Starts checking if the link's page is up.
+    pub fn check(&mut self, tabb: usize, link: Link) {
+        let url = link.link.clone();
+        let work = Work::Check(std::thread::spawn(move || check(&url)));
+        self.running.push((tabb, link, work));
+    }
+
+    /// Starts queued snapshots and gives back what finished since the last
+    /// call.
+    pub fn tick(&mut self) -> Vec<Done> {
+        let snapshots = (self.running.iter())
+            .filter(|r| matches!(r.2, Work::Snapshot(_)))
+            .count();
+        for _ in snapshots..JOBS {
+            let Some((tabb, link, dir, format)) = self.queued.pop_front() else {
+                break;
+            };
+            let url = link.link.clone();
+            let work = Work::Snapshot(std::thread::spawn(move || snapshot(&url, &dir, format)));
+            self.running.push((tabb, link, work));
+        }
+
+        let finished = |w: &Work| match w {
+            Work::Snapshot(t) => t.is_finished(),
+            Work::Check(t) => t.is_finished(),
+        };
+        let (done, running): (Vec<_>, Vec<_>) = std::mem::take(&mut self.running)
+            .into_iter()
+            .partition(|r| finished(&r.2));
+        self.running = running;
+
+        let panicked = "it panicked".to_owned();
+        done.into_iter()
+            .map(|(tabb, link, work)| match work {
+                Work::Snapshot(t) => {
+                    Done::Saved(tabb, link, t.join().unwrap_or(Err(panicked.clone())))
+                }
+                Work::Check(t) => {
+                    Done::Checked(tabb, link, t.join().unwrap_or(Err(panicked.clone())))
+                }
+            })
+            .collect()
+    }
+
+    pub fn busy(&self) -> bool {
+        !self.queued.is_empty() || !self.running.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use super::{check, date, snapshot, Format};
+    use crate::prelude::*;
+
+    /// Serves the pages at their paths gzipped in chunks, anything else is
+    /// not found.
+    fn serve(pages: &'static [(&'static str, &'static str, &'static [u8])]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut first = String::new();
+                reader.read_line(&mut first).unwrap();
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                        break;
+                    }
+                }
+                let path = first.split_whitespace().nth(1).unwrap_or("");
+                let (status, ty, body) = match pages.iter().find(|p| p.0 == path) {
+                    Some((_, ty, body)) => ("200 OK", *ty, *body),
+                    None => ("404 Not Found", "text/plain", &b"gone"[..]),
+                };
+                let head = first.starts_with("HEAD");
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Encoding: gzip\r\n\
+                     Transfer-Encoding: chunked\r\nConnection: close\r\n\r\n",
+                    status, ty,
+                );
+                if !head {
+                    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+                    gz.write_all(body).unwrap();
+                    let gz = gz.finish().unwrap();
+                    for chunk in gz.chunks(16) {
+                        let _ = write!(stream, "{:x}\r\n", chunk.len());
+                        let _ = stream.write_all(chunk);
+                        let _ = stream.write_all(b"\r\n");
+                    }
+                    let _ = stream.write_all(b"0\r\n\r\n");
+                }
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn snapshots() {
+        let site = serve(&[
+            (
+                "/post",
+                "text/html",
+                b"<html><head><link rel=stylesheet href=\"/s.css\"><script src=\"js/a.js\"></script>\
+                  <style>b { background: url('/dot.png') }</style></head>\
+                  <body><!-- <img src=\"/dot.png\"> --><a href=\"/other?a=1&amp;b=2\">other</a>\
+                  <img src=\"dot.png\" srcset=\"big.png 2x\"><img src=\"/missing.png\"></body></html>",
+            ),
+            ("/s.css", "text/css", b"p { background: url(dot.png) }"),
+            ("/js/a.js", "text/javascript", b"let x = '</script>';"),
+            ("/dot.png", "image/png", b"png"),
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!("{}/post", site);
+
+        let (path, _) = snapshot(&url, dir.path(), Format::Html).unwrap();
+        let html = fs::read_to_string(&path).unwrap();
+        let png = "data:image/png;base64,cG5n";
+        assert!(html.starts_with(&format!("<!-- archived from {} at ", url)));
+        assert!(html.contains(&format!(
+            "<style>p {{ background: url(\"{}\") }}</style>",
+            png
+        )));
+        assert!(html.contains("<script>let x = '<\\/script>';</script>"));
+        assert!(html.contains(&format!("b {{ background: url(\"{}\") }}", png)));
+        assert!(html.contains("<!-- <img src=\"/dot.png\"> -->"));
+        assert!(html.contains(&format!("<a href=\"{}/other?a=1&amp;b=2\">", site)));
+        assert!(html.contains(&format!("<img src=\"{}\">", png)));
+        assert!(html.contains("<img src=\"/missing.png\">"));
+
+        let (path, _) = snapshot(&url, dir.path(), Format::Warc).unwrap();
+        let warc = String::from_utf8(fs::read(&path).unwrap()).unwrap();
+        assert!(warc.starts_with("WARC/1.1\r\nWARC-Type: warcinfo\r\n"));
+        let targets: Vec<&str> = warc
+            .lines()
+            .filter_map(|l| l.strip_prefix("WARC-Target-URI: "))
+            .collect();
+        let want: Vec<String> = ["/post", "/s.css", "/dot.png", "/js/a.js"]
+            .iter()
+            .map(|p| format!("{}{}", site, p))
+            .collect();
+        assert_eq!(targets, want);
+        // the bodies are kept as they were read, with headers to match
+        assert!(!warc.contains("Transfer-Encoding") && !warc.contains("Content-Encoding"));
+        assert!(warc.contains("\r\nContent-Length: 3\r\n\r\npng\r\n"));
+
+        assert_eq!(check(&url), Ok(()));
+        assert_eq!(
+            check(&format!("{}/gone", site)),
+            Err("the page answered 404".to_owned())
+        );
+        assert!(snapshot(&format!("{}/gone", site), dir.path(), Format::Html).is_err());
+
+        let day = UNIX_EPOCH + Duration::from_secs(1_710_763_200);
+        assert_eq!(date(day), "2024-03-18T12:00:00Z");
+    }
+}
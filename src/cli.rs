@@ -1,5 +1,7 @@
 use std::path::PathBuf;
 
+use crate::import::ImportDir;
+
 #[derive(Debug)]
 pub struct Args {
     /// The input file
@@ -10,6 +12,14 @@ pub struct Args {
 
     /// Path where log files should be written
     pub file: Option<PathBuf>,
+
+    /// The lua file to load instead of the usual config
+    pub config: Option<PathBuf>,
+
+    /// Import this directory into the links read from the input file and save
+    /// them where the app does, instead of opening the app,
+    /// from `luma import-dir <dir> [-t tab] [-i glob].. [-x glob].. <input>`
+    pub import: Option<ImportDir>,
 }
 
 pub fn parse() -> Args {
@@ -18,20 +28,56 @@ pub fn parse() -> Args {
     let mut log = false;
     let mut input = None;
     let mut file = None;
+    let mut config = None;
+    let mut import: Option<ImportDir> = None;
 
     while let Some(arg) = args.next() {
         match arg.as_str() {
+            "import-dir" => {
+                let path = PathBuf::from(args.next().unwrap());
+                import = Some(ImportDir {
+                    path: path.canonicalize().unwrap_or(path),
+                    ..Default::default()
+                });
+            }
+            "-t" | "--tab" if import.is_some() => {
+                import.as_mut().unwrap().tab = args.next().unwrap();
+            }
+            "-i" | "--include" if import.is_some() => {
+                import.as_mut().unwrap().include.push(args.next().unwrap());
+            }
+            "-x" | "--exclude" if import.is_some() => {
+                import.as_mut().unwrap().exclude.push(args.next().unwrap());
+            }
             "-l" | "--log" => log = true,
             "-L" | "--log-file" => {
                 let f = args.next().unwrap();
                 file = Some(PathBuf::from(f));
             }
+            "-c" | "--config" => {
+                let c = args.next().unwrap();
+                config = Some(PathBuf::from(c));
+            }
             _ => input = Some(PathBuf::from(arg)),
         }
     }
 
+    // the tab is named after the directory unless it was given
+    if let Some(i) = import.as_mut().filter(|i| i.tab.is_empty()) {
+        i.tab = i
+            .path
+            .file_name()
+            .map_or_else(|| "files".to_owned(), |n| n.to_string_lossy().into_owned());
+    }
+
     if let Some(input) = input {
-        Args { input, log, file }
+        Args {
+            input,
+            log,
+            file,
+            config,
+            import,
+        }
     } else {
         // show help
         std::process::exit(1);
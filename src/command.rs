@@ -0,0 +1,496 @@
+//! The commands typed on the `:` command line.
+
+use std::path::PathBuf;
+
+use crate::input::Msg;
+use crate::state::Link;
+
+/// Names of the commands, the actions from [`Msg::NAMES`] can be run too.
+pub const COMMANDS: &[&str] = &[
+    "tab",
+    "sort",
+    "mv",
+    "filter",
+    "search",
+    "w",
+    "q",
+    "q!",
+    "wq",
+    "x",
+    "export",
+    "download",
+    "archive",
+    "feed",
+    "opml",
+    "mpd",
+    "import-dir",
+    "import-playlist",
+];
+const TAB_COMMANDS: &[&str] = &["new", "rename", "delete"];
+const FEED_COMMANDS: &[&str] = &["add", "remove"];
+const OPML_COMMANDS: &[&str] = &["import", "export"];
+const MPD_COMMANDS: &[&str] = &["add", "status", "library", "playlists"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Add a tab with the name.
+    NewTab(String),
+    /// Rename the selected tab.
+    RenameTab(String),
+    /// Delete the selected tab.
+    DeleteTab,
+    /// Go to the tab with the number or name.
+    SelectTab(String),
+    /// Sort the selected tab by the field, backwards if set.
+    Sort(Field, bool),
+    /// Move the selected link to the end of the tab with the number or name.
+    Move(String),
+    /// Only show the links that match, or all of them when `None`.
+    Filter(Option<Filter>),
+    Write,
+    /// Quit, without saving if set.
+    Quit(bool),
+    WriteQuit,
+    Export(Format, PathBuf),
+    /// Download the selected link, or every link shown in the tab if set.
+    Download(bool),
+    /// Take a snapshot of the selected link, or of every link shown in the
+    /// tab if set.
+    Archive(bool),
+    /// Look over the links in the text file before adding them.
+    Import(PathBuf),
+    /// Import the files in the directory that match the globs into the tab.
+    ImportDir {
+        path: PathBuf,
+        include: Vec<String>,
+        exclude: Vec<String>,
+    },
+    /// Import the M3U or XSPF playlist into a new tab.
+    ImportPlaylist(PathBuf),
+    /// Subscribe the selected tab to the feed.
+    AddFeed(String),
+    /// Unsubscribe the selected tab from the feed.
+    RemoveFeed(String),
+    /// Subscribe to the feeds in the OPML file, in tabs named after their
+    /// outlines.
+    ImportOpml(PathBuf),
+    /// Write the feeds every tab is subscribed to as OPML.
+    ExportOpml(PathBuf),
+    /// Add the selected link's file to MPD's queue, or every link shown in
+    /// the tab if set.
+    MpdAdd(bool),
+    /// Show what MPD is playing, and keep showing it.
+    MpdStatus,
+    /// Import MPD's library into a tab.
+    MpdLibrary,
+    /// Import each of MPD's playlists into a tab with its name.
+    MpdPlaylists,
+    /// Search the text of every link for the query.
+    Search(String),
+    /// Run the action with the name, built in or from the config.
+    Action(String),
+}
+
+/// A field of a [`Link`] that can be sorted and filtered on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Name,
+    Link,
+    File,
+    Desc,
+    Artist,
+    Color,
+}
+
+impl Field {
+    pub const NAMES: &'static [&'static str] = &["name", "link", "file", "desc", "artist", "color"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let f = match name {
+            "name" => Field::Name,
+            "link" => Field::Link,
+            "file" => Field::File,
+            "desc" => Field::Desc,
+            "artist" => Field::Artist,
+            // the color is what links are tagged with
+            "color" | "tag" => Field::Color,
+            _ => return None,
+        };
+        Some(f)
+    }
+
+    pub fn get(self, link: &Link) -> Option<&str> {
+        match self {
+            Field::Name => Some(&link.name),
+            Field::Link => Some(&link.link),
+            Field::File => link.file.as_deref(),
+            Field::Desc => link.desc.as_deref(),
+            Field::Artist => link.artist.as_deref(),
+            Field::Color => link.color.as_deref(),
+        }
+    }
+}
+
+/// Words that all have to be found in a link, ignoring case. A word like
+/// `field:text` only looks in that field, or the link's note for `note:text`.
+/// Others look in the name, link, description and note.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    pub query: String,
+    terms: Vec<(Term, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Term {
+    Any,
+    Field(Field),
+    Note,
+}
+
+impl Filter {
+    pub fn new(query: &str) -> Result<Self, String> {
+        let terms = query
+            .split_whitespace()
+            .map(|w| match w.split_once(':') {
+                Some(("note", text)) => Ok((Term::Note, text.to_lowercase())),
+                Some((f, text)) => match Field::from_name(f) {
+                    Some(f) => Ok((Term::Field(f), text.to_lowercase())),
+                    None => Err(format!("unknown field {:?}", f)),
+                },
+                None => Ok((Term::Any, w.to_lowercase())),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            query: query.trim().to_owned(),
+            terms,
+        })
+    }
+
+    /// Checks the link, `note` gives its note when one is looked at.
+    pub fn matches(&self, link: &Link, note: impl Fn(&Link) -> Option<String>) -> bool {
+        let has =
+            |f: Field, text: &str| f.get(link).is_some_and(|v| v.to_lowercase().contains(text));
+        // the note is only read once, and only if it is needed
+        let read = std::cell::OnceCell::new();
+        let in_note = |text: &str| {
+            read.get_or_init(|| note(link).map(|n| n.to_lowercase()))
+                .as_ref()
+                .is_some_and(|n| n.contains(text))
+        };
+        self.terms.iter().all(|(t, text)| match t {
+            Term::Field(f) => has(*f, text),
+            Term::Note => in_note(text),
+            Term::Any => {
+                [Field::Name, Field::Link, Field::Desc]
+                    .into_iter()
+                    .any(|f| has(f, text))
+                    || in_note(text)
+            }
+        })
+    }
+}
+
+/// The formats links can be exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Markdown,
+    Json,
+    /// An M3U8 playlist.
+    M3u,
+    /// An XSPF playlist.
+    Xspf,
+}
+
+impl Format {
+    pub const NAMES: &'static [&'static str] = &["md", "json", "m3u", "xspf"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "md" | "markdown" => Some(Format::Markdown),
+            "json" => Some(Format::Json),
+            "m3u" | "m3u8" => Some(Format::M3u),
+            "xspf" => Some(Format::Xspf),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a command line, without the `:`.
+pub fn parse(line: &str) -> Result<Command, String> {
+    let line = line.trim();
+    let (name, rest) = match line.split_once(char::is_whitespace) {
+        Some((n, r)) => (n, r.trim()),
+        None => (line, ""),
+    };
+    let need = |what: &str| {
+        if rest.is_empty() {
+            Err(format!("{} needs a {}", name, what))
+        } else {
+            Ok(rest.to_owned())
+        }
+    };
+
+    let cmd = match name {
+        "tab" => {
+            let (sub, arg) = match rest.split_once(char::is_whitespace) {
+                Some((s, a)) => (s, a.trim()),
+                None => (rest, ""),
+            };
+            match sub {
+                "new" if !arg.is_empty() => Command::NewTab(arg.to_owned()),
+                "rename" if !arg.is_empty() => Command::RenameTab(arg.to_owned()),
+                "new" | "rename" => return Err(format!("tab {} needs a name", sub)),
+                "delete" => Command::DeleteTab,
+                "" => return Err("tab needs a tab or one of new, rename, delete".into()),
+                _ => Command::SelectTab(rest.to_owned()),
+            }
+        }
+        "sort" => {
+            let mut words = rest.split_whitespace();
+            let field = words.next().ok_or("sort needs a field")?;
+            let field =
+                Field::from_name(field).ok_or_else(|| format!("unknown field {:?}", field))?;
+            let rev = match words.next() {
+                None | Some("asc") => false,
+                Some("desc") | Some("rev") => true,
+                Some(w) => return Err(format!("unknown sort order {:?}", w)),
+            };
+            Command::Sort(field, rev)
+        }
+        "mv" => Command::Move(need("tab")?),
+        "filter" if rest.is_empty() => Command::Filter(None),
+        "filter" => Command::Filter(Some(Filter::new(rest)?)),
+        "w" => Command::Write,
+        "q" => Command::Quit(false),
+        "q!" => Command::Quit(true),
+        "wq" | "x" => Command::WriteQuit,
+        "export" => {
+            let (fmt, path) = rest
+                .split_once(char::is_whitespace)
+                .ok_or("export needs a format and a path")?;
+            let format =
+                Format::from_name(fmt).ok_or_else(|| format!("unknown format {:?}", fmt))?;
+            Command::Export(format, PathBuf::from(path.trim()))
+        }
+        "download" => match rest {
+            "" => Command::Download(false),
+            "all" => Command::Download(true),
+            _ => return Err(format!("download takes nothing or all, not {:?}", rest)),
+        },
+        "archive" => match rest {
+            "" => Command::Archive(false),
+            "all" => Command::Archive(true),
+            _ => return Err(format!("archive takes nothing or all, not {:?}", rest)),
+        },
+        "search" => Command::Search(rest.to_owned()),
+        "feed" => match rest.split_once(char::is_whitespace) {
+            Some(("add", url)) => Command::AddFeed(url.trim().to_owned()),
+            Some(("remove", url)) => Command::RemoveFeed(url.trim().to_owned()),
+            _ => return Err("feed takes add or remove and a url".into()),
+        },
+        "opml" => match rest.split_once(char::is_whitespace) {
+            Some(("import", path)) => Command::ImportOpml(PathBuf::from(path.trim())),
+            Some(("export", path)) => Command::ExportOpml(PathBuf::from(path.trim())),
+            _ => return Err("opml takes import or export and a file".into()),
+        },
+        "mpd" => match rest {
+            "add" => Command::MpdAdd(false),
+            "add all" => Command::MpdAdd(true),
+            "status" => Command::MpdStatus,
+            "library" => Command::MpdLibrary,
+            "playlists" => Command::MpdPlaylists,
+            _ => return Err("mpd takes add, add all, status, library or playlists".into()),
+        },
+        "import" => Command::Import(PathBuf::from(need("file")?)),
+        "import-playlist" => Command::ImportPlaylist(PathBuf::from(need("file")?)),
+        "import-dir" => {
+            let mut words = rest.split_whitespace();
+            let path = words.next().ok_or("import-dir needs a directory")?;
+            // globs starting with `!` leave files out
+            let (exclude, include): (Vec<_>, Vec<_>) = words.partition(|w| w.starts_with('!'));
+            Command::ImportDir {
+                path: PathBuf::from(path),
+                include: include.into_iter().map(str::to_owned).collect(),
+                exclude: exclude.iter().map(|w| w[1..].to_owned()).collect(),
+            }
+        }
+        "" => return Err("no command given".into()),
+        _ if rest.is_empty() => Command::Action(name.to_owned()),
+        _ => return Err(format!("unknown command {:?}", name)),
+    };
+    Ok(cmd)
+}
+
+/// Ways to finish the last word of the line. `tabs` and `actions` are the
+/// names that can be given as arguments.
+pub fn complete(line: &str, tabs: &[&str], actions: &[&str]) -> Vec<String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let done = line.is_empty() || line.ends_with(char::is_whitespace);
+    let last = if done {
+        ""
+    } else {
+        words.last().copied().unwrap_or("")
+    };
+    // the words before the one being completed
+    let before = &words[..words.len() - usize::from(!done)];
+
+    let options: Vec<String> = match before {
+        [] => COMMANDS
+            .iter()
+            .chain(Msg::NAMES)
+            .chain(actions)
+            .map(|s| s.to_string())
+            .collect(),
+        ["tab"] => TAB_COMMANDS
+            .iter()
+            .chain(tabs)
+            .map(|s| s.to_string())
+            .collect(),
+        ["mv"] => tabs.iter().map(|s| s.to_string()).collect(),
+        ["sort"] => Field::NAMES.iter().map(|s| s.to_string()).collect(),
+        ["sort", _] => vec!["asc".into(), "desc".into()],
+        ["filter", ..] => Field::NAMES
+            .iter()
+            .chain(&["note"])
+            .map(|f| format!("{}:", f))
+            .collect(),
+        ["download"] | ["archive"] | ["mpd", "add"] => vec!["all".into()],
+        ["mpd"] => MPD_COMMANDS.iter().map(|s| s.to_string()).collect(),
+        ["feed"] => FEED_COMMANDS.iter().map(|s| s.to_string()).collect(),
+        ["opml"] => OPML_COMMANDS.iter().map(|s| s.to_string()).collect(),
+        ["export"] => Format::NAMES.iter().map(|s| s.to_string()).collect(),
+        ["export", _] | ["import-dir"] | ["import"] | ["import-playlist"] | ["opml", _] => {
+            return complete_path(last)
+        }
+        _ => Vec::new(),
+    };
+
+    // actions can share names with commands, the first one is kept
+    let mut seen = std::collections::HashSet::new();
+    options
+        .into_iter()
+        .filter(|o| o.starts_with(last) && o != last && seen.insert(o.clone()))
+        .collect()
+}
+
+fn complete_path(part: &str) -> Vec<String> {
+    let (dir, file) = match part.rfind('/') {
+        Some(i) => (&part[..=i], &part[i + 1..]),
+        None => ("", part),
+    };
+    let read = if dir.is_empty() { "." } else { dir };
+    let Ok(entries) = std::fs::read_dir(read) else {
+        return Vec::new();
+    };
+
+    let mut paths: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().into_string().ok()?;
+            if !name.starts_with(file) || (name.starts_with('.') && !file.starts_with('.')) {
+                return None;
+            }
+            let slash = if e.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, name, slash))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::{complete, parse, Command, Field, Filter, Format};
+    use crate::state::Link;
+
+    #[test]
+    fn commands() {
+        assert_eq!(
+            parse("tab new my music"),
+            Ok(Command::NewTab("my music".into()))
+        );
+        assert_eq!(parse("tab 2"), Ok(Command::SelectTab("2".into())));
+        assert_eq!(
+            parse("sort name desc"),
+            Ok(Command::Sort(Field::Name, true))
+        );
+        assert_eq!(parse(" mv 3 "), Ok(Command::Move("3".into())));
+        assert_eq!(parse("q!"), Ok(Command::Quit(true)));
+        assert_eq!(
+            parse("export md out.md"),
+            Ok(Command::Export(Format::Markdown, PathBuf::from("out.md")))
+        );
+        assert_eq!(parse("filter"), Ok(Command::Filter(None)));
+        assert_eq!(parse("download all"), Ok(Command::Download(true)));
+        assert_eq!(parse("archive"), Ok(Command::Archive(false)));
+        assert_eq!(
+            parse("feed add https://a.example/rss"),
+            Ok(Command::AddFeed("https://a.example/rss".into()))
+        );
+        assert!(parse("feed https://a.example/rss").is_err());
+        assert_eq!(parse("mpd add all"), Ok(Command::MpdAdd(true)));
+        assert!(parse("mpd play").is_err());
+        assert_eq!(
+            parse("export m3u8 mix.m3u8"),
+            Ok(Command::Export(Format::M3u, PathBuf::from("mix.m3u8")))
+        );
+        assert_eq!(
+            parse("import-playlist ~/mix.xspf"),
+            Ok(Command::ImportPlaylist(PathBuf::from("~/mix.xspf")))
+        );
+        assert_eq!(
+            parse("search \"rust book\" prog*"),
+            Ok(Command::Search("\"rust book\" prog*".into()))
+        );
+        assert_eq!(
+            parse("import-dir ~/music *.flac !live*"),
+            Ok(Command::ImportDir {
+                path: PathBuf::from("~/music"),
+                include: vec!["*.flac".into()],
+                exclude: vec!["live*".into()],
+            })
+        );
+        assert_eq!(parse("open"), Ok(Command::Action("open".into())));
+        assert!(parse("sort size").is_err());
+        assert!(parse("tab new").is_err());
+        assert!(parse("open now").is_err());
+    }
+
+    #[test]
+    fn filter() {
+        let f = Filter::new("tag:video Cat").unwrap();
+        let mut link = Link {
+            name: "cats".into(),
+            link: "https://youtube.com".into(),
+            color: Some("video".into()),
+            ..Default::default()
+        };
+        assert!(f.matches(&link, |_| None));
+        link.color = None;
+        assert!(!f.matches(&link, |_| None));
+        assert!(Filter::new("size:1").is_err());
+
+        let f = Filter::new("note:purr").unwrap();
+        assert!(f.matches(&link, |_| Some("# Cats\nthey Purr".into())));
+        assert!(!f.matches(&link, |_| None));
+    }
+
+    #[test]
+    fn completion() {
+        let tabs = ["music", "web"];
+        assert_eq!(complete("ta", &tabs, &[]), ["tab", "tag", "tab_note"]);
+        assert_eq!(complete("tab m", &tabs, &[]), ["music"]);
+        assert_eq!(complete("sort n", &tabs, &[]), ["name"]);
+        assert_eq!(
+            complete("do", &tabs, &["download"]),
+            ["download", "down", "downloads"]
+        );
+        assert_eq!(
+            complete("export ", &tabs, &[]),
+            ["md", "json", "m3u", "xspf"]
+        );
+        assert_eq!(complete("export md src/mai", &tabs, &[]), ["src/main.rs"]);
+    }
+}
@@ -0,0 +1,332 @@
+//! Options that change how the app behaves, set from the config script.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::archive;
+use crate::event::Key;
+use crate::mpd;
+use crate::prelude::*;
+use crate::state::{Link, OpenCommand, Validate};
+
+/// Fields of a [`Link`] an action can capture into, and `yaml` for a map of
+/// fields.
+const CAPTURES: &[&str] = &["name", "link", "file", "desc", "artist", "color", "yaml"];
+
+/// Ways of drawing pictures.
+const IMAGES: &[&str] = &["auto", "kitty", "sixel", "iterm", "blocks", "none"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Options {
+    /// Width of the link list in tenths of the screen.
+    pub size: u16,
+    /// Program and arguments used to open links.
+    pub opener: Vec<String>,
+    /// Program and arguments used to play media, like podcast episodes.
+    pub player: Vec<String>,
+    /// Program and arguments used to edit text. It is run in the terminal pane.
+    pub editor: Vec<String>,
+    /// Commands the user can run on the selected link.
+    pub actions: Vec<Action>,
+    /// Where links are downloaded to. A leading `~` is the home directory.
+    pub download_dir: String,
+    /// The most downloads that run at once.
+    pub download_jobs: usize,
+    /// Where notes are kept. Empty for a folder in the data directory.
+    pub vault: String,
+    /// How pictures are drawn in the preview: `auto`, `kitty`, `sixel`,
+    /// `iterm`, `blocks` or `none`.
+    pub images: String,
+    /// Where snapshots of pages are kept. Empty for a folder next to the
+    /// links.
+    pub archive_dir: String,
+    /// How snapshots are saved: `html` for one file with everything in it or
+    /// `warc`.
+    pub archive_format: String,
+    /// Minutes between refreshing the feeds of tabs, 0 to only refresh them
+    /// by hand.
+    pub feed_refresh: u64,
+    /// Where MPD is, as `host:port` or the path of its socket, with an
+    /// optional `password@` before it. Empty for `MPD_HOST` and `MPD_PORT`,
+    /// or MPD on this machine.
+    pub mpd: String,
+    /// MPD's music directory, so files in it can be added to its queue. Other
+    /// files can only be added when MPD is connected to by its socket.
+    pub music_dir: String,
+}
+
+/// A command run on the selected link, set in `luma.o.actions`.
+///
+/// ```lua
+/// table.insert(luma.o.actions, {
+///     name = "download",
+///     key = "<C-d>",
+///     cmd = { "yt-dlp", "--print", "after_move:filepath", "{link}" },
+///     capture = "file",
+/// })
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Action {
+    /// Shown in the help and used to bind it with `luma.keymap.set`.
+    pub name: String,
+    /// The key that runs it in vim notation.
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Program and arguments. `{link}`, `{name}`, `{file}` and `{tab}` are
+    /// replaced with those of the selected link.
+    pub cmd: Vec<String>,
+    /// The field of the link that is set to what the command prints.
+    #[serde(default)]
+    pub capture: Option<String>,
+    /// If the command is run in the terminal pane.
+    #[serde(default)]
+    pub term: bool,
+}
+
+impl Action {
+    pub fn key(&self) -> Option<Key> {
+        self.key.as_ref().and_then(|k| k.parse().ok())
+    }
+
+    /// The command with the placeholders filled in from the link.
+    pub fn command(&self, link: &Link, tab: &str) -> Option<OpenCommand> {
+        let argv: Vec<String> = self
+            .cmd
+            .iter()
+            .map(|a| {
+                a.replace("{link}", &link.link)
+                    .replace("{name}", &link.name)
+                    .replace("{file}", link.file.as_deref().unwrap_or(""))
+                    .replace("{tab}", tab)
+            })
+            .collect();
+        let cmd = OpenCommand::new(&argv)?;
+        Some(if self.term { cmd.in_term() } else { cmd })
+    }
+}
+
+/// Sets the field of the link to the output of a command. For `yaml` the
+/// output is a map of the fields to change.
+pub fn capture(link: &Link, field: &str, out: &str) -> std::result::Result<Link, Vec<String>> {
+    let yaml::Value::Mapping(mut map) = yaml::to_value(link).map_err(|e| vec![e.to_string()])?
+    else {
+        unreachable!("links are maps");
+    };
+
+    if field == "yaml" {
+        match yaml::from_str::<yaml::Mapping>(out) {
+            Ok(new) => map.extend(new),
+            Err(e) => return Err(vec![format!("output is not a map of fields: {}", e)]),
+        }
+    } else {
+        let out = out.trim_end_matches(['\r', '\n']).to_owned();
+        map.insert(field.into(), out.into());
+    }
+
+    let new: Link = yaml::from_value(yaml::Value::Mapping(map)).map_err(|e| vec![e.to_string()])?;
+    let errs = new.validate();
+    if errs.is_empty() {
+        Ok(new)
+    } else {
+        Err(errs)
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        let owned = |a: &[&str]| a.iter().map(|s| s.to_string()).collect();
+        Self {
+            size: 4,
+            opener: owned(LINK_OPENER),
+            player: owned(FILE_OPENER),
+            editor: owned(TEXT_OPENER),
+            actions: Vec::new(),
+            download_dir: "~/dln".into(),
+            download_jobs: 3,
+            vault: String::new(),
+            images: "auto".into(),
+            archive_dir: String::new(),
+            archive_format: "html".into(),
+            feed_refresh: 60,
+            mpd: String::new(),
+            music_dir: String::new(),
+        }
+    }
+}
+
+impl Options {
+    pub fn opener(&self) -> OpenCommand {
+        OpenCommand::new(&self.opener)
+            .or_else(|| OpenCommand::new(LINK_OPENER))
+            .unwrap()
+    }
+
+    pub fn player(&self) -> OpenCommand {
+        OpenCommand::new(&self.player)
+            .or_else(|| OpenCommand::new(FILE_OPENER))
+            .unwrap()
+    }
+
+    pub fn editor(&self) -> OpenCommand {
+        OpenCommand::new(&self.editor)
+            .or_else(|| OpenCommand::new(TEXT_OPENER))
+            .unwrap()
+            .in_term()
+    }
+
+    pub fn action(&self, name: &str) -> Option<&Action> {
+        self.actions.iter().find(|a| a.name == name)
+    }
+
+    pub fn download_dir(&self) -> PathBuf {
+        expand(&self.download_dir)
+    }
+
+    pub fn vault(&self) -> PathBuf {
+        if self.vault.is_empty() {
+            data_dir().join("vault")
+        } else {
+            expand(&self.vault)
+        }
+    }
+
+    /// Where snapshots of the links saved at the path are kept.
+    pub fn archive_dir(&self, links: &Path) -> PathBuf {
+        if self.archive_dir.is_empty() {
+            archive::dir(links)
+        } else {
+            expand(&self.archive_dir)
+        }
+    }
+
+    pub fn archive_format(&self) -> archive::Format {
+        archive::Format::new(&self.archive_format).unwrap_or(archive::Format::Html)
+    }
+
+    pub fn mpd(&self) -> String {
+        mpd::address(&self.mpd)
+    }
+
+    pub fn music_dir(&self) -> Option<PathBuf> {
+        Some(expand(&self.music_dir)).filter(|_| !self.music_dir.is_empty())
+    }
+
+    /// Percentage of the screen the list takes up.
+    pub fn list_width(&self) -> u16 {
+        self.size.clamp(1, 9) * 10
+    }
+}
+
+impl Validate for Options {
+    fn validate(&self) -> Vec<String> {
+        let mut errs = Vec::new();
+        if !IMAGES.contains(&self.images.as_str()) {
+            errs.push(format!(
+                "images must be one of {}, not {:?}",
+                IMAGES.join(", "),
+                self.images
+            ));
+        }
+        if archive::Format::new(&self.archive_format).is_none() {
+            errs.push(format!(
+                "archive_format must be html or warc, not {:?}",
+                self.archive_format
+            ));
+        }
+        for a in &self.actions {
+            if a.cmd.is_empty() {
+                errs.push(format!("action {}: cmd must not be empty", a.name));
+            }
+            if let Some(k) = &a.key {
+                if let Err(e) = k.parse::<Key>() {
+                    errs.push(format!("action {}: {}", a.name, e));
+                }
+            }
+            if let Some(c) = &a.capture {
+                if !CAPTURES.contains(&c.as_str()) {
+                    errs.push(format!("action {}: can't capture into {:?}", a.name, c));
+                }
+                if a.term {
+                    errs.push(format!(
+                        "action {}: output can't be captured from the pane",
+                        a.name
+                    ));
+                }
+            }
+        }
+        errs
+    }
+}
+
+/// The directory the config and hooks are in.
+pub fn dir() -> PathBuf {
+    PathBuf::from(env::var("XDG_CONFIG_HOME").unwrap_or_else(|_| {
+        let home = env::var("HOME").expect("You don't have a $HOME???");
+        format!("{home}/.config")
+    }))
+    .join("luma")
+}
+
+/// Replaces a leading `~/` with the home directory.
+pub fn expand(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(env::var("HOME").unwrap_or_default()).join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+/// The directory things the app remembers are kept in.
+pub fn data_dir() -> PathBuf {
+    PathBuf::from(env::var("XDG_DATA_HOME").unwrap_or_else(|_| {
+        let home = env::var("HOME").expect("You don't have a $HOME???");
+        format!("{home}/.local/share")
+    }))
+    .join("luma")
+}
+
+/// The directory for files that can be made again, like thumbnails.
+pub fn cache_dir() -> PathBuf {
+    PathBuf::from(env::var("XDG_CACHE_HOME").unwrap_or_else(|_| {
+        let home = env::var("HOME").expect("You don't have a $HOME???");
+        format!("{home}/.cache")
+    }))
+    .join("luma")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{capture, Action};
+    use crate::state::Link;
+
+    #[test]
+    fn actions() {
+        let a = Action {
+            name: "dl".into(),
+            key: Some("<C-d>".into()),
+            cmd: vec!["get".into(), "{link}".into(), "--to={tab}/{name}".into()],
+            capture: Some("file".into()),
+            term: false,
+        };
+        let link = Link {
+            name: "a".into(),
+            link: "https://a.com".into(),
+            ..Default::default()
+        };
+
+        let cmd = a.command(&link, "music").unwrap();
+        assert_eq!(cmd.args, ["https://a.com", "--to=music/a"]);
+
+        let new = capture(&link, "file", "/tmp/a.mp3\n").unwrap();
+        assert_eq!(new.file.as_deref(), Some("/tmp/a.mp3"));
+
+        let new = capture(&link, "yaml", "artist: b\ndesc: c").unwrap();
+        assert_eq!(
+            (new.artist.as_deref(), new.desc.as_deref()),
+            (Some("b"), Some("c"))
+        );
+
+        assert!(capture(&link, "link", "not a url").is_err());
+    }
+}
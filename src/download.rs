@@ -0,0 +1,598 @@
+//! Fetching links into local files.
+//!
+//! Links are fetched over http unless an opener rule gives a command to
+//! download them with. Podcast episodes fetch their enclosure instead of the
+//! link. Http downloads are written to a `.part` file named for the url first
+//! so starting the same download again picks up where it left off. A file
+//! that is already there is only kept for the link that points to it, other
+//! downloads with the same name get a number added to it.
+//!
+//! Commands can use `{link}`, `{dir}` and `{file}`, the path the file would be
+//! saved at. The file is found from the last line the command prints, or at
+//! `{file}` if that is not a path.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::prelude::*;
+use crate::state::Link;
+
+/// How a link is fetched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Backend {
+    Http,
+    /// Run the program and arguments.
+    Command(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Queued,
+    Running,
+    Done(PathBuf),
+    Failed(String),
+    Cancelled,
+}
+
+impl Status {
+    pub fn finished(&self) -> bool {
+        !matches!(self, Status::Queued | Status::Running)
+    }
+}
+
+/// What the download thread shares with the app.
+#[derive(Debug)]
+struct Shared {
+    status: Status,
+    /// Bytes written so far.
+    got: u64,
+    /// How much of the download is done from 0 to 1, if it is known.
+    fraction: Option<f64>,
+}
+
+#[derive(Debug)]
+pub struct Download {
+    /// The name of the tab and the link that is being downloaded.
+    pub tab: String,
+    pub link: Link,
+    backend: Backend,
+    dir: PathBuf,
+    shared: Arc<Mutex<Shared>>,
+    cancel: Arc<AtomicBool>,
+    /// If the app has been told it finished.
+    reported: bool,
+}
+
+impl Download {
+    pub fn status(&self) -> Status {
+        self.shared.lock().unwrap().status.clone()
+    }
+
+    /// Bytes written and how much is done, if that is known.
+    pub fn progress(&self) -> (u64, Option<f64>) {
+        let s = self.shared.lock().unwrap();
+        (s.got, s.fraction)
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Release);
+        let mut s = self.shared.lock().unwrap();
+        if s.status == Status::Queued {
+            s.status = Status::Cancelled;
+        }
+    }
+
+    fn start(&mut self) {
+        self.shared.lock().unwrap().status = Status::Running;
+        self.cancel.store(false, Ordering::Release);
+
+        let link = self.link.clone();
+        let backend = self.backend.clone();
+        let dir = self.dir.clone();
+        let shared = Arc::clone(&self.shared);
+        let cancel = Arc::clone(&self.cancel);
+
+        std::thread::spawn(move || {
+            let res = fs::create_dir_all(&dir)
+                .map_err(|e| format!("could not create {}: {}", dir.display(), e))
+                .and_then(|()| match &backend {
+                    Backend::Http => http(&link, &dir, &shared, &cancel),
+                    Backend::Command(argv) => command(argv, &link, &dir, &shared, &cancel),
+                });
+
+            let status = match res {
+                Ok(path) => Status::Done(path),
+                Err(_) if cancel.load(Ordering::Acquire) => Status::Cancelled,
+                Err(e) => Status::Failed(e),
+            };
+            log::info!("download of {} finished: {:?}", link.link, status);
+            shared.lock().unwrap().status = status;
+        });
+    }
+}
+
+/// The downloads that have been asked for, oldest first.
+#[derive(Debug, Default)]
+pub struct Downloads {
+    pub list: Vec<Download>,
+}
+
+impl Downloads {
+    /// Queues the link to be fetched into the directory.
+    pub fn push(&mut self, tab: &str, link: Link, backend: Backend, dir: PathBuf) {
+        self.list.push(Download {
+            tab: tab.to_owned(),
+            link,
+            backend,
+            dir,
+            shared: Arc::new(Mutex::new(Shared {
+                status: Status::Queued,
+                got: 0,
+                fraction: None,
+            })),
+            cancel: Arc::new(AtomicBool::new(false)),
+            reported: false,
+        });
+    }
+
+    /// Starts queued downloads so no more than `limit` run at once. Returns
+    /// the ones that have finished since the last call.
+    pub fn tick(&mut self, limit: usize) -> Vec<(String, Link, Status)> {
+        let mut running = self.running();
+        for d in &mut self.list {
+            if running >= limit.max(1) {
+                break;
+            }
+            if d.status() == Status::Queued {
+                d.start();
+                running += 1;
+            }
+        }
+
+        let mut done = Vec::new();
+        for d in &mut self.list {
+            let status = d.status();
+            if status.finished() && !d.reported {
+                d.reported = true;
+                done.push((d.tab.clone(), d.link.clone(), status));
+            }
+        }
+        done
+    }
+
+    pub fn running(&self) -> usize {
+        (self.list.iter())
+            .filter(|d| d.status() == Status::Running)
+            .count()
+    }
+
+    /// If there are downloads that have not finished.
+    pub fn busy(&self) -> bool {
+        self.list.iter().any(|d| !d.status().finished())
+    }
+
+    /// Queues a failed or cancelled download again.
+    pub fn retry(&mut self, i: usize) {
+        if let Some(d) = self.list.get_mut(i) {
+            if matches!(d.status(), Status::Failed(_) | Status::Cancelled) {
+                *d.shared.lock().unwrap() = Shared {
+                    status: Status::Queued,
+                    got: 0,
+                    fraction: None,
+                };
+                d.reported = false;
+            }
+        }
+    }
+
+    /// Forgets the downloads that have finished.
+    pub fn clear(&mut self) {
+        self.list.retain(|d| !d.status().finished());
+    }
+}
+
+/// The name of the file a link is saved as.
+pub fn file_name(link: &Link) -> String {
+    let from_url = url::Url::parse(link.media()).ok().and_then(|u| {
+        let last = u.path_segments()?.next_back()?.to_owned();
+        let name = percent_encoding::percent_decode_str(&last)
+            .decode_utf8_lossy()
+            .into_owned();
+        (!name.is_empty()).then_some(name)
+    });
+    let name = from_url.unwrap_or_else(|| link.name.clone());
+
+    let clean: String = name
+        .chars()
+        .map(|c| if c == '/' || c == '\0' { '_' } else { c })
+        .collect();
+    match clean.trim() {
+        "" | "." | ".." => "download".to_owned(),
+        n => n.to_owned(),
+    }
+}
+
+fn http(
+    link: &Link,
+    dir: &Path,
+    shared: &Mutex<Shared>,
+    cancel: &AtomicBool,
+) -> std::result::Result<PathBuf, String> {
+    let path = dir.join(file_name(link));
+    if link.file.as_deref().is_some_and(|f| Path::new(f) == path) && path.exists() {
+        log::info!("{} is already downloaded", path.display());
+        return Ok(path);
+    }
+    let mut part = path.clone().into_os_string();
+    part.push(format!(
+        ".{:016x}.part",
+        crate::thumb::fnv(link.media().as_bytes())
+    ));
+    let part = PathBuf::from(part);
+
+    let have = fs::metadata(&part).map_or(0, |m| m.len());
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(30))
+        .build();
+    let mut req = agent.get(link.media());
+    if have > 0 {
+        req = req.set("Range", &format!("bytes={}-", have));
+    }
+    let resp = match req.call() {
+        Ok(r) => r,
+        // the part file already has everything
+        Err(ureq::Error::Status(416, _)) if have > 0 => {
+            return finish(&part, &path);
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+
+    // servers that ignore the range send all of it again
+    let resume = resp.status() == 206;
+    let start = if resume { have } else { 0 };
+    let total = resp
+        .header("Content-Length")
+        .and_then(|l| l.parse::<u64>().ok())
+        .map(|l| l + start);
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resume)
+        .truncate(!resume)
+        .open(&part)
+        .map_err(|e| format!("could not open {}: {}", part.display(), e))?;
+
+    let mut body = resp.into_reader();
+    let mut buf = vec![0; 64 * 1024];
+    let mut got = start;
+    loop {
+        if cancel.load(Ordering::Acquire) {
+            return Err("cancelled".into());
+        }
+        let n = body.read(&mut buf).map_err(|e| e.to_string())?;
+        if n == 0 {
+            break;
+        }
+        file.write_all(&buf[..n]).map_err(|e| e.to_string())?;
+        got += n as u64;
+
+        let mut s = shared.lock().unwrap();
+        s.got = got;
+        s.fraction = total.map(|t| got as f64 / t.max(1) as f64);
+    }
+
+    if total.is_some_and(|t| got < t) {
+        return Err(format!("the connection closed after {} bytes", got));
+    }
+    finish(&part, &path)
+}
+
+/// Moves the finished part file to the path, or if there is a file there to
+/// the first free one with a number after its name. The name is taken by
+/// creating the file so downloads finishing at once don't get the same one.
+fn finish(part: &Path, path: &Path) -> std::result::Result<PathBuf, String> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()));
+    for n in 1.. {
+        let to = match n {
+            1 => path.to_owned(),
+            n => path.with_file_name(format!("{} ({}){}", stem, n, ext.as_deref().unwrap_or(""))),
+        };
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&to)
+        {
+            Ok(_) => {
+                fs::rename(part, &to).map_err(|e| e.to_string())?;
+                return Ok(to);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("could not create {}: {}", to.display(), e)),
+        }
+    }
+    unreachable!("there are always more numbers")
+}
+
+fn command(
+    argv: &[String],
+    link: &Link,
+    dir: &Path,
+    shared: &Mutex<Shared>,
+    cancel: &AtomicBool,
+) -> std::result::Result<PathBuf, String> {
+    let file = dir.join(file_name(link));
+    let argv: Vec<String> = argv
+        .iter()
+        .map(|a| {
+            a.replace("{link}", link.media())
+                .replace("{dir}", &dir.to_string_lossy())
+                .replace("{file}", &file.to_string_lossy())
+        })
+        .collect();
+    let (prog, args) = argv.split_first().ok_or("the download command is empty")?;
+
+    let mut child = Command::new(prog)
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run {}: {}", prog, e))?;
+
+    // downloaders show progress on either stream
+    let last = Arc::new(Mutex::new(String::new()));
+    let readers: Vec<_> = [
+        child
+            .stdout
+            .take()
+            .map(|o| Box::new(o) as Box<dyn Read + Send>),
+        child
+            .stderr
+            .take()
+            .map(|e| Box::new(e) as Box<dyn Read + Send>),
+    ]
+    .into_iter()
+    .flatten()
+    .enumerate()
+    .map(|(i, mut r)| {
+        let last = Arc::clone(&last);
+        let fraction = Arc::new(Mutex::new(None));
+        let seen = Arc::clone(&fraction);
+        let h = std::thread::spawn(move || {
+            let mut buf = [0; 4096];
+            let mut line = Vec::new();
+            while let Ok(n) = r.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                for &b in &buf[..n] {
+                    if b != b'\n' && b != b'\r' {
+                        line.push(b);
+                        continue;
+                    }
+                    let l = String::from_utf8_lossy(&line).trim().to_owned();
+                    if let Some(p) = percent(&l) {
+                        *seen.lock().unwrap() = Some(p);
+                    } else if i == 0 && !l.is_empty() {
+                        *last.lock().unwrap() = l;
+                    }
+                    line.clear();
+                }
+            }
+            let l = String::from_utf8_lossy(&line).trim().to_owned();
+            if i == 0 && !l.is_empty() {
+                *last.lock().unwrap() = l;
+            }
+        });
+        (h, fraction)
+    })
+    .collect();
+
+    let status = loop {
+        if cancel.load(Ordering::Acquire) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err("cancelled".into());
+        }
+        if let Some(s) = child.try_wait().map_err(|e| e.to_string())? {
+            break s;
+        }
+        let fraction = readers.iter().find_map(|(_, f)| *f.lock().unwrap());
+        if fraction.is_some() {
+            shared.lock().unwrap().fraction = fraction;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    for (h, _) in readers {
+        let _ = h.join();
+    }
+
+    if !status.success() {
+        return Err(format!("{} exited with {}", prog, status));
+    }
+
+    let printed = PathBuf::from(last.lock().unwrap().as_str());
+    let path = [dir.join(printed), file]
+        .into_iter()
+        .find(|p| p.is_file())
+        .ok_or("could not find the downloaded file")?;
+    shared.lock().unwrap().got = fs::metadata(&path).map_or(0, |m| m.len());
+    Ok(path)
+}
+
+/// Reads a progress percentage like `45.3%` from a line of output.
+fn percent(line: &str) -> Option<f64> {
+    let end = line.rfind('%')?;
+    let start = line[..end]
+        .rfind(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .map_or(0, |i| i + 1);
+    let p: f64 = line[start..end].parse().ok()?;
+    (0.0..=100.0).contains(&p).then_some(p / 100.0)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::time::{Duration, Instant};
+
+    use super::{file_name, percent, Backend, Downloads, Status};
+    use crate::prelude::*;
+    use crate::state::Link;
+
+    /// Serves the body once to each connection, honoring a range header.
+    fn serve(body: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut from = 0;
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                        break;
+                    }
+                    if let Some(r) = line.to_lowercase().strip_prefix("range: bytes=") {
+                        from = r.trim().trim_end_matches('-').parse().unwrap();
+                    }
+                }
+                let head = if from > 0 {
+                    "206 Partial Content"
+                } else {
+                    "200 OK"
+                };
+                let rest = &body[from..];
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    head,
+                    rest.len()
+                );
+                let _ = stream.write_all(rest);
+            }
+        });
+        format!("http://{}/files/a%20song.mp3", addr)
+    }
+
+    fn wait(dls: &mut Downloads) -> Vec<Status> {
+        let start = Instant::now();
+        let mut done = Vec::new();
+        while dls.busy() {
+            assert!(start.elapsed() < Duration::from_secs(10), "download hung");
+            done.extend(dls.tick(2).into_iter().map(|d| d.2));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        done.extend(dls.tick(2).into_iter().map(|d| d.2));
+        done
+    }
+
+    #[test]
+    fn http_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let link = Link {
+            name: "song".into(),
+            link: serve(b"0123456789"),
+            ..Default::default()
+        };
+        assert_eq!(file_name(&link), "a song.mp3");
+
+        // half of it was downloaded before
+        let hash = crate::thumb::fnv(link.link.as_bytes());
+        let part = format!("a song.mp3.{:016x}.part", hash);
+        fs::write(dir.path().join(part), b"01234").unwrap();
+
+        let mut dls = Downloads::default();
+        dls.push("a", link.clone(), Backend::Http, dir.path().to_owned());
+        let path = dir.path().join("a song.mp3");
+        assert_eq!(wait(&mut dls), [Status::Done(path.clone())]);
+        assert_eq!(fs::read(&path).unwrap(), b"0123456789");
+        assert_eq!(dls.list[0].progress(), (10, Some(1.0)));
+
+        // the file is kept for the link that has it, another url with the
+        // same name gets one of its own
+        let got = Link {
+            file: Some(path.to_string_lossy().into()),
+            ..link.clone()
+        };
+        let other = Link {
+            link: link.link.replace("/files/", "/other/"),
+            ..link
+        };
+        dls.push("a", got.clone(), Backend::Http, dir.path().to_owned());
+        dls.push("a", other, Backend::Http, dir.path().to_owned());
+        let done = wait(&mut dls);
+        assert!(done.contains(&Status::Done(path)));
+        assert!(done.contains(&Status::Done(dir.path().join("a song (2).mp3"))));
+
+        // downloads with the same name running at once each get a file
+        let dir = tempfile::tempdir().unwrap();
+        let mut dls = Downloads::default();
+        for path in ["/a/", "/b/"] {
+            let l = Link {
+                link: got.link.replace("/files/", path),
+                ..Default::default()
+            };
+            dls.push("a", l, Backend::Http, dir.path().to_owned());
+        }
+        let mut done: Vec<_> = wait(&mut dls)
+            .into_iter()
+            .map(|s| match s {
+                Status::Done(p) => p,
+                s => panic!("{:?}", s),
+            })
+            .collect();
+        done.sort();
+        assert_eq!(
+            done,
+            [
+                dir.path().join("a song (2).mp3"),
+                dir.path().join("a song.mp3")
+            ]
+        );
+        for p in done {
+            assert_eq!(fs::read(p).unwrap(), b"0123456789");
+        }
+    }
+
+    #[test]
+    fn commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let link = Link {
+            name: "a".into(),
+            link: "https://a.com/b.txt".into(),
+            ..Default::default()
+        };
+        let sh = |script: &str| Backend::Command(vec!["sh".into(), "-c".into(), script.into()]);
+
+        let mut dls = Downloads::default();
+        let script = "echo ' 50.0% of 2MiB'; echo hi > {dir}/c.txt; echo c.txt";
+        dls.push("a", link.clone(), sh(script), dir.path().to_owned());
+        dls.push(
+            "a",
+            link.clone(),
+            sh("echo hi > {file}"),
+            dir.path().to_owned(),
+        );
+        dls.push("a", link, sh("exit 2"), dir.path().to_owned());
+
+        let done = wait(&mut dls);
+        assert_eq!(done.len(), 3);
+        assert!(done.contains(&Status::Done(dir.path().join("c.txt"))));
+        assert!(done.contains(&Status::Done(dir.path().join("b.txt"))));
+        assert!(done.iter().any(|s| matches!(s, Status::Failed(_))));
+
+        assert_eq!(percent("[download]  45.5% of 10MiB"), Some(0.455));
+        assert_eq!(percent("no progress"), None);
+    }
+}
@@ -70,6 +70,83 @@ impl fmt::Display for Key {
     }
 }
 
+/// The key could not be understood.
+#[derive(Debug)]
+pub struct KeyParseError(pub String);
+impl fmt::Display for KeyParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown key {:?}", self.0)
+    }
+}
+impl std::error::Error for KeyParseError {}
+
+impl std::str::FromStr for Key {
+    type Err = KeyParseError;
+
+    /// Reads keys written the way vim does, `x`, `<C-x>`, `<A-x>`, `<CR>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || KeyParseError(s.to_owned());
+
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Ok(Key::Char(c));
+        }
+
+        let inner = s
+            .strip_prefix('<')
+            .and_then(|s| s.strip_suffix('>'))
+            .ok_or_else(err)?;
+
+        let single = |s: &str| {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(c),
+                _ if s.eq_ignore_ascii_case("space") => Some(' '),
+                _ => None,
+            }
+        };
+
+        if let Some(rest) = inner.strip_prefix("C-").or(inner.strip_prefix("c-")) {
+            return single(rest).map(Key::Ctrl).ok_or_else(err);
+        }
+        if let Some(rest) = inner
+            .strip_prefix("A-")
+            .or(inner.strip_prefix("a-"))
+            .or(inner.strip_prefix("M-"))
+            .or(inner.strip_prefix("m-"))
+        {
+            return single(rest).map(Key::Alt).ok_or_else(err);
+        }
+        if let Some(n) = inner.strip_prefix('F').or(inner.strip_prefix('f')) {
+            if let Ok(n) = n.parse() {
+                return Ok(Key::F(n));
+            }
+        }
+
+        let key = match inner.to_ascii_lowercase().as_str() {
+            "cr" | "enter" | "return" => Key::Enter,
+            "tab" => Key::Tab,
+            "s-tab" => Key::ShiftTab,
+            "bs" | "backspace" => Key::Backspace,
+            "esc" => Key::Esc,
+            "left" => Key::Left,
+            "right" => Key::Right,
+            "up" => Key::Up,
+            "down" => Key::Down,
+            "ins" | "insert" => Key::Ins,
+            "del" | "delete" => Key::Delete,
+            "home" => Key::Home,
+            "end" => Key::End,
+            "pageup" => Key::PageUp,
+            "pagedown" => Key::PageDown,
+            "space" => Key::Char(' '),
+            "lt" => Key::Char('<'),
+            _ => return Err(err()),
+        };
+        Ok(key)
+    }
+}
+
 impl From<crossterm::event::KeyEvent> for Key {
     fn from(event: KeyEvent) -> Self {
         // let KeyEvent {
@@ -211,3 +288,20 @@ impl From<crossterm::event::KeyEvent> for Key {
 //         }
 //     }
 // }
+
+#[cfg(test)]
+mod test {
+    use super::Key;
+
+    #[test]
+    fn parse() {
+        assert_eq!("x".parse::<Key>().unwrap(), Key::Char('x'));
+        assert_eq!("<C-x>".parse::<Key>().unwrap(), Key::Ctrl('x'));
+        assert_eq!("<A-Space>".parse::<Key>().unwrap(), Key::Alt(' '));
+        assert_eq!("<CR>".parse::<Key>().unwrap(), Key::Enter);
+        assert_eq!("<F5>".parse::<Key>().unwrap(), Key::F(5));
+        assert_eq!("<lt>".parse::<Key>().unwrap(), Key::Char('<'));
+        assert!("<nope>".parse::<Key>().is_err());
+        assert!("xy".parse::<Key>().is_err());
+    }
+}
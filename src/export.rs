@@ -0,0 +1,76 @@
+//! Writes a tab of links out to other formats.
+
+use std::io::Write;
+
+use crate::command::Format;
+use crate::prelude::*;
+use crate::state::Link;
+
+/// Writes the tab with the name and its links in the format.
+pub fn write(w: impl Write, format: Format, name: &str, links: &[Link]) -> io::Result<()> {
+    let mut w = io::BufWriter::new(w);
+    match format {
+        Format::Markdown => markdown(&mut w, name, links)?,
+        Format::Json => json::to_writer_pretty(&mut w, &(name, links))?,
+        Format::M3u => crate::playlist::write_m3u(&mut w, name, links)?,
+        Format::Xspf => crate::playlist::write_xspf(&mut w, name, links)?,
+    }
+    w.flush()
+}
+
+fn markdown(w: &mut impl Write, name: &str, links: &[Link]) -> io::Result<()> {
+    writeln!(w, "# {}", name)?;
+    writeln!(w)?;
+    for l in links {
+        // local files are linked to by path
+        let target = match (&l.file, l.link.is_empty()) {
+            (Some(f), true) => f.as_str(),
+            _ => l.link.as_str(),
+        };
+        write!(w, "- [{}](<{}>)", l.name.replace(']', "\\]"), target)?;
+        if let Some(a) = &l.artist {
+            write!(w, " by {}", a)?;
+        }
+        writeln!(w)?;
+        if let Some(d) = &l.desc {
+            for line in d.lines() {
+                writeln!(w, "  {}", line)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::write;
+    use crate::command::Format;
+    use crate::state::Link;
+
+    #[test]
+    fn markdown() {
+        let links = [
+            Link {
+                name: "a [b]".into(),
+                link: "https://a.com".into(),
+                desc: Some("one\ntwo".into()),
+                ..Default::default()
+            },
+            Link {
+                name: "song".into(),
+                file: Some("/music/song.mp3".into()),
+                artist: Some("c".into()),
+                ..Default::default()
+            },
+        ];
+
+        let mut out = Vec::new();
+        write(&mut out, Format::Markdown, "web", &links).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "# web\n\n\
+             - [a [b\\]](<https://a.com>)\n  one\n  two\n\
+             - [song](</music/song.mp3>) by c\n"
+        );
+    }
+}
@@ -0,0 +1,421 @@
+//! RSS and Atom feeds that tabs are subscribed to.
+//!
+//! Refreshing a tab fetches its feeds and adds the entries it doesn't have
+//! yet, told apart by their GUID, as unread links. The GUIDs a feed had are
+//! kept so entries deleted from the tab stay deleted. Subscriptions can be
+//! moved in and out as OPML.
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::Read;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use quick_xml::events::Event;
+use scraper::Html;
+use serde::{Deserialize, Serialize};
+
+use crate::state::Link;
+
+/// A feed a tab is subscribed to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Saved")]
+pub struct Subscription {
+    pub url: String,
+    /// GUIDs of the entries it had when last refreshed, so the ones that
+    /// were deleted from the tab aren't added again
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub seen: Vec<String>,
+}
+
+impl Subscription {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            seen: Vec::new(),
+        }
+    }
+}
+
+/// Subscriptions used to be saved as just their url.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Saved {
+    Url(String),
+    Full {
+        url: String,
+        #[serde(default)]
+        seen: Vec<String>,
+    },
+}
+
+impl From<Saved> for Subscription {
+    fn from(s: Saved) -> Self {
+        match s {
+            Saved::Url(url) => Self::new(url),
+            Saved::Full { url, seen } => Self { url, seen },
+        }
+    }
+}
+
+/// Feeds bigger than this are not read.
+const MAX_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Fetches the feed and gives back its entries, oldest first.
+pub fn fetch(url: &str) -> std::result::Result<Vec<Link>, String> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(10))
+        .timeout_read(Duration::from_secs(30))
+        .build();
+    let resp = agent.get(url).call().map_err(|e| e.to_string())?;
+    let mut body = Vec::new();
+    resp.into_reader()
+        .take(MAX_SIZE)
+        .read_to_end(&mut body)
+        .map_err(|e| e.to_string())?;
+    parse(&body)
+}
+
+/// The entries of an RSS or Atom feed as links, oldest first.
+pub fn parse(body: &[u8]) -> std::result::Result<Vec<Link>, String> {
+    let mut links: Vec<Link> = if let Ok(channel) = rss::Channel::read_from(body) {
+        channel.items().iter().map(rss_item).map(fill).collect()
+    } else if let Ok(feed) = atom_syndication::Feed::read_from(body) {
+        feed.entries().iter().map(atom_entry).map(fill).collect()
+    } else {
+        return Err("it is not an RSS or Atom feed".into());
+    };
+    // feeds list the newest first
+    links.reverse();
+    if links.iter().all(|l| l.published.is_some()) {
+        links.sort_by(|a, b| a.published.cmp(&b.published));
+    }
+    Ok(links)
+}
+
+fn rss_item(item: &rss::Item) -> Link {
+    let link = item.link().unwrap_or_default().to_owned();
+    let name = item.title().map_or_else(|| link.clone(), str::to_owned);
+    let guid = item
+        .guid()
+        .map(|g| g.value().to_owned())
+        .or_else(|| item.link().map(str::to_owned))
+        .unwrap_or_else(|| name.clone());
+    let published = item
+        .pub_date()
+        .and_then(|d| chrono::DateTime::parse_from_rfc2822(d.trim()).ok())
+        .map(|d| utc(&d));
+    let enclosure = item.enclosure();
+    let duration = (item.itunes_ext())
+        .and_then(|i| i.duration())
+        .and_then(seconds);
+    Link {
+        name,
+        link,
+        desc: item.description().map(plain).filter(|d| !d.is_empty()),
+        guid: Some(guid),
+        published,
+        unread: true,
+        enclosure: enclosure.map(|e| e.url().to_owned()),
+        size: enclosure
+            .and_then(|e| e.length().trim().parse().ok())
+            .unwrap_or(0),
+        duration: duration.unwrap_or(0),
+        ..Default::default()
+    }
+}
+
+/// Gives an entry without a link or a title ones from what it does have, so
+/// it can be added. Podcasts often only have an enclosure.
+fn fill(mut link: Link) -> Link {
+    if link.link.trim().is_empty() {
+        let guid = (link.guid.clone()).filter(|g| url::Url::parse(g).is_ok());
+        link.link = link.enclosure.clone().or(guid).unwrap_or_default();
+    }
+    if link.name.trim().is_empty() {
+        link.name = match &link.published {
+            _ if !link.link.is_empty() => link.link.clone(),
+            Some(p) => p.clone(),
+            None => String::new(),
+        };
+    }
+    link
+}
+
+/// Seconds in an iTunes duration, `1:02:03`, `62:03` or `3723`.
+fn seconds(duration: &str) -> Option<u64> {
+    let mut secs = 0;
+    for part in duration.trim().split(':') {
+        secs = secs * 60 + part.trim().parse::<u64>().ok()?;
+    }
+    Some(secs)
+}
+
+fn atom_entry(entry: &atom_syndication::Entry) -> Link {
+    let links = entry.links();
+    let link = (links.iter())
+        .find(|l| l.rel() == "alternate")
+        .or(links.iter().find(|l| l.rel() != "enclosure"))
+        .or(links.first())
+        .map_or_else(String::new, |l| l.href().to_owned());
+    let desc = (entry.summary().map(|s| s.as_str()))
+        .or_else(|| entry.content().and_then(|c| c.value()))
+        .map(plain);
+    let enclosure = links.iter().find(|l| l.rel() == "enclosure");
+    Link {
+        name: entry.title().as_str().to_owned(),
+        link,
+        desc: desc.filter(|d| !d.is_empty()),
+        guid: Some(entry.id().to_owned()),
+        published: Some(utc(entry.published().unwrap_or(entry.updated()))),
+        unread: true,
+        enclosure: enclosure.map(|l| l.href().to_owned()),
+        size: (enclosure.and_then(|l| l.length()))
+            .and_then(|l| l.trim().parse().ok())
+            .unwrap_or(0),
+        ..Default::default()
+    }
+}
+
+/// The date in UTC, so dates sort by when they are.
+fn utc(date: &chrono::DateTime<chrono::FixedOffset>) -> String {
+    date.with_timezone(&chrono::Utc).to_rfc3339()
+}
+
+/// The text of the html, a paragraph to a line.
+fn plain(html: &str) -> String {
+    let doc = Html::parse_fragment(html);
+    let text: String = doc.root_element().text().collect();
+    let lines: Vec<String> = text
+        .lines()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|l| !l.is_empty())
+        .collect();
+    lines.join("\n\n")
+}
+
+/// The entries the tab doesn't have yet and that weren't seen in the feed
+/// before. What was seen becomes what the feed has now.
+pub fn fresh(have: &[Link], sub: &mut Subscription, entries: Vec<Link>) -> Vec<Link> {
+    let mut seen: HashSet<String> = (have.iter().filter_map(|l| l.guid.clone()))
+        .chain(sub.seen.iter().cloned())
+        .collect();
+    // entries that left the feed won't come back, so they are forgotten
+    sub.seen = entries.iter().filter_map(|e| e.guid.clone()).collect();
+    entries
+        .into_iter()
+        .filter(|e| e.guid.clone().is_some_and(|g| seen.insert(g)))
+        .collect()
+}
+
+/// The feeds in an OPML file, each with the tab it goes in. Feeds in an
+/// outline go in a tab named after it, the rest in one named after them.
+pub fn read_opml(text: &str) -> std::result::Result<Vec<(String, String)>, String> {
+    let mut reader = quick_xml::Reader::from_str(text);
+    let mut groups: Vec<Option<String>> = Vec::new();
+    let mut feeds = Vec::new();
+    loop {
+        let event = reader.read_event().map_err(|e| e.to_string())?;
+        let (e, empty) = match &event {
+            Event::Start(e) => (e, false),
+            Event::Empty(e) => (e, true),
+            Event::End(e) if e.name().as_ref() == b"outline" => {
+                groups.pop();
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+        if e.name().as_ref() != b"outline" {
+            continue;
+        }
+        let attr = |key: &str| {
+            let a = e.try_get_attribute(key).ok().flatten()?;
+            let version = quick_xml::XmlVersion::default();
+            let v = (a.decoded_and_normalized_value(version, reader.decoder())).ok()?;
+            Some(v.trim().to_owned())
+        };
+        let title = attr("text")
+            .or_else(|| attr("title"))
+            .filter(|t| !t.is_empty());
+        match attr("xmlUrl").filter(|u| !u.is_empty()) {
+            Some(url) => {
+                let group = groups.iter().rev().flatten().next().cloned();
+                let tab = group.or(title).unwrap_or_else(|| url.clone());
+                feeds.push((tab, url));
+                if !empty {
+                    groups.push(None);
+                }
+            }
+            None if !empty => groups.push(title),
+            None => {}
+        }
+    }
+    if feeds.is_empty() {
+        return Err("no feeds were found".into());
+    }
+    Ok(feeds)
+}
+
+/// The subscriptions as OPML, an outline for each tab.
+pub fn write_opml(feeds: &BTreeMap<String, Vec<Subscription>>) -> String {
+    use quick_xml::escape::escape;
+
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<opml version=\"2.0\">\n  \
+         <head>\n    <title>luma feeds</title>\n  </head>\n  <body>\n",
+    );
+    for (tab, urls) in feeds.iter().filter(|(_, u)| !u.is_empty()) {
+        out.push_str(&format!(
+            "    <outline text=\"{}\">\n",
+            escape(tab.as_str())
+        ));
+        for sub in urls {
+            let url = escape(sub.url.as_str());
+            out.push_str(&format!(
+                "      <outline type=\"rss\" text=\"{}\" xmlUrl=\"{}\"/>\n",
+                url, url
+            ));
+        }
+        out.push_str("    </outline>\n");
+    }
+    out.push_str("  </body>\n</opml>\n");
+    out
+}
+
+/// The entries of a feed, or why they couldn't be fetched.
+pub type Entries = std::result::Result<Vec<Link>, String>;
+/// Feed entries fetched for a tab, with the tab and the feed.
+pub type Fetched = (String, String, Entries);
+
+/// Feeds being fetched in the background, by the tab they are for.
+#[derive(Debug, Default)]
+pub struct Feeds {
+    running: Vec<(String, String, JoinHandle<Entries>)>,
+}
+
+impl Feeds {
+    /// Starts fetching the feed for the tab, unless it already is.
+    pub fn fetch(&mut self, tab: &str, url: &str) {
+        if self.running.iter().any(|r| r.0 == tab && r.1 == url) {
+            return;
+        }
+        let u = url.to_owned();
+        let handle = std::thread::spawn(move || fetch(&u));
+        self.running.push((tab.to_owned(), url.to_owned(), handle));
+    }
+
+    /// The feeds that were fetched since the last call.
+    pub fn tick(&mut self) -> Vec<Fetched> {
+        let (done, running): (Vec<_>, Vec<_>) = std::mem::take(&mut self.running)
+            .into_iter()
+            .partition(|r| r.2.is_finished());
+        self.running = running;
+        done.into_iter()
+            .map(|(tab, url, h)| {
+                let res = h.join().unwrap_or(Err("it panicked".into()));
+                (tab, url, res)
+            })
+            .collect()
+    }
+
+    pub fn busy(&self) -> bool {
+        !self.running.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::{fresh, parse, read_opml, write_opml, Subscription};
+    use crate::state::Validate;
+
+    #[test]
+    fn feeds() {
+        let rss = br#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd"><channel><title>Blog</title><link>https://blog.example</link>
+<description>posts</description>
+<item><title>Second</title><link>https://blog.example/2</link><guid>b2</guid>
+<pubDate>Tue, 02 Jan 2024 10:00:00 +0000</pubDate>
+<description>&lt;p&gt;Second &lt;b&gt;post&lt;/b&gt;&lt;/p&gt;</description></item>
+<item><title>First</title><link>https://blog.example/1</link><guid>b1</guid>
+<pubDate>Mon, 01 Jan 2024 10:00:00 +0000</pubDate>
+<enclosure url="https://blog.example/1.mp3" length="1234" type="audio/mpeg"/>
+<itunes:duration>1:02:03</itunes:duration></item>
+</channel></rss>"#;
+        let links = parse(rss).unwrap();
+        let names: Vec<&str> = links.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, ["First", "Second"]);
+        assert_eq!(links[1].desc.as_deref(), Some("Second post"));
+        assert_eq!(
+            links[1].published.as_deref(),
+            Some("2024-01-02T10:00:00+00:00")
+        );
+        assert!(links.iter().all(|l| l.unread));
+        // episodes carry their media
+        assert_eq!(
+            (links[0].media(), links[0].size, links[0].duration),
+            ("https://blog.example/1.mp3", 1234, 3723)
+        );
+        assert_eq!(links[1].media(), "https://blog.example/2");
+
+        // an episode with only its media can still be added
+        let bare = br#"<?xml version="1.0"?>
+<rss version="2.0"><channel><title>Pod</title><link>https://pod.example</link>
+<description>eps</description>
+<item><title></title><guid isPermaLink="false">ep-1</guid>
+<enclosure url="https://pod.example/1.mp3" length="0" type="audio/mpeg"/></item>
+</channel></rss>"#;
+        let links = parse(bare).unwrap();
+        assert_eq!(links[0].link, "https://pod.example/1.mp3");
+        assert_eq!(links[0].name, "https://pod.example/1.mp3");
+        assert!(links[0].validate().is_empty());
+
+        let atom = br#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom"><title>Site</title><id>urn:site</id>
+<updated>2024-01-03T00:00:00Z</updated>
+<entry><title>Note</title><id>urn:note</id><updated>2024-01-03T00:00:00Z</updated>
+<link rel="alternate" href="https://site.example/note"/><summary>short</summary></entry>
+</feed>"#;
+        let links = parse(atom).unwrap();
+        assert_eq!(links[0].link, "https://site.example/note");
+        assert_eq!(links[0].guid.as_deref(), Some("urn:note"));
+        assert!(parse(b"<html></html>").is_err());
+
+        // entries are only added once
+        let have = parse(rss).unwrap();
+        let mut again = parse(rss).unwrap();
+        again.extend(parse(atom).unwrap());
+        again.extend(parse(atom).unwrap());
+        let mut sub = Subscription::new("https://blog.example/rss".into());
+        let new = fresh(&have[..1], &mut sub, again);
+        let names: Vec<&str> = new.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, ["Second", "Note"]);
+        // and not again once deleted from the tab
+        assert!(fresh(&[], &mut sub, parse(rss).unwrap()).is_empty());
+        let old: Subscription = json::from_str("\"https://a.example/rss\"").unwrap();
+        assert_eq!(old, Subscription::new("https://a.example/rss".into()));
+
+        let mut subs = BTreeMap::new();
+        subs.insert(
+            "news & blogs".to_owned(),
+            vec![Subscription::new("https://a.example/rss?x=1&y=2".into())],
+        );
+        subs.insert("empty".to_owned(), Vec::new());
+        let opml = write_opml(&subs);
+        assert!(!opml.contains("empty"));
+        assert_eq!(
+            read_opml(&opml).unwrap(),
+            [(
+                "news & blogs".to_owned(),
+                "https://a.example/rss?x=1&y=2".to_owned()
+            )]
+        );
+        let loose =
+            r#"<opml><body><outline text="Loose" xmlUrl="https://l.example/feed"/></body></opml>"#;
+        assert_eq!(
+            read_opml(loose).unwrap(),
+            [("Loose".to_owned(), "https://l.example/feed".to_owned())]
+        );
+    }
+}
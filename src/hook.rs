@@ -0,0 +1,223 @@
+//! Hooks that run around changes to the links.
+//!
+//! Every hook is given the link, the tab's new name for `tab`, or all of
+//! [`Luma`] for `save`, once before
+//! the change as `pre_<hook>` and once after as `post_<hook>`. The ones that
+//! run before can stop the change or give back a different value to use.
+//! Deleting a tab runs `delete` for each of its links and moving a link runs
+//! `delete` in its tab and `add` in the other. Sorting and marking links read
+//! or played only keep track of them, so they don't run hooks.
+//!
+//! Hooks are lua functions passed to `luma.on` and executables in the hooks
+//! dir named after the hook. An executable gets the value as json on stdin and
+//! the name of the tab in `$LUMA_TAB`. Exiting with an error stops the change
+//! and json written to stdout replaces the value.
+
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::prelude::*;
+
+/// How long an executable can run before it is killed.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    Add,
+    Open,
+    Edit,
+    Delete,
+    Save,
+    /// A tab is made or renamed.
+    Tab,
+}
+
+impl fmt::Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Hook::Add => "add",
+            Hook::Open => "open",
+            Hook::Edit => "edit",
+            Hook::Delete => "delete",
+            Hook::Save => "save",
+            Hook::Tab => "tab",
+        })
+    }
+}
+
+impl Hook {
+    pub fn pre(self) -> String {
+        format!("pre_{}", self)
+    }
+
+    pub fn post(self) -> String {
+        format!("post_{}", self)
+    }
+}
+
+/// What a hook decided.
+#[derive(Debug, PartialEq)]
+pub enum Flow<T> {
+    /// Go on with this value.
+    Go(T),
+    /// Stop the change, for this reason.
+    Stop(String),
+}
+
+#[derive(Debug)]
+pub struct HookError(pub String);
+impl fmt::Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "hook {} failed", self.0)
+    }
+}
+impl Context for HookError {}
+
+/// The executables that are run as hooks.
+#[derive(Debug, Default)]
+pub struct Hooks {
+    dir: Option<PathBuf>,
+}
+
+/// Where hook executables are looked for.
+pub fn dir() -> PathBuf {
+    crate::config::dir().join("hooks")
+}
+
+impl Hooks {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir: Some(dir) }
+    }
+
+    /// Runs the executable with the name if there is one.
+    pub fn run<T: Serialize + DeserializeOwned>(
+        &self,
+        name: &str,
+        tab: Option<&str>,
+        value: T,
+    ) -> Result<Flow<T>, HookError> {
+        let Some(path) = self.dir.as_ref().map(|d| d.join(name)) else {
+            return Ok(Flow::Go(value));
+        };
+        if !path.is_file() {
+            return Ok(Flow::Go(value));
+        }
+        let err = || HookError(name.to_owned());
+
+        let input = json::to_vec(&value).change_context_lazy(err)?;
+
+        let mut cmd = Command::new(&path);
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(tab) = tab {
+            cmd.env("LUMA_TAB", tab);
+        }
+        let mut child = cmd
+            .spawn()
+            .change_context_lazy(err)
+            .attach_printable_lazy(|| format!("could not run {}", path.display()))?;
+
+        // the pipes are read on other threads so a chatty hook can't block
+        let mut stdin = child.stdin.take().unwrap();
+        std::thread::spawn(move || io::Write::write_all(&mut stdin, &input));
+        let mut stdout = child.stdout.take().unwrap();
+        let out = std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            io::Read::read_to_end(&mut stdout, &mut buf).map(|_| buf)
+        });
+        let mut stderr = child.stderr.take().unwrap();
+        let errs = std::thread::spawn(move || {
+            let mut buf = String::new();
+            io::Read::read_to_string(&mut stderr, &mut buf).map(|_| buf)
+        });
+
+        let start = Instant::now();
+        let status = loop {
+            if let Some(s) = child.try_wait().change_context_lazy(err)? {
+                break s;
+            }
+            if start.elapsed() > TIMEOUT {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(Report::new(err()))
+                    .attach_printable(format!("took longer than {:?}", TIMEOUT));
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        };
+
+        let out = out.join().unwrap().change_context_lazy(err)?;
+        let errs = errs.join().unwrap().unwrap_or_default();
+        if !errs.is_empty() {
+            log::info!("{} said: {}", name, errs.trim_end());
+        }
+
+        if !status.success() {
+            let why = errs.lines().next().unwrap_or("").trim();
+            let why = if why.is_empty() {
+                format!("{} exited with {}", name, status)
+            } else {
+                format!("{}: {}", name, why)
+            };
+            return Ok(Flow::Stop(why));
+        }
+
+        if out.iter().all(u8::is_ascii_whitespace) {
+            Ok(Flow::Go(value))
+        } else {
+            json::from_slice(&out)
+                .map(Flow::Go)
+                .change_context_lazy(err)
+                .attach_printable("the output was not valid json")
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::{Flow, Hooks};
+    use crate::prelude::*;
+    use crate::state::Link;
+
+    #[test]
+    fn executables() {
+        let dir = tempfile::tempdir().unwrap();
+        let hook = |name: &str, body: &str| {
+            let path = dir.path().join(name);
+            fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        };
+        hook(
+            "pre_add",
+            r#"sed 's/"color":null/"color":"'"$LUMA_TAB"'"/'"#,
+        );
+        hook("pre_delete", "echo 'not this one' >&2; exit 1");
+
+        let hooks = Hooks::new(dir.path().to_owned());
+        let link = Link {
+            name: "a".into(),
+            link: "https://a.com".into(),
+            ..Default::default()
+        };
+
+        let Flow::Go(l) = hooks.run("pre_add", Some("red"), link.clone()).unwrap() else {
+            panic!("add was stopped");
+        };
+        assert_eq!(l.color.as_deref(), Some("red"));
+
+        let flow = hooks.run("pre_delete", None, link.clone()).unwrap();
+        assert_eq!(flow, Flow::Stop("pre_delete: not this one".into()));
+
+        // no executable means nothing changes
+        assert_eq!(
+            hooks.run("post_add", None, link.clone()).unwrap(),
+            Flow::Go(link)
+        );
+    }
+}
@@ -0,0 +1,391 @@
+//! Adds links for the local files in a directory, or the urls in a text file.
+//!
+//! The directories a tab was filled from are kept in [`Luma`] so they can be
+//! scanned again later, which adds the new files and flags the links whose
+//! files went away. Urls found in text are [`Candidate`]s that are looked
+//! over before they are added.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+use wildmatch::WildMatch;
+
+use crate::prelude::*;
+use crate::state::Link;
+
+/// Files that have tags worth reading for a name.
+const TAGGED: &[&str] = &["flac", "ogg", "oga", "opus", "mp3"];
+
+/// A directory that is imported into a tab.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportDir {
+    pub tab: String,
+    pub path: PathBuf,
+    /// Only files matching one of these are imported, all of them when empty.
+    /// Globs without a `/` match the file name, others the path in the
+    /// directory.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Files matching one of these are left out.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+}
+
+/// What changed in a tab since the directory was last scanned.
+#[derive(Debug, Default, PartialEq)]
+pub struct Scan {
+    /// Links for the files that aren't in the tab yet.
+    pub new: Vec<Link>,
+    /// The links whose file is gone.
+    pub missing: Vec<usize>,
+    /// The links that were missing and whose file is back.
+    pub found: Vec<usize>,
+}
+
+impl Scan {
+    /// Flags the missing links and adds the new ones to the end.
+    pub fn apply(self, links: &mut Vec<Link>) {
+        for i in self.missing {
+            links[i].missing = true;
+        }
+        for i in self.found {
+            links[i].missing = false;
+        }
+        links.extend(self.new);
+    }
+}
+
+impl fmt::Display for Scan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} new, {} missing", self.new.len(), self.missing.len())?;
+        if !self.found.is_empty() {
+            write!(f, ", {} found again", self.found.len())?;
+        }
+        Ok(())
+    }
+}
+
+impl ImportDir {
+    fn matches(globs: &[String], rel: &Path) -> bool {
+        let rel = rel.to_string_lossy();
+        let name = rel.rsplit('/').next().unwrap_or(&rel);
+        globs.iter().any(|g| {
+            let against = if g.contains('/') { &*rel } else { name };
+            WildMatch::new(g).matches(against)
+        })
+    }
+
+    fn wanted(&self, rel: &Path) -> bool {
+        (self.include.is_empty() || Self::matches(&self.include, rel))
+            && !Self::matches(&self.exclude, rel)
+    }
+
+    /// The files in the directory that should be imported, hidden ones are
+    /// skipped.
+    pub fn files(&self) -> io::Result<Vec<PathBuf>> {
+        if !self.path.is_dir() {
+            let msg = format!("{} is not a directory", self.path.display());
+            return Err(io::Error::new(io::ErrorKind::NotFound, msg));
+        }
+
+        let walk = WalkDir::new(&self.path)
+            .follow_links(true)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'));
+
+        let mut files = Vec::new();
+        for e in walk {
+            let e = match e {
+                Ok(e) => e,
+                Err(e) => {
+                    log::warn!("skipping while importing: {}", e);
+                    continue;
+                }
+            };
+            let rel = e.path().strip_prefix(&self.path).unwrap_or(e.path());
+            if e.file_type().is_file() && self.wanted(rel) {
+                files.push(e.into_path());
+            }
+        }
+        Ok(files)
+    }
+
+    /// Compares the files in the directory with the links of the tab.
+    pub fn scan(&self, links: &[Link]) -> io::Result<Scan> {
+        let files = self.files()?;
+        let mut scan = Scan::default();
+
+        let mut known = HashSet::new();
+        for (i, l) in links.iter().enumerate() {
+            let Some(file) = &l.file else {
+                continue;
+            };
+            let path = Path::new(file);
+            known.insert(path);
+            if !path.starts_with(&self.path) {
+                continue;
+            }
+            match (path.exists(), l.missing) {
+                (false, false) => scan.missing.push(i),
+                (true, true) => scan.found.push(i),
+                _ => {}
+            }
+        }
+
+        scan.new = files
+            .iter()
+            .filter(|f| !known.contains(f.as_path()))
+            .map(|f| link(f))
+            .collect();
+        Ok(scan)
+    }
+}
+
+/// A link to the file named after its tags, or its file name.
+pub fn link(path: &Path) -> Link {
+    let tagged = path
+        .extension()
+        .is_some_and(|e| TAGGED.iter().any(|t| e.eq_ignore_ascii_case(t)));
+    let tags = tagged.then(|| crate::tag::read(path).ok()).flatten();
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let stem = stem.replace('_', " ");
+    // files are often called `artist - title`
+    let (artist, name) = match stem.split_once(" - ") {
+        Some((a, n)) => (Some(a.trim().to_owned()), n.trim().to_owned()),
+        None => (None, stem.trim().to_owned()),
+    };
+    let artist = artist.filter(|a| !a.is_empty());
+    // a name like `_` or `-` says nothing, the file name at least tells
+    // the links apart
+    let name = if name.chars().any(char::is_alphanumeric) {
+        name
+    } else {
+        path.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    };
+
+    let link = Link {
+        name,
+        artist,
+        file: Some(path.to_string_lossy().into_owned()),
+        ..Default::default()
+    };
+    match tags {
+        Some(t) => t.sync(link),
+        None => link,
+    }
+}
+
+/// A link found in a text file that can be added to a tab.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub link: Link,
+    pub tabb: usize,
+    pub keep: bool,
+}
+
+/// Finds the links in text. Markdown `[name](url)` and org `[[url][name]]`
+/// links keep their name, bare urls are named after themselves. Each url is
+/// only given once.
+pub fn urls(text: &str) -> Vec<Link> {
+    let mut links: Vec<Link> = Vec::new();
+    let mut push = |name: &str, url: &str| {
+        let url = url.trim();
+        let ok = url::Url::parse(url).is_ok_and(|u| u.has_host());
+        if ok && !links.iter().any(|l| l.link == url) {
+            let name = match name.trim() {
+                "" => url.split_once("://").map_or(url, |(_, rest)| rest),
+                n => n,
+            };
+            links.push(Link {
+                name: name.to_owned(),
+                link: url.to_owned(),
+                ..Default::default()
+            });
+        }
+    };
+
+    let mut rest = text;
+    while let Some(i) = rest.find(['[', 'h', '<']) {
+        let (found, len) = if rest[i..].starts_with("[[") {
+            org(&rest[i..])
+        } else if rest[i..].starts_with('[') {
+            markdown(&rest[i..])
+        } else if rest[i..].starts_with('<') {
+            // markdown autolinks
+            match rest[i + 1..].split_once('>') {
+                Some((url, _)) if !url.contains(char::is_whitespace) => {
+                    (Some(("", url)), url.len() + 2)
+                }
+                _ => (None, 1),
+            }
+        } else {
+            bare(&rest[i..])
+        };
+        if let Some((name, url)) = found {
+            push(name, url);
+        }
+        rest = &rest[i + len..];
+    }
+    links
+}
+
+/// An org link, `[[url]]` or `[[url][name]]`, and how long it is.
+fn org(text: &str) -> (Option<(&str, &str)>, usize) {
+    let Some(end) = text.find("]]").filter(|&e| !text[..e].contains('\n')) else {
+        return (None, 2);
+    };
+    let inner = &text[2..end];
+    let found = match inner.split_once("][") {
+        Some((url, name)) => (name, url),
+        None => ("", inner),
+    };
+    (Some(found), end + 2)
+}
+
+/// A markdown link, `[name](url "title")`, and how long it is.
+fn markdown(text: &str) -> (Option<(&str, &str)>, usize) {
+    let Some(close) = text.find(']').filter(|&e| !text[..e].contains('\n')) else {
+        return (None, 1);
+    };
+    let name = &text[1..close];
+    let Some(after) = text[close + 1..].strip_prefix('(') else {
+        return (None, 1);
+    };
+
+    // urls can have brackets in them, like on wikipedia
+    let mut depth = 0;
+    let Some(end) = after.find(|c| {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return true,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        c == '\n'
+    }) else {
+        return (None, 1);
+    };
+    if after[end..].starts_with('\n') {
+        return (None, 1);
+    }
+    let url = after[..end].split_whitespace().next().unwrap_or("");
+    let url = url.trim_start_matches('<').trim_end_matches('>');
+    (Some((name, url)), close + 2 + end + 1)
+}
+
+/// A url in the middle of text, and how long it is.
+fn bare(text: &str) -> (Option<(&str, &str)>, usize) {
+    if !(text.starts_with("http://") || text.starts_with("https://")) {
+        return (None, 1);
+    }
+    let end = text
+        .find(|c: char| c.is_whitespace() || "<>\"'`".contains(c))
+        .unwrap_or(text.len());
+    let mut url = &text[..end];
+
+    // punctuation after a url is part of the sentence, unless it closes a
+    // bracket from the url
+    while let Some(c) = url.chars().last() {
+        let open = match c {
+            ')' => '(',
+            ']' => '[',
+            '.' | ',' | ';' | ':' | '!' | '?' | '*' | '_' => '\0',
+            _ => break,
+        };
+        if open != '\0' && url.matches(open).count() >= url.matches(c).count() {
+            break;
+        }
+        url = &url[..url.len() - 1];
+    }
+    (Some(("", url)), end)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::{urls, ImportDir};
+    use crate::prelude::*;
+
+    #[test]
+    fn scan() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for f in [
+            "a - b.mp3",
+            "_.mp3",
+            "c/d_e.ogg",
+            "c/live.ogg",
+            "notes.txt",
+            ".hidden/f.mp3",
+        ] {
+            let path = root.join(f);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+
+        let import = ImportDir {
+            tab: "music".into(),
+            path: root.to_owned(),
+            include: vec!["*.mp3".into(), "*.ogg".into()],
+            exclude: vec!["c/live*".into()],
+        };
+        let mut links = Vec::new();
+        let scan = import.scan(&links).unwrap();
+        let names: Vec<_> = scan
+            .new
+            .iter()
+            .map(|l| (l.name.as_str(), l.artist.as_deref()))
+            .collect();
+        assert_eq!(names, [("_.mp3", None), ("b", Some("a")), ("d e", None)]);
+        scan.apply(&mut links);
+
+        fs::remove_file(root.join("a - b.mp3")).unwrap();
+        fs::write(root.join("g.mp3"), b"").unwrap();
+        let scan = import.scan(&links).unwrap();
+        assert_eq!(scan.to_string(), "1 new, 1 missing");
+        scan.apply(&mut links);
+        assert!(links[1].missing);
+        assert_eq!(
+            links[3].file,
+            Some(root.join("g.mp3").to_string_lossy().into())
+        );
+
+        let gone = ImportDir {
+            path: PathBuf::from("/not/here"),
+            ..import
+        };
+        assert!(gone.scan(&links).is_err());
+    }
+
+    #[test]
+    fn text() {
+        let text = "\
+            # reading\n\
+            - [Rust (lang)](https://rust-lang.org \"home\") and https://a.com/x_(y).\n\
+            - see <https://b.com/>, or https://rust-lang.org again!\n\
+            * [[https://c.org/page][c page]] [[https://d.org]]\n\
+            [not a link] (https://e.com/f) mailto:me@x.com [x](nope)\n";
+        let found: Vec<_> = urls(text).into_iter().map(|l| (l.name, l.link)).collect();
+        let want = [
+            ("Rust (lang)", "https://rust-lang.org"),
+            ("a.com/x_(y)", "https://a.com/x_(y)"),
+            ("b.com/", "https://b.com/"),
+            ("c page", "https://c.org/page"),
+            ("d.org", "https://d.org"),
+            ("e.com/f", "https://e.com/f"),
+        ];
+        let want: Vec<_> = want
+            .iter()
+            .map(|(n, l)| (n.to_string(), l.to_string()))
+            .collect();
+        assert_eq!(found, want);
+    }
+}
@@ -0,0 +1,35 @@
+use crate::{
+    app::{Mode, State},
+    event::Key,
+    input::Msg,
+};
+
+pub fn handle(key: Key, stat: &mut State) -> Option<Msg> {
+    let line = &mut stat.cmdline;
+    stat.draw = true;
+
+    match key {
+        Key::Esc | Key::Ctrl('c') => return Some(Msg::ChangeMode(Mode::Normal)),
+        Key::Enter => return Some(Msg::Command(line.take())),
+        Key::Tab => return Some(Msg::Complete(false)),
+        Key::ShiftTab => return Some(Msg::Complete(true)),
+
+        // deleting past the start leaves the command line like in vim
+        Key::Backspace if line.text.is_empty() => return Some(Msg::ChangeMode(Mode::Normal)),
+        Key::Backspace => line.backspace(),
+        Key::Delete => line.delete(),
+        Key::Ctrl('w') => line.delete_word(),
+        Key::Ctrl('u') => line.reset(),
+
+        Key::Left => line.left(),
+        Key::Right => line.right(),
+        Key::Home | Key::Ctrl('a') => line.home(),
+        Key::End | Key::Ctrl('e') => line.end(),
+        Key::Up | Key::Ctrl('p') => line.older(),
+        Key::Down | Key::Ctrl('n') => line.newer(),
+
+        Key::Char(c) => line.insert(c),
+        _ => {}
+    }
+    None
+}
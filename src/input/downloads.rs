@@ -0,0 +1,29 @@
+use crate::{
+    app::{Mode, State},
+    event::Key,
+    input::Msg,
+};
+
+pub fn handle(key: Key, stat: &mut State) -> Option<Msg> {
+    let msg = match key {
+        Key::Char('x') => Msg::CancelDownload,
+        Key::Char('r') => Msg::RetryDownload,
+        Key::Char('c') => Msg::ClearDownloads,
+        Key::Up | Key::Char('k') => {
+            stat.dl_selected = stat.dl_selected.saturating_sub(1);
+            stat.draw = true;
+            return None;
+        }
+        Key::Down | Key::Char('j') => {
+            // the app keeps it in range
+            stat.dl_selected = stat.dl_selected.saturating_add(1);
+            stat.draw = true;
+            return None;
+        }
+        Key::Char('q') | Key::Char('F') | Key::Ctrl('c') | Key::Esc | Key::Enter => {
+            Msg::ChangeMode(Mode::Normal)
+        }
+        _ => return None,
+    };
+    Some(msg)
+}
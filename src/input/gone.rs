@@ -0,0 +1,13 @@
+use crate::{app::Mode, event::Key, input::Msg};
+
+pub fn handle(key: Key) -> Option<Msg> {
+    let msg = match key {
+        Key::Char('a') | Key::Char('y') | Key::Enter => Msg::OpenArchive,
+        Key::Char('o') => Msg::OpenLive,
+        Key::Char('n') | Key::Char('q') | Key::Ctrl('c') | Key::Esc => {
+            Msg::ChangeMode(Mode::Normal)
+        }
+        _ => return None,
+    };
+    Some(msg)
+}
@@ -0,0 +1,43 @@
+use crate::{
+    app::{Mode, State},
+    event::Key,
+    input::Msg,
+};
+
+pub fn handle(key: Key, stat: &mut State) -> Option<Msg> {
+    stat.draw = true;
+    let sel = stat.cand_selected;
+
+    let msg = match key {
+        Key::Enter => Msg::FinishImport,
+        Key::Char('q') | Key::Ctrl('c') | Key::Esc => {
+            stat.candidates.clear();
+            Msg::ChangeMode(Mode::Normal)
+        }
+        Key::Tab | Key::Char('l') => Msg::ImportTab(false),
+        Key::ShiftTab | Key::Char('h') => Msg::ImportTab(true),
+        Key::Char(' ') | Key::Char('x') => {
+            if let Some(c) = stat.candidates.get_mut(sel) {
+                c.keep = !c.keep;
+            }
+            stat.cand_selected = (sel + 1).min(stat.candidates.len().saturating_sub(1));
+            return None;
+        }
+        // keep or drop all of them
+        Key::Char('a') | Key::Char('n') => {
+            let keep = key == Key::Char('a');
+            stat.candidates.iter_mut().for_each(|c| c.keep = keep);
+            return None;
+        }
+        Key::Up | Key::Char('k') => {
+            stat.cand_selected = sel.saturating_sub(1);
+            return None;
+        }
+        Key::Down | Key::Char('j') => {
+            stat.cand_selected = (sel + 1).min(stat.candidates.len().saturating_sub(1));
+            return None;
+        }
+        _ => return None,
+    };
+    Some(msg)
+}
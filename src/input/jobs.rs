@@ -0,0 +1,12 @@
+use crate::{app::Mode, event::Key, input::Msg};
+
+pub fn handle(key: Key) -> Option<Msg> {
+    let msg = match key {
+        Key::Char('c') => Msg::ClearJobs,
+        Key::Char('q') | Key::Char('J') | Key::Ctrl('c') | Key::Esc | Key::Enter => {
+            Msg::ChangeMode(Mode::Normal)
+        }
+        _ => return None,
+    };
+    Some(msg)
+}
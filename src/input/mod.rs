@@ -1,7 +1,20 @@
-use crate::app::Mode;
+use crate::app::{Item, Mode, State};
 
+pub mod command;
 pub mod delete;
+pub mod downloads;
+pub mod gone;
+pub mod import;
+pub mod jobs;
 pub mod normal;
+pub mod reader;
+pub mod search;
+pub mod term;
+
+/// Rows the preview scrolls by a page.
+pub const PAGE: i16 = 10;
+/// Seconds mpv seeks by.
+pub const SEEK: i64 = 10;
 
 #[derive(Debug)]
 pub enum Msg {
@@ -23,8 +36,175 @@ pub enum Msg {
     Delete,
     /// Add a blank link then edit it
     Add,
+    /// Write the links to disk
+    Save,
+    /// Forget the jobs that have finished
+    ClearJobs,
+    /// Edit the tags of the selected link's file
+    Tag,
+    /// Scroll the preview down by the rows, up if negative
+    ScrollPreview(i16),
+    /// Edit the note of the selected link, or of the tab if set
+    Note(bool),
+    /// Scan the directories imported into the tab again
+    Rescan,
+    /// Put the selected import candidate in the next tab, or the previous one
+    ImportTab(bool),
+    /// Add the import candidates that were kept
+    FinishImport,
+    /// Read the saved copy of the selected link, saving one first if there
+    /// is none
+    Read,
+    /// Save a readable copy of the selected link's page again
+    SaveReadable,
+    /// Leave the reader, remembering how far it was read
+    CloseReader,
+    /// Take a snapshot of the selected link's page
+    Archive,
+    /// Open the latest snapshot of the selected link
+    OpenArchive,
+    /// Open the selected link even if its page seems to be gone
+    OpenLive,
+    /// Start typing a command with the text
+    Prompt(String),
+    /// Go to the link of the selected search result
+    GoToHit,
+    /// Fetch the feeds of the selected tab
+    RefreshFeeds,
+    /// Mark every link in the selected tab as read
+    MarkAllRead,
+    /// Mark the selected link as unread, or as read if it is
+    ToggleRead,
+    /// Play the selected link's file or media with the player
+    Play,
+    /// Mark the selected episode as played, or as not played if it is
+    TogglePlayed,
+    /// Play the selected link after what mpv is already playing
+    Enqueue,
+    /// Pause mpv, or play again if it is paused
+    Pause,
+    /// Skip to the next link queued in mpv
+    Next,
+    /// Move the seconds forward in what mpv is playing, back if negative
+    Seek(i64),
+    /// Download the selected link
+    Download,
+    /// Stop the download selected in the downloads pane
+    CancelDownload,
+    /// Start the selected download again if it failed
+    RetryDownload,
+    /// Forget the downloads that have finished
+    ClearDownloads,
+    /// Run the action from the config with the name
+    Action(String),
+    /// Run the line typed in command mode
+    Command(String),
+    /// Complete the word on the command line, backwards if set
+    Complete(bool),
+    /// Send the bytes to the program in the terminal pane
+    Forward(Vec<u8>),
 
     RenameTab,
     DeleteTab,
     AddTab,
 }
+
+impl Msg {
+    /// Names of the actions that can be bound to keys.
+    pub const NAMES: &'static [&'static str] = &[
+        "quit",
+        "edit",
+        "open",
+        "add",
+        "save",
+        "delete",
+        "add_tab",
+        "rename_tab",
+        "delete_tab",
+        "next_tab",
+        "prev_tab",
+        "down",
+        "up",
+        "top",
+        "bottom",
+        "jobs",
+        "command",
+        "download",
+        "downloads",
+        "tag",
+        "rescan",
+        "note",
+        "tab_note",
+        "preview_down",
+        "preview_up",
+        "read",
+        "save_readable",
+        "archive",
+        "open_archive",
+        "search",
+        "refresh_feeds",
+        "mark_all_read",
+        "toggle_read",
+        "play",
+        "toggle_played",
+        "enqueue",
+        "pause",
+        "next",
+        "seek_back",
+        "seek_forward",
+    ];
+
+    /// Makes the message for the action with the name, built in or from the
+    /// config.
+    pub fn from_name(name: &str, stat: &State) -> Option<Msg> {
+        let msg = match name {
+            "quit" => Msg::Quit,
+            "edit" => Msg::Edit,
+            "open" => Msg::Open,
+            "add" => Msg::Add,
+            "save" => Msg::Save,
+            "delete" => Msg::ChangeMode(Mode::Delete(Item::Link)),
+            "add_tab" => Msg::AddTab,
+            "rename_tab" => Msg::RenameTab,
+            "delete_tab" => Msg::ChangeMode(Mode::Delete(Item::Tab)),
+            "next_tab" => Msg::SelectTab(stat.tabb + 1),
+            "prev_tab" => Msg::SelectTab(stat.tabb.saturating_sub(1)),
+            "down" => Msg::MoveDown(1),
+            "up" => Msg::MoveUp(1),
+            "top" => Msg::MoveUp(usize::MAX),
+            "bottom" => Msg::MoveDown(usize::MAX),
+            "jobs" => Msg::ChangeMode(Mode::Jobs),
+            "command" => Msg::ChangeMode(Mode::Command),
+            "download" => Msg::Download,
+            "tag" => Msg::Tag,
+            "rescan" => Msg::Rescan,
+            "note" => Msg::Note(false),
+            "tab_note" => Msg::Note(true),
+            "preview_down" => Msg::ScrollPreview(PAGE),
+            "preview_up" => Msg::ScrollPreview(-PAGE),
+            "downloads" => Msg::ChangeMode(Mode::Downloads),
+            "read" => Msg::Read,
+            "save_readable" => Msg::SaveReadable,
+            "archive" => Msg::Archive,
+            "open_archive" => Msg::OpenArchive,
+            "search" => Msg::Prompt("search ".into()),
+            "refresh_feeds" => Msg::RefreshFeeds,
+            "mark_all_read" => Msg::MarkAllRead,
+            "toggle_read" => Msg::ToggleRead,
+            "play" => Msg::Play,
+            "toggle_played" => Msg::TogglePlayed,
+            "enqueue" => Msg::Enqueue,
+            "pause" => Msg::Pause,
+            "next" => Msg::Next,
+            "seek_back" => Msg::Seek(-SEEK),
+            "seek_forward" => Msg::Seek(SEEK),
+            _ => {
+                return stat
+                    .options
+                    .action(name)
+                    .map(|a| Msg::Action(a.name.clone()))
+            }
+        };
+        Some(msg)
+    }
+}
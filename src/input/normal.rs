@@ -1,7 +1,7 @@
 use crate::{
     app::{Mode, State},
     event::Key,
-    input::Msg,
+    input::{Msg, PAGE, SEEK},
 };
 
 pub fn handle(key: Key, stat: &mut State) -> Option<Msg> {
@@ -12,6 +12,7 @@ pub fn handle(key: Key, stat: &mut State) -> Option<Msg> {
         Key::Char('r') => Msg::RenameTab,
 
         Key::Char('a') => Msg::Add,
+        Key::Char('s') => Msg::Save,
         Key::Char('n') => Msg::AddTab,
 
         Key::Enter | Key::Char('o') => Msg::Open,
@@ -23,6 +24,33 @@ pub fn handle(key: Key, stat: &mut State) -> Option<Msg> {
             Msg::ChangeMode(Mode::Delete(crate::app::Item::Link))
         }
         Key::Char('D') => Msg::ChangeMode(Mode::Delete(crate::app::Item::Tab)),
+        Key::Char('J') => Msg::ChangeMode(Mode::Jobs),
+        Key::Char(':') => Msg::ChangeMode(Mode::Command),
+        Key::Char('/') => Msg::Prompt("search ".into()),
+        Key::Char('f') => Msg::Download,
+        Key::Char('t') => Msg::Tag,
+        Key::Char('N') => Msg::Note(false),
+        Key::Ctrl('e') => Msg::ScrollPreview(1),
+        Key::Ctrl('y') => Msg::ScrollPreview(-1),
+        Key::Ctrl('f') => Msg::ScrollPreview(PAGE),
+        Key::Ctrl('b') => Msg::ScrollPreview(-PAGE),
+        Key::Char('M') => Msg::Note(true),
+        Key::Ctrl('\\') if stat.term.is_some() => Msg::ChangeMode(Mode::Term),
+        Key::Char('F') => Msg::ChangeMode(Mode::Downloads),
+        Key::Char('R') => Msg::Read,
+        Key::Char('W') => Msg::SaveReadable,
+        Key::Char('A') => Msg::Archive,
+        Key::Char('O') => Msg::OpenArchive,
+        Key::Char('U') => Msg::RefreshFeeds,
+        Key::Char('X') => Msg::MarkAllRead,
+        Key::Char('u') => Msg::ToggleRead,
+        Key::Char('p') => Msg::Play,
+        Key::Char('P') => Msg::TogglePlayed,
+        Key::Char('Q') => Msg::Enqueue,
+        Key::Char(' ') => Msg::Pause,
+        Key::Char('>') => Msg::Next,
+        Key::Char(',') => Msg::Seek(-SEEK),
+        Key::Char('.') => Msg::Seek(SEEK),
 
         Key::Up | Key::Char('k') => Msg::MoveUp(1),
         Key::Down | Key::Char('j') => Msg::MoveDown(1),
@@ -0,0 +1,41 @@
+use crate::{app::State, event::Key, input::Msg};
+
+pub fn handle(key: Key, stat: &mut State) -> Option<Msg> {
+    let reader = stat.reader.as_mut()?;
+    stat.draw = true;
+
+    if let Some(typing) = &mut reader.typing {
+        match key {
+            Key::Enter => {
+                let query = std::mem::take(typing);
+                reader.typing = None;
+                reader.find(query);
+            }
+            Key::Esc | Key::Ctrl('c') => reader.typing = None,
+            Key::Backspace => {
+                typing.pop();
+            }
+            Key::Char(c) => typing.push(c),
+            _ => {}
+        }
+        return None;
+    }
+
+    let page = reader.page();
+    match key {
+        Key::Char('q') | Key::Esc | Key::Ctrl('c') => return Some(Msg::CloseReader),
+        Key::Down | Key::Char('j') | Key::Ctrl('e') => reader.scroll(1),
+        Key::Up | Key::Char('k') | Key::Ctrl('y') => reader.scroll(-1),
+        Key::PageDown | Key::Char(' ') | Key::Ctrl('f') => reader.scroll(page),
+        Key::PageUp | Key::Ctrl('b') => reader.scroll(-page),
+        Key::Ctrl('d') => reader.scroll(page / 2),
+        Key::Ctrl('u') => reader.scroll(-page / 2),
+        Key::Home | Key::Char('g') => reader.scroll(isize::MIN),
+        Key::End | Key::Char('G') => reader.scroll(isize::MAX),
+        Key::Char('/') => reader.typing = Some(String::new()),
+        Key::Char('n') => reader.next(false),
+        Key::Char('N') => reader.next(true),
+        _ => stat.draw = false,
+    }
+    None
+}
@@ -0,0 +1,35 @@
+use crate::{
+    app::{Mode, State},
+    event::Key,
+    input::Msg,
+};
+
+pub fn handle(key: Key, stat: &mut State) -> Option<Msg> {
+    stat.draw = true;
+    let sel = stat.hit_selected;
+    let last = stat.hits.len().saturating_sub(1);
+
+    let msg = match key {
+        Key::Enter => Msg::GoToHit,
+        Key::Char('q') | Key::Ctrl('c') | Key::Esc => Msg::ChangeMode(Mode::Normal),
+        Key::Char('/') => Msg::Prompt("search ".into()),
+        Key::Up | Key::Char('k') => {
+            stat.hit_selected = sel.saturating_sub(1);
+            return None;
+        }
+        Key::Down | Key::Char('j') => {
+            stat.hit_selected = (sel + 1).min(last);
+            return None;
+        }
+        Key::Char('g') | Key::Home => {
+            stat.hit_selected = 0;
+            return None;
+        }
+        Key::Char('G') | Key::End => {
+            stat.hit_selected = last;
+            return None;
+        }
+        _ => return None,
+    };
+    Some(msg)
+}
@@ -0,0 +1,69 @@
+use crate::{app::Mode, event::Key, input::Msg};
+
+/// Turns the key back into the bytes a terminal would send for it. `C-\`
+/// goes back to the list and leaves the program running.
+pub fn handle(key: Key, parser: &vt100::Parser) -> Option<Msg> {
+    if key == Key::Ctrl('\\') {
+        return Some(Msg::ChangeMode(Mode::Normal));
+    }
+
+    // arrow keys are sent differently when the program asks for it
+    let app_cursor = parser.screen().application_cursor();
+    let arrow = |c: char| {
+        if app_cursor {
+            format!("\x1bO{}", c).into_bytes()
+        } else {
+            format!("\x1b[{}", c).into_bytes()
+        }
+    };
+
+    let bytes = match key {
+        Key::Enter => b"\r".to_vec(),
+        Key::Tab => b"\t".to_vec(),
+        Key::ShiftTab => b"\x1b[Z".to_vec(),
+        Key::Backspace => b"\x7f".to_vec(),
+        Key::Esc => b"\x1b".to_vec(),
+
+        Key::Up => arrow('A'),
+        Key::Down => arrow('B'),
+        Key::Right => arrow('C'),
+        Key::Left => arrow('D'),
+
+        Key::Home => b"\x1b[H".to_vec(),
+        Key::End => b"\x1b[F".to_vec(),
+        Key::Ins => b"\x1b[2~".to_vec(),
+        Key::Delete => b"\x1b[3~".to_vec(),
+        Key::PageUp => b"\x1b[5~".to_vec(),
+        Key::PageDown => b"\x1b[6~".to_vec(),
+
+        Key::F(n @ 1..=4) => format!("\x1bO{}", (b'P' + n - 1) as char).into_bytes(),
+        Key::F(n) => {
+            let code = match n {
+                5 => 15,
+                6 => 17,
+                7 => 18,
+                8 => 19,
+                9 => 20,
+                10 => 21,
+                11 => 23,
+                12 => 24,
+                _ => return None,
+            };
+            format!("\x1b[{}~", code).into_bytes()
+        }
+
+        Key::Char(c) => c.to_string().into_bytes(),
+        Key::Ctrl(c) => match c {
+            'a'..='z' => vec![c as u8 - b'a' + 1],
+            ' ' | '@' => vec![0],
+            '[' => vec![0x1b],
+            ']' => vec![0x1d],
+            '^' => vec![0x1e],
+            '_' => vec![0x1f],
+            _ => return None,
+        },
+        Key::Alt(c) => format!("\x1b{}", c).into_bytes(),
+        Key::Unknown => return None,
+    };
+    Some(Msg::Forward(bytes))
+}
@@ -0,0 +1,195 @@
+//! Processes started by openers that run in the background.
+
+use std::process::{Child, ExitStatus};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::prelude::*;
+use crate::state::Link;
+
+/// The most finished jobs that are remembered.
+const MAX_FINISHED: usize = 32;
+
+pub struct Job {
+    /// What was run, shown in the job list.
+    pub name: String,
+    pub started: Instant,
+    child: Child,
+    /// The exit status and how long it ran, once the process has been reaped.
+    pub done: Option<(ExitStatus, Duration)>,
+    /// Reads the output of the process when it is kept.
+    out: Option<(Capture, JoinHandle<io::Result<String>>)>,
+}
+
+/// Where the output of a job goes once it exits.
+#[derive(Debug, Clone)]
+pub struct Capture {
+    pub tabb: usize,
+    /// The link the job was started on, to find it again if it has moved.
+    pub link: Link,
+    /// The field of the link that is set, or `yaml` for a map of fields.
+    pub field: String,
+}
+
+impl Job {
+    pub fn new(name: String, child: Child) -> Self {
+        Self {
+            name,
+            started: Instant::now(),
+            child,
+            done: None,
+            out: None,
+        }
+    }
+
+    /// A job that keeps what the process writes to stdout, see [`Job::output`].
+    pub fn capture(name: String, mut child: Child, to: Capture) -> Self {
+        let out = child.stdout.take().map(|mut o| {
+            std::thread::spawn(move || {
+                let mut buf = String::new();
+                io::Read::read_to_string(&mut o, &mut buf).map(|_| buf)
+            })
+        });
+        let mut job = Self::new(name, child);
+        job.out = out.map(|o| (to, o));
+        job
+    }
+
+    /// The output of a job that exited successfully. It is only given once.
+    pub fn output(&mut self) -> Option<(Capture, io::Result<String>)> {
+        let (status, _) = self.done?;
+        let (to, out) = self.out.take()?;
+        if !status.success() {
+            return None;
+        }
+        let out = out
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("output reader panicked")));
+        Some((to, out))
+    }
+
+    /// Reaps the process if it has exited. Returns the status only the first
+    /// time it is seen.
+    pub fn poll(&mut self) -> Option<ExitStatus> {
+        if self.done.is_some() {
+            return None;
+        }
+        match self.child.try_wait() {
+            Ok(Some(status)) => {
+                self.done = Some((status, self.started.elapsed()));
+                Some(status)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                log::warn!("could not wait on {}: {}", self.name, e);
+                None
+            }
+        }
+    }
+
+    pub fn running(&self) -> bool {
+        self.done.is_none()
+    }
+}
+
+impl fmt::Debug for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Job")
+            .field("name", &self.name)
+            .field("pid", &self.child.id())
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+/// Reaps every job that has exited and returns the ones that failed.
+pub fn reap(jobs: &mut Vec<Job>) -> Vec<(String, ExitStatus)> {
+    let failed = jobs
+        .iter_mut()
+        .filter_map(|j| j.poll().map(|s| (j.name.clone(), s)))
+        .filter(|(_, s)| !s.success())
+        .collect();
+
+    // forget the oldest finished jobs so the list doesn't grow forever, but
+    // not before their output has been taken
+    let gone = |j: &Job| !j.running() && j.out.is_none();
+    let mut finished = jobs.iter().filter(|j| gone(j)).count();
+    jobs.retain(|j| {
+        if finished > MAX_FINISHED && gone(j) {
+            finished -= 1;
+            false
+        } else {
+            true
+        }
+    });
+
+    failed
+}
+
+#[cfg(test)]
+mod test {
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+
+    use super::{reap, Capture, Job, MAX_FINISHED};
+
+    #[test]
+    fn reaps_failures() {
+        let spawn = |code: u8| {
+            let c = Command::new("sh")
+                .args(["-c", &format!("exit {}", code)])
+                .spawn()
+                .unwrap();
+            Job::new(format!("exit {}", code), c)
+        };
+        let mut jobs = vec![spawn(0), spawn(3)];
+
+        let start = Instant::now();
+        let mut failed = Vec::new();
+        while jobs.iter().any(Job::running) {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            failed.extend(reap(&mut jobs));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, "exit 3");
+        assert_eq!(failed[0].1.code(), Some(3));
+        // a reaped job is not reported twice
+        assert!(reap(&mut jobs).is_empty());
+    }
+
+    #[test]
+    fn keeps_output() {
+        let to = Capture {
+            tabb: 0,
+            link: Default::default(),
+            field: "desc".into(),
+        };
+        let mut jobs: Vec<_> = (0..MAX_FINISHED + 2)
+            .map(|i| {
+                let c = Command::new("echo")
+                    .arg(i.to_string())
+                    .stdout(std::process::Stdio::piped())
+                    .spawn()
+                    .unwrap();
+                Job::capture(i.to_string(), c, to.clone())
+            })
+            .collect();
+
+        let start = Instant::now();
+        while jobs.iter().any(Job::running) {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            reap(&mut jobs);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        // every job is kept until its output is taken
+        assert_eq!(jobs.len(), MAX_FINISHED + 2);
+        let outs: Vec<_> = jobs.iter_mut().filter_map(Job::output).collect();
+        assert_eq!(outs.len(), MAX_FINISHED + 2);
+        assert_eq!(outs[0].1.as_ref().unwrap(), "0\n");
+        reap(&mut jobs);
+        assert_eq!(jobs.len(), MAX_FINISHED);
+    }
+}
@@ -1,66 +1,615 @@
+//! The embedded lua runtime that runs the config and user scripts.
+//!
+//! Everything is reached through the global `luma` table:
+//!
+//! - `luma.o` options, see [`Options`]
+//! - `luma.keymap.set(mode, key, action)` binds a key to an action name or function
+//! - `luma.opener { pattern = "youtube%.com", cmd = { "mpv" }, term = false }`,
+//!   `download = { "yt-dlp", "-P", "{dir}", "{link}" }` fetches matching links
+//!   with the command, see [`crate::download`]
+//! - `luma.on(event, function)` runs the function when the event happens, see
+//!   [`crate::hook`] for the hooks around changes to links
+//! - `luma.notify(msg, level)` shows a toast
+//! - `luma.db` reads and changes the links, tabs and links are counted from 1
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
 use mlua::prelude::*;
+use mlua::{Function, RegistryKey, Table, Value};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::app::Level;
+use crate::config::Options;
+use crate::event::Key;
+use crate::hook::Flow;
+use crate::input::Msg;
+use crate::prelude::*;
+use crate::state::{Link, OpenCommand, Validate};
+
+#[derive(Debug)]
+pub struct ScriptError;
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("lua script failed")
+    }
+}
+impl Context for ScriptError {}
 
-use std::fs;
+/// What scripts can see of the app while they run.
+#[derive(Debug, Default)]
+pub struct Ctx {
+    pub luma: Luma,
+    pub tabb: usize,
+    pub selected: usize,
+    /// Set when a script changes [`Luma`].
+    pub dirty: bool,
+    /// Messages for the user from `luma.notify`.
+    pub toasts: Vec<(Level, String)>,
+}
 
-// add from LuaError for LumaError
+/// What a key is bound to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Binding {
+    /// The name of an action from [`Msg::NAMES`].
+    Action(String),
+    /// A lua function, run with [`Script::call_key`].
+    Function,
+}
 
-async fn run() -> Result<()> {
-    let lua = Lua::new();
-    // let map_table = lua.create_table()?;
-    // map_table.set(1, "one")?;
-    // map_table.set("two", 2)?;
-    // lua.globals().set("map_table", map_table)?;
+enum Bind {
+    Action(String),
+    Function(RegistryKey),
+}
 
-    let globals = lua.globals();
-    globals.set("luma", LUMA.read().unwrap());
+/// Opens links that match the pattern with the command and downloads them
+/// with the other one.
+struct Rule {
+    pattern: String,
+    cmd: Option<OpenCommand>,
+    download: Option<Vec<String>>,
+}
 
-    lua.set_hook(HookTriggers::EVERY_LINE, |_lua, debug| {
-        log::info!("line {}", debug.curr_line());
-        Ok(())
-    });
-
-    lua.load(
-        r#"
-    local x = 2 + 3
-    local y = x * 63
-    local z = string.len(x..", "..y)
-"#,
-    )
-    .exec();
-    let greet = lua.create_function(|_, name: String| {
-        log::info!("Hello, {}!", name);
-        Ok(())
-    });
-
-    let my_table: Table = lua
-        .load(
-            r#"
-    {
-        [1] = 4,
-        [2] = 5,
-        [4] = 7,
-        key = 2
-    }
-"#,
-        )
-        .eval()?;
-
-    let table1 = lua.create_table()?;
-    table1.set(1, "value")?;
-
-    let p_person = lua.create_function(|_, (name, age): (String, u8)| {
-        log::info!("{} is {} years old!", name, age);
+/// Things registered by scripts.
+#[derive(Default)]
+struct Registry {
+    keymaps: HashMap<Key, Bind>,
+    openers: Vec<Rule>,
+    hooks: HashMap<String, Vec<RegistryKey>>,
+}
+
+pub struct Script {
+    lua: Lua,
+}
+
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Script").finish_non_exhaustive()
+    }
+}
+
+/// Where the config script is looked for.
+pub fn config_path() -> PathBuf {
+    crate::config::dir().join("init.lua")
+}
+
+impl Script {
+    pub fn new() -> Result<Self, ScriptError> {
+        let lua = Lua::new();
+        lua.set_app_data(Registry::default());
+        api(&lua)
+            .change_context(ScriptError)
+            .attach_printable("could not create the luma table")?;
+        Ok(Self { lua })
+    }
+
+    /// Lets scripts see and change the context while `f` runs.
+    pub fn run<R>(
+        &self,
+        ctx: Ctx,
+        f: impl FnOnce(&Self) -> LuaResult<R>,
+    ) -> (Ctx, Result<R, ScriptError>) {
+        self.lua.set_app_data(ctx);
+        let res = f(self).change_context(ScriptError);
+        let ctx = self.lua.remove_app_data::<Ctx>().unwrap_or_default();
+        (ctx, res)
+    }
+
+    /// Runs the file. Modules it `require`s are looked for next to it.
+    pub fn load(&self, path: &Path) -> LuaResult<()> {
+        if let Some(dir) = path.parent() {
+            let package: Table = self.lua.globals().get("package")?;
+            let old: String = package.get("path")?;
+            let new = format!("{0}/?.lua;{0}/?/init.lua;{1}", dir.display(), old);
+            package.set("path", new)?;
+        }
+
+        let src = fs::read_to_string(path).into_lua_err()?;
+        self.lua
+            .load(src)
+            .set_name(format!("@{}", path.display()))
+            .exec()
+    }
+
+    /// Reads back `luma.o`.
+    pub fn options(&self) -> Result<Options, ScriptError> {
+        let get = || -> LuaResult<Options> {
+            let luma: Table = self.lua.globals().get("luma")?;
+            self.lua.from_value(luma.get("o")?)
+        };
+        get()
+            .change_context(ScriptError)
+            .attach_printable("luma.o is not valid")
+    }
+
+    pub fn keymap(&self, key: Key) -> Option<Binding> {
+        let reg = self.lua.app_data_ref::<Registry>()?;
+        match reg.keymaps.get(&key)? {
+            Bind::Action(a) => Some(Binding::Action(a.clone())),
+            Bind::Function(_) => Some(Binding::Function),
+        }
+    }
+
+    /// Runs the function bound to the key.
+    pub fn call_key(&self, key: Key) -> LuaResult<()> {
+        let f: Function = {
+            let reg = self.lua.app_data_ref::<Registry>().unwrap();
+            match reg.keymaps.get(&key) {
+                Some(Bind::Function(f)) => self.lua.registry_value(f)?,
+                _ => return Ok(()),
+            }
+        };
+        f.call(key.to_string())
+    }
+
+    /// Finds the first opener rule that matches the link or file.
+    pub fn opener(&self, link: &Link) -> LuaResult<Option<OpenCommand>> {
+        self.find_rule(link, |r| r.cmd.clone())
+    }
+
+    /// Finds the download command of the first rule that matches the link.
+    pub fn downloader(&self, link: &Link) -> LuaResult<Option<Vec<String>>> {
+        self.find_rule(link, |r| r.download.clone())
+    }
+
+    fn find_rule<T>(&self, link: &Link, get: impl Fn(&Rule) -> Option<T>) -> LuaResult<Option<T>> {
+        let string: Table = self.lua.globals().get("string")?;
+        let find: Function = string.get("find")?;
+
+        let reg = self.lua.app_data_ref::<Registry>().unwrap();
+        for rule in &reg.openers {
+            let Some(v) = get(rule) else {
+                continue;
+            };
+            for s in [Some(&link.link), link.file.as_ref()].into_iter().flatten() {
+                let found: Value = find.call((s.as_str(), rule.pattern.as_str()))?;
+                if !found.is_nil() {
+                    return Ok(Some(v));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Calls every function registered for the event with the arguments.
+    pub fn fire<'lua>(
+        &'lua self,
+        event: &str,
+        args: impl IntoLuaMulti<'lua> + Clone,
+    ) -> LuaResult<()> {
+        for f in self.handlers(event)? {
+            f.call::<_, ()>(args.clone())?;
+        }
         Ok(())
-    });
+    }
+
+    /// Passes the value through the functions registered for the hook. Each
+    /// can return `false` to stop, a new value to replace it or nothing to
+    /// keep it.
+    pub fn filter<T: Serialize + DeserializeOwned>(
+        &self,
+        hook: &str,
+        tab: Option<&str>,
+        mut value: T,
+    ) -> LuaResult<Flow<T>> {
+        for f in self.handlers(hook)? {
+            let ret: Value = f.call((self.lua.to_value(&value)?, tab))?;
+            match ret {
+                Value::Nil | Value::Boolean(true) => {}
+                Value::Boolean(false) => return Ok(Flow::Stop(format!("{} in lua", hook))),
+                v => value = self.lua.from_value(v)?,
+            }
+        }
+        Ok(Flow::Go(value))
+    }
+
+    fn handlers(&self, event: &str) -> LuaResult<Vec<Function<'_>>> {
+        let reg = self.lua.app_data_ref::<Registry>().unwrap();
+        match reg.hooks.get(event) {
+            Some(keys) => keys.iter().map(|k| self.lua.registry_value(k)).collect(),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+fn api(lua: &Lua) -> LuaResult<()> {
+    let luma = lua.create_table()?;
+    luma.set("o", lua.to_value(&Options::default())?)?;
+
+    let keymap = lua.create_table()?;
+    keymap.set(
+        "set",
+        lua.create_function(|lua, (mode, key, action): (String, String, Value)| {
+            if mode != "n" {
+                return Err(LuaError::external(format!("unknown mode {:?}", mode)));
+            }
+            let key: Key = key.parse().into_lua_err()?;
+            let bind = match action {
+                Value::String(s) => {
+                    let name = s.to_str()?;
+                    if !Msg::NAMES.contains(&name) {
+                        return Err(LuaError::external(format!("unknown action {:?}", name)));
+                    }
+                    Bind::Action(name.to_owned())
+                }
+                Value::Function(f) => Bind::Function(lua.create_registry_value(f)?),
+                v => {
+                    let msg = format!("a key can't be bound to a {}", v.type_name());
+                    return Err(LuaError::external(msg));
+                }
+            };
+            registry(lua).keymaps.insert(key, bind);
+            Ok(())
+        })?,
+    )?;
+    keymap.set(
+        "del",
+        lua.create_function(|lua, (_, key): (String, String)| {
+            let key: Key = key.parse().into_lua_err()?;
+            registry(lua).keymaps.remove(&key);
+            Ok(())
+        })?,
+    )?;
+    luma.set("keymap", keymap)?;
+
+    luma.set(
+        "opener",
+        lua.create_function(|lua, rule: Table| {
+            let pattern: String = rule.get("pattern")?;
+            let argv: Option<Vec<String>> = rule.get("cmd")?;
+            let term: Option<bool> = rule.get("term")?;
+            let download: Option<Vec<String>> = rule.get("download")?;
+
+            let cmd = match argv {
+                Some(argv) => {
+                    let cmd = OpenCommand::new(&argv)
+                        .ok_or_else(|| LuaError::external("the opener cmd is empty"))?;
+                    Some(if term.unwrap_or(false) {
+                        cmd.in_term()
+                    } else {
+                        cmd
+                    })
+                }
+                None if download.is_none() => {
+                    return Err(LuaError::external("an opener needs a cmd or a download"));
+                }
+                None => None,
+            };
+            if download.as_ref().is_some_and(Vec::is_empty) {
+                return Err(LuaError::external("the opener download is empty"));
+            }
+            registry(lua).openers.push(Rule {
+                pattern,
+                cmd,
+                download,
+            });
+            Ok(())
+        })?,
+    )?;
+
+    luma.set(
+        "on",
+        lua.create_function(|lua, (event, f): (String, Function)| {
+            let key = lua.create_registry_value(f)?;
+            registry(lua).hooks.entry(event).or_default().push(key);
+            Ok(())
+        })?,
+    )?;
+
+    luma.set(
+        "notify",
+        lua.create_function(|lua, (msg, level): (String, Option<String>)| {
+            let level = match level.as_deref() {
+                None | Some("info") => Level::Info,
+                Some("warn") => Level::Warn,
+                Some("error") => Level::Error,
+                Some(l) => return Err(LuaError::external(format!("unknown level {:?}", l))),
+            };
+            ctx(lua)?.toasts.push((level, msg));
+            Ok(())
+        })?,
+    )?;
+
+    luma.set("db", db(lua)?)?;
+
+    lua.globals().set("luma", luma)
+}
+
+/// The functions that work on the links.
+fn db(lua: &Lua) -> LuaResult<Table<'_>> {
+    let db = lua.create_table()?;
+
+    db.set(
+        "tabs",
+        lua.create_function(|lua, ()| {
+            let ctx = ctx(lua)?;
+            Ok(ctx
+                .luma
+                .tabs
+                .iter()
+                .map(|t| t.0.clone())
+                .collect::<Vec<_>>())
+        })?,
+    )?;
+
+    db.set(
+        "links",
+        lua.create_function(|lua, tab: Value| {
+            let ctx = ctx(lua)?;
+            let t = tab_index(&ctx.luma, tab)?;
+            lua.to_value(&ctx.luma.tabs[t].1)
+        })?,
+    )?;
+
+    db.set(
+        "get",
+        lua.create_function(|lua, (tab, i): (Value, usize)| {
+            let ctx = ctx(lua)?;
+            let t = tab_index(&ctx.luma, tab)?;
+            match i.checked_sub(1).and_then(|i| ctx.luma.tabs[t].1.get(i)) {
+                Some(l) => lua.to_value(l),
+                None => Ok(Value::Nil),
+            }
+        })?,
+    )?;
+
+    db.set(
+        "add",
+        lua.create_function(|lua, (tab, link): (Value, Value)| {
+            let link = to_link(lua, link)?;
+            let mut ctx = ctx(lua)?;
+            let t = tab_index(&ctx.luma, tab)?;
+            ctx.luma.tabs[t].1.push(link);
+            ctx.dirty = true;
+            Ok(ctx.luma.tabs[t].1.len())
+        })?,
+    )?;
+
+    db.set(
+        "update",
+        lua.create_function(|lua, (tab, i, link): (Value, usize, Value)| {
+            let link = to_link(lua, link)?;
+            let mut ctx = ctx(lua)?;
+            let t = tab_index(&ctx.luma, tab)?;
+            let old = i
+                .checked_sub(1)
+                .and_then(|i| ctx.luma.tabs[t].1.get_mut(i))
+                .ok_or_else(|| LuaError::external(format!("no link at {}", i)))?;
+            *old = link;
+            ctx.dirty = true;
+            Ok(())
+        })?,
+    )?;
+
+    db.set(
+        "remove",
+        lua.create_function(|lua, (tab, i): (Value, usize)| {
+            let mut ctx = ctx(lua)?;
+            let t = tab_index(&ctx.luma, tab)?;
+            let links = &mut ctx.luma.tabs[t].1;
+            if i == 0 || i > links.len() {
+                return Err(LuaError::external(format!("no link at {}", i)));
+            }
+            let old = links.remove(i - 1);
+            ctx.dirty = true;
+            lua.to_value(&old)
+        })?,
+    )?;
+
+    db.set(
+        "add_tab",
+        lua.create_function(|lua, name: String| {
+            if let Some(e) = name.validate().pop() {
+                return Err(LuaError::external(e));
+            }
+            let mut ctx = ctx(lua)?;
+            ctx.luma.tabs.push((name, Vec::new()));
+            ctx.dirty = true;
+            Ok(ctx.luma.tabs.len())
+        })?,
+    )?;
+
+    db.set(
+        "remove_tab",
+        lua.create_function(|lua, tab: Value| {
+            let mut ctx = ctx(lua)?;
+            let t = tab_index(&ctx.luma, tab)?;
+            ctx.luma.remove_tab(t);
+            ctx.dirty = true;
+            Ok(())
+        })?,
+    )?;
+
+    db.set(
+        "selected",
+        lua.create_function(|lua, ()| {
+            let ctx = ctx(lua)?;
+            Ok((ctx.tabb + 1, ctx.selected + 1))
+        })?,
+    )?;
+
+    db.set(
+        "select",
+        lua.create_function(|lua, (tab, i): (Value, Option<usize>)| {
+            let mut ctx = ctx(lua)?;
+            ctx.tabb = tab_index(&ctx.luma, tab)?;
+            ctx.selected = i.unwrap_or(1).saturating_sub(1);
+            Ok(())
+        })?,
+    )?;
+
+    Ok(db)
+}
 
-    Ok(())
+fn registry(lua: &Lua) -> mlua::AppDataRefMut<'_, Registry> {
+    lua.app_data_mut::<Registry>().unwrap()
 }
 
-fn factorial(n: u64, lua: &Lua) -> String {
-    let script = fs::read_to_string("./lua/factorial.lua").unwrap();
-    let global = lua.globals();
-    _ = global.set("arg", vec![n]);
-    let res = lua.load(&script).eval().unwrap();
+fn ctx(lua: &Lua) -> LuaResult<mlua::AppDataRefMut<'_, Ctx>> {
+    lua.app_data_mut::<Ctx>()
+        .ok_or_else(|| LuaError::external("the links can't be used here"))
+}
+
+/// Finds a tab from its number or name.
+fn tab_index(luma: &Luma, tab: Value) -> LuaResult<usize> {
+    let i = match &tab {
+        Value::Integer(i) => i.checked_sub(1).and_then(|i| usize::try_from(i).ok()),
+        Value::Number(n) => Some(*n as usize).and_then(|i| i.checked_sub(1)),
+        Value::String(s) => {
+            let s = s.to_str()?;
+            luma.tabs.iter().position(|t| t.0 == s)
+        }
+        _ => None,
+    };
+    i.filter(|i| *i < luma.tabs.len())
+        .ok_or_else(|| LuaError::external(format!("no tab {:?}", tab)))
+}
+
+fn to_link(lua: &Lua, v: Value) -> LuaResult<Link> {
+    let link: Link = lua.from_value(v)?;
+    let errs = link.validate();
+    if errs.is_empty() {
+        Ok(link)
+    } else {
+        Err(LuaError::external(errs.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{tab_index, Binding, Ctx, Script};
+    use crate::event::Key;
+    use crate::hook::Flow;
+    use crate::prelude::*;
+    use crate::state::Link;
+
+    #[test]
+    fn example_config() {
+        let s = Script::new().unwrap();
+        let (_, res) = s.run(Ctx::default(), |s| s.load(Path::new("lua/init.lua")));
+        res.unwrap();
+        assert_eq!(s.options().unwrap().size, 4);
+    }
+
+    #[test]
+    fn api() {
+        let s = Script::new().unwrap();
+        let ctx = Ctx {
+            luma: Luma {
+                tabs: vec![("web".into(), Vec::new())],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let (ctx, res) = s.run(ctx, |s| {
+            s.lua
+                .load(
+                    r#"
+                    luma.db.add("web", { name = "a", link = "https://a.com" })
+                    local t = luma.db.add_tab("music")
+                    luma.db.add(t, { name = "b", link = "https://youtube.com/b" })
+                    luma.db.update(1, 1, { name = "c", link = "https://c.com" })
+                    luma.db.select("music", 1)
 
-    res
+                    luma.keymap.set("n", "<C-y>", function() luma.db.remove(2, 1) end)
+                    luma.keymap.set("n", "x", "quit")
+                    luma.opener { pattern = "youtube%.com", cmd = { "mpv", "--no-video" } }
+                    luma.opener { pattern = "youtube%.com", download = { "yt-dlp", "{link}" } }
+                    luma.on("test", function(n) luma.notify("got " .. n) end)
+                    "#,
+                )
+                .exec()?;
+            s.fire("test", 1)
+        });
+        res.unwrap();
+
+        assert!(ctx.dirty);
+        assert_eq!(ctx.luma.tabs[0].1[0].name, "c");
+        assert_eq!(ctx.luma.tabs[1].0, "music");
+        assert_eq!((ctx.tabb, ctx.selected), (1, 0));
+        assert_eq!(ctx.toasts[0].1, "got 1");
+
+        assert_eq!(
+            s.keymap(Key::Char('x')),
+            Some(Binding::Action("quit".into()))
+        );
+        assert_eq!(s.keymap(Key::Ctrl('y')), Some(Binding::Function));
+
+        let yt = &ctx.luma.tabs[1].1[0];
+        let cmd = s.opener(yt).unwrap().unwrap();
+        assert_eq!((cmd.name.as_str(), cmd.term), ("mpv", false));
+        assert!(s.opener(&Link::default()).unwrap().is_none());
+        assert_eq!(s.downloader(yt).unwrap().unwrap(), ["yt-dlp", "{link}"]);
+
+        let (ctx, res) = s.run(ctx, |s| s.call_key(Key::Ctrl('y')));
+        res.unwrap();
+        assert!(ctx.luma.tabs[1].1.is_empty());
+
+        // invalid links and tabs are refused
+        let (ctx, res) = s.run(ctx, |s| {
+            s.lua
+                .load(r#"luma.db.add(1, { name = "", link = "nope" })"#)
+                .exec()
+        });
+        assert!(res.is_err());
+        assert!(tab_index(&ctx.luma, mlua::Value::Integer(i64::MIN)).is_err());
+    }
+
+    #[test]
+    fn filter() {
+        let s = Script::new().unwrap();
+        s.lua
+            .load(
+                r#"
+                luma.on("pre_add", function(l, tab)
+                    if l.link:find("youtube") then
+                        l.color = tab
+                        return l
+                    end
+                end)
+                luma.on("pre_add", function(l) return l.name ~= "spam" end)
+                "#,
+            )
+            .exec()
+            .unwrap();
+
+        let link = |name: &str, link: &str| Link {
+            name: name.into(),
+            link: link.into(),
+            ..Default::default()
+        };
+
+        let res = s.filter("pre_add", Some("video"), link("a", "https://youtube.com/a"));
+        let Flow::Go(l) = res.unwrap() else {
+            panic!("add was stopped");
+        };
+        assert_eq!(l.color.as_deref(), Some("video"));
+
+        let res = s.filter("pre_add", None, link("spam", "https://a.com"));
+        assert!(matches!(res.unwrap(), Flow::Stop(_)));
+    }
 }
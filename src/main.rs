@@ -4,27 +4,52 @@
 // #![warn(missing_docs)]
 
 mod app;
+mod archive;
 mod cli;
+mod command;
+mod config;
+mod download;
 mod event;
+mod export;
+mod feed;
+mod hook;
+mod import;
 mod input;
+mod job;
+mod lua;
+mod mpd;
+mod mpv;
+mod note;
+mod playlist;
 mod prelude;
+mod reader;
+mod search;
 mod state;
+mod tag;
+mod term;
+mod thumb;
 mod ui;
 
 use crate::prelude::*;
 
 use std::os::fd::FromRawFd;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use app::App;
 
+/// Where the app saves the links, and where imports are saved so it sees them.
+const SAVE_PATH: &str = "out.json";
+
 #[derive(Debug)]
 enum LumaError {
     Input,
     Parse,
     Event,
     Render,
+    Save,
+    Panic,
+    Start,
 }
 
 impl fmt::Display for LumaError {
@@ -34,6 +59,9 @@ impl fmt::Display for LumaError {
             LumaError::Parse => f.write_str("could not parse input as luma"),
             LumaError::Event => f.write_str("could not read terminal event"),
             LumaError::Render => f.write_str("render pass failed"),
+            LumaError::Save => f.write_str("could not save links"),
+            LumaError::Panic => f.write_str("the app panicked"),
+            LumaError::Start => f.write_str("could not start the app"),
         }
     }
 }
@@ -47,8 +75,13 @@ fn eloop<B: tui::backend::Backend + io::Write>(app: &mut App<B>) -> Result<(), L
     // --------------------------------------------
     while !app.state.quit {
         log::debug!("starting event loop.");
-        if let Some(e) = read_event()? {
-            app.event(e).change_context(LumaError::Event)?;
+        if let Some(e) = read_event(app.timeout())? {
+            if let Err(r) = app.event(e) {
+                app.report(r);
+            }
+        }
+        if let Err(r) = app.tick() {
+            app.report(r);
         }
         if app.state.draw {
             app.draw().change_context(LumaError::Render)?;
@@ -79,6 +112,10 @@ fn main() -> Result<(), LumaError> {
     let f = fs::File::open(&args.input).change_context(LumaError::Input)?;
     let luma: Luma = json::from_reader(f).change_context(LumaError::Parse)?;
 
+    if let Some(dir) = args.import {
+        return import_dir(Path::new(SAVE_PATH), luma, dir);
+    }
+
     // Safety: "I do solumnly swear that this is the only way I will write to
     // stdout and understand that if I choose to do it in any additional way I
     // will remain happy when my program explodes."
@@ -86,18 +123,42 @@ fn main() -> Result<(), LumaError> {
     let mut stdout = unsafe { fs::File::from_raw_fd(1) };
 
     app::init(&mut stdout);
-    let mut app = App::new(luma, stdout);
+    let mut app = match App::new(luma, PathBuf::from(SAVE_PATH), stdout) {
+        Ok(app) => app,
+        Err(e) => {
+            app::deinit(&mut std::io::stdout());
+            return Err(e.change_context(LumaError::Start));
+        }
+    };
+    app.state.cmdline.load(&app::history_path());
+    let config = args.config.clone().unwrap_or_else(lua::config_path);
+    if let Err(r) = app.load_config(&config) {
+        app.report(r);
+    }
 
-    let res = eloop(&mut app);
+    // the panic hook has already given back the terminal by the time this
+    // returns, all that is left is to not lose the links
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| eloop(&mut app)))
+        .unwrap_or_else(|_| Err(Report::new(LumaError::Panic)))
+        .and_then(|()| {
+            if app.state.discard {
+                return Ok(());
+            }
+            app.save().change_context(LumaError::Save)
+        });
 
     let (luma, mut term) = app.finish();
     app::deinit(term.backend_mut());
-    res?;
-
-    // .change_context(LumaError::Panic);
 
-    let f = fs::File::create("out.json").change_context(LumaError::Input)?;
-    json::to_writer_pretty::<_, Luma>(f, &luma).change_context(LumaError::Parse)?;
+    if let Err(e) = res {
+        return Err(match emergency_save(&args.input, &luma) {
+            Ok(p) => e.attach_printable(format!("your links were saved to {}", p.display())),
+            Err(se) => {
+                log::error!("{:?}", se);
+                e.attach_printable("your links could not be saved")
+            }
+        });
+    }
 
     log::trace!("exit.");
 
@@ -109,8 +170,43 @@ fn main() -> Result<(), LumaError> {
     // an so the error displays correctly.
 }
 
-fn read_event() -> Result<Option<crossterm::event::Event>, LumaError> {
-    if crossterm::event::poll(Duration::from_secs(3)).change_context(LumaError::Event)? {
+/// Adds the files of the directory to its tab and saves the links to the path.
+fn import_dir(path: &Path, mut luma: Luma, dir: import::ImportDir) -> Result<(), LumaError> {
+    let tabb = match luma.tabs.iter().position(|t| t.0 == dir.tab) {
+        Some(i) => i,
+        None => {
+            luma.tabs.push((dir.tab.clone(), Vec::new()));
+            luma.tabs.len() - 1
+        }
+    };
+
+    let scan = dir
+        .scan(&luma.tabs[tabb].1)
+        .change_context(LumaError::Input)
+        .attach_printable_lazy(|| format!("could not import {}", dir.path.display()))?;
+    eprintln!("{}: {}", dir.tab, scan);
+    scan.apply(&mut luma.tabs[tabb].1);
+
+    // importing the same directory again only changes its globs
+    luma.dirs.retain(|d| d.tab != dir.tab || d.path != dir.path);
+    luma.dirs.push(dir);
+
+    let f = fs::File::create(path).change_context(LumaError::Save)?;
+    json::to_writer_pretty(io::BufWriter::new(f), &luma).change_context(LumaError::Save)
+}
+
+/// Writes the links next to the input file when the app can't exit normally.
+fn emergency_save(input: &Path, luma: &Luma) -> Result<PathBuf, LumaError> {
+    let path = input.with_extension("emergency.json");
+    let f = fs::File::create(&path)
+        .change_context(LumaError::Save)
+        .attach_printable_lazy(|| format!("could not create {}", path.display()))?;
+    json::to_writer_pretty(f, luma).change_context(LumaError::Save)?;
+    Ok(path)
+}
+
+fn read_event(timeout: Duration) -> Result<Option<crossterm::event::Event>, LumaError> {
+    if crossterm::event::poll(timeout).change_context(LumaError::Event)? {
         let e = crossterm::event::read().change_context(LumaError::Event)?;
         Ok(Some(e))
     } else {
@@ -140,29 +236,3 @@ fn init_logger(file: Option<PathBuf>) {
 
     log::debug!("log init");
 }
-
-use pty_process as _;
-// fn a() {
-//     use std::io::Read;
-//     let mut pty = pty_process::blocking::Pty::new().unwrap();
-//
-//     let (x, y) = crossterm::terminal::size().unwrap();
-//     pty.resize(pty_process::Size::new(x, y)).unwrap();
-//
-//     let mut cmd = pty_process::blocking::Command::new("nvim");
-//     let mut child = cmd.spawn(&pty.pts().unwrap()).unwrap();
-//     loop {
-//         if child.try_wait().unwrap().is_some() {
-//             break;
-//         }
-//         let mut buf = [0u8; 256];
-//         let len = pty.read(&mut buf).unwrap();
-//         if len == 0 {
-//             break;
-//         }
-//         use std::io::Write;
-//         std::io::stdout().write_all(&buf[..len]).unwrap();
-//         // let s = std::str::from_utf8(&buf[..len]).unwrap();
-//         // log::info!("{}", s);
-//     }
-// }
@@ -0,0 +1,518 @@
+//! Talking to MPD, the music player daemon, over its text protocol.
+//!
+//! A command is a line, and MPD answers with `key: value` lines ending in
+//! `OK`, or with an `ACK` line when the command failed.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::mpv::Playing;
+use crate::prelude::*;
+use crate::state::Link;
+
+/// How long to wait on MPD before giving up on it.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often MPD is asked what it is playing.
+const POLL: Duration = Duration::from_millis(500);
+
+/// Where MPD is: the option, else `MPD_HOST` and `MPD_PORT`, else the default
+/// port on this machine. A password can go before the host, as `pass@host`,
+/// and a host starting with `/` is the path of a socket.
+pub fn address(option: &str) -> String {
+    if !option.is_empty() {
+        return option.to_owned();
+    }
+    let host = env::var("MPD_HOST").unwrap_or_else(|_| "localhost".into());
+    let port = env::var("MPD_PORT").unwrap_or_else(|_| "6600".into());
+    let bare = host.rsplit('@').next().unwrap_or_default();
+    if bare.starts_with('/') || bare.contains(':') {
+        host
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// Quotes an argument so spaces and quotes in it get through.
+fn quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A song from the library or a playlist.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Song {
+    /// Its path in the music directory, or a url.
+    pub file: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    /// Seconds it lasts.
+    pub duration: u64,
+}
+
+impl Song {
+    /// Makes a link to the song. Files are found in the music directory when
+    /// it is known.
+    pub fn link(&self, music_dir: Option<&Path>) -> Link {
+        let is_url = self.file.contains("://");
+        let stem = Path::new(&self.file).file_stem().unwrap_or_default();
+        let name = match &self.title {
+            Some(t) => t.clone(),
+            None if is_url => self.file.clone(),
+            None => stem.to_string_lossy().into_owned(),
+        };
+        let (link, file) = match music_dir {
+            _ if is_url => (self.file.clone(), None),
+            Some(dir) => (String::new(), Some(dir.join(&self.file))),
+            None => (String::new(), Some(PathBuf::from(&self.file))),
+        };
+        Link {
+            name,
+            link,
+            file: file.map(|f| f.to_string_lossy().into_owned()),
+            artist: self.artist.clone(),
+            duration: self.duration,
+            ..Default::default()
+        }
+    }
+}
+
+/// What MPD is given to play for the link: its file relative to the music
+/// directory, or else its media url. MPD only takes other files when it is
+/// connected to by its socket, as `file://` urls.
+pub fn uri(
+    link: &Link,
+    music_dir: Option<&Path>,
+    local: bool,
+) -> std::result::Result<String, String> {
+    let Some(file) = &link.file else {
+        return Some(link.media().to_owned())
+            .filter(|m| !m.is_empty())
+            .ok_or_else(|| format!("{} has no file or url", link.name));
+    };
+    let path = Path::new(file);
+    match music_dir.map(|d| path.strip_prefix(d)) {
+        Some(Ok(rel)) => Ok(rel.to_string_lossy().into_owned()),
+        _ if local && path.is_absolute() => Ok(format!("file://{}", file)),
+        Some(Err(_)) => Err(format!("{} is not in the music_dir", file)),
+        None => Err("set music_dir to MPD's music directory to add files".into()),
+    }
+}
+
+/// A connection to MPD over the network or its socket.
+#[derive(Debug)]
+enum Stream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn connect(host: &str) -> io::Result<Self> {
+        let stream = if host.starts_with('/') {
+            let s = UnixStream::connect(host)?;
+            s.set_read_timeout(Some(TIMEOUT))?;
+            s.set_write_timeout(Some(TIMEOUT))?;
+            Stream::Unix(s)
+        } else {
+            let s = TcpStream::connect(host)?;
+            s.set_read_timeout(Some(TIMEOUT))?;
+            s.set_write_timeout(Some(TIMEOUT))?;
+            Stream::Tcp(s)
+        };
+        Ok(stream)
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Stream::Tcp(s) => s.try_clone().map(Stream::Tcp),
+            Stream::Unix(s) => s.try_clone().map(Stream::Unix),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.read(buf),
+            Stream::Unix(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.write(buf),
+            Stream::Unix(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(s) => s.flush(),
+            Stream::Unix(s) => s.flush(),
+        }
+    }
+}
+
+/// Songs from the pairs MPD gave back, each starts with its `file`.
+fn songs(pairs: Vec<(String, String)>) -> Vec<Song> {
+    let mut songs: Vec<Song> = Vec::new();
+    for (k, v) in pairs {
+        match k.as_str() {
+            "file" => songs.push(Song {
+                file: v,
+                ..Default::default()
+            }),
+            _ => {
+                let Some(s) = songs.last_mut() else {
+                    continue;
+                };
+                match k.as_str() {
+                    "Title" => s.title = Some(v),
+                    "Artist" if s.artist.is_none() => s.artist = Some(v),
+                    "duration" => s.duration = v.parse::<f64>().unwrap_or(0.0) as u64,
+                    "Time" if s.duration == 0 => s.duration = v.parse().unwrap_or(0),
+                    _ => {}
+                }
+            }
+        }
+    }
+    songs
+}
+
+#[derive(Debug)]
+pub struct Client {
+    read: BufReader<Stream>,
+    write: Stream,
+}
+
+impl Client {
+    /// Connects to MPD at the address and logs in if it has a password.
+    pub fn connect(addr: &str) -> std::result::Result<Self, String> {
+        let (pass, host) = match addr.rsplit_once('@') {
+            Some((p, h)) => (Some(p), h),
+            None => (None, addr),
+        };
+        let stream = Stream::connect(host).map_err(|e| format!("{}: {}", host, e))?;
+        let write = stream.try_clone().map_err(|e| e.to_string())?;
+        let mut client = Self {
+            read: BufReader::new(stream),
+            write,
+        };
+
+        let mut hello = String::new();
+        client
+            .read
+            .read_line(&mut hello)
+            .map_err(|e| e.to_string())?;
+        if !hello.starts_with("OK MPD") {
+            return Err(format!("{} is not MPD", host));
+        }
+        if let Some(p) = pass {
+            client.command(&format!("password {}", quote(p)))?;
+        }
+        Ok(client)
+    }
+
+    /// If MPD is on this machine and connected to by its socket, so it can
+    /// play any file.
+    pub fn is_local(&self) -> bool {
+        matches!(self.write, Stream::Unix(_))
+    }
+
+    /// Runs the command, gives back the pairs MPD answered with.
+    fn command(&mut self, line: &str) -> std::result::Result<Vec<(String, String)>, String> {
+        writeln!(self.write, "{}", line).map_err(|e| e.to_string())?;
+        let mut pairs = Vec::new();
+        loop {
+            let mut l = String::new();
+            match self.read.read_line(&mut l) {
+                Ok(0) => return Err("MPD closed the connection".into()),
+                Ok(_) => {}
+                Err(e) => return Err(e.to_string()),
+            }
+            let l = l.trim_end_matches(['\r', '\n']);
+            if l == "OK" {
+                return Ok(pairs);
+            }
+            if let Some(err) = l.strip_prefix("ACK ") {
+                // the message is after the `[error@line] {command}` part
+                let msg = err.split_once("} ").map_or(err, |(_, m)| m);
+                return Err(msg.to_owned());
+            }
+            if let Some((k, v)) = l.split_once(": ") {
+                pairs.push((k.to_owned(), v.to_owned()));
+            }
+        }
+    }
+
+    /// Adds the uri to the end of the queue.
+    pub fn add(&mut self, uri: &str) -> std::result::Result<(), String> {
+        self.command(&format!("add {}", quote(uri))).map(drop)
+    }
+
+    /// What is playing, `None` when MPD is stopped.
+    pub fn status(&mut self) -> std::result::Result<Option<Playing>, String> {
+        let status = self.command("status")?;
+        let get = |k: &str| {
+            (status.iter())
+                .find(|(key, _)| key == k)
+                .map(|(_, v)| v.as_str())
+        };
+        let num = |k: &str| get(k).and_then(|v| v.parse::<f64>().ok());
+        let paused = match get("state") {
+            Some("play") => false,
+            Some("pause") => true,
+            _ => return Ok(None),
+        };
+        let (pos, duration) = (num("elapsed"), num("duration"));
+        let next = match (num("playlistlength"), num("song")) {
+            (Some(len), Some(at)) => (len - at - 1.0).max(0.0) as usize,
+            _ => 0,
+        };
+
+        let song = songs(self.command("currentsong")?)
+            .pop()
+            .unwrap_or_default();
+        let title = song.link(None).name;
+        let title = match &song.artist {
+            Some(a) => format!("{} - {}", a, title),
+            None => title,
+        };
+        Ok(Some(Playing {
+            title,
+            pos: pos.unwrap_or(0.0),
+            duration: duration.unwrap_or(song.duration as f64),
+            paused,
+            next,
+        }))
+    }
+
+    /// Pauses, or plays again if paused.
+    pub fn pause(&mut self) -> std::result::Result<(), String> {
+        self.command("pause").map(drop)
+    }
+
+    pub fn next(&mut self) -> std::result::Result<(), String> {
+        self.command("next").map(drop)
+    }
+
+    /// Moves the seconds forward, or back if negative.
+    pub fn seek(&mut self, secs: i64) -> std::result::Result<(), String> {
+        self.command(&format!("seekcur {:+}", secs)).map(drop)
+    }
+
+    /// Every song in the library.
+    pub fn library(&mut self) -> std::result::Result<Vec<Song>, String> {
+        self.command("listallinfo").map(songs)
+    }
+
+    /// Names of the saved playlists.
+    pub fn playlists(&mut self) -> std::result::Result<Vec<String>, String> {
+        let pairs = self.command("listplaylists")?;
+        Ok((pairs.into_iter())
+            .filter(|(k, _)| k == "playlist")
+            .map(|(_, v)| v)
+            .collect())
+    }
+
+    /// The songs in the saved playlist.
+    pub fn playlist(&mut self, name: &str) -> std::result::Result<Vec<Song>, String> {
+        self.command(&format!("listplaylistinfo {}", quote(name)))
+            .map(songs)
+    }
+}
+
+/// Asks MPD what it is playing from its own connection and thread, so a
+/// slow MPD doesn't hold up the screen.
+#[derive(Debug)]
+pub struct Watch {
+    rx: mpsc::Receiver<std::result::Result<Option<Playing>, String>>,
+    /// What MPD was playing when it was last asked.
+    pub playing: Option<Playing>,
+}
+
+impl Watch {
+    pub fn new(addr: &str) -> Self {
+        let addr = addr.to_owned();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut client = match Client::connect(&addr) {
+                Ok(c) => c,
+                Err(e) => return drop(tx.send(Err(e))),
+            };
+            loop {
+                let status = client.status();
+                let failed = status.is_err();
+                if tx.send(status).is_err() || failed {
+                    break;
+                }
+                std::thread::sleep(POLL);
+            }
+        });
+        Self { rx, playing: None }
+    }
+
+    /// Takes in what MPD said since the last call, fails when it can't be
+    /// asked anymore.
+    pub fn tick(&mut self) -> std::result::Result<(), String> {
+        loop {
+            match self.rx.try_recv() {
+                Ok(status) => self.playing = status?,
+                Err(mpsc::TryRecvError::Empty) => return Ok(()),
+                Err(mpsc::TryRecvError::Disconnected) => return Err("MPD stopped answering".into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::os::unix::net::UnixListener;
+    use std::path::Path;
+
+    use super::{uri, Client, Song, Watch};
+    use crate::mpv::Playing;
+    use crate::state::Link;
+
+    /// A fake MPD that knows a few commands, gives back what it was sent.
+    fn fake(stream: impl Read, mut out: impl Write) -> Vec<String> {
+        out.write_all(b"OK MPD 0.23.5\n").unwrap();
+        let mut got = Vec::new();
+        for line in BufReader::new(stream).lines() {
+            let line = line.unwrap();
+            let reply = match line.as_str() {
+                "password \"secret\"" | "add \"rock/a.flac\"" => "OK\n",
+                "add \"file:///tmp/b.flac\"" => "OK\n",
+                "status" => concat!(
+                    "volume: 50\nstate: pause\nsong: 1\nplaylistlength: 3\n",
+                    "elapsed: 61.500\nduration: 120.000\nOK\n",
+                ),
+                "currentsong" => "file: rock/a.flac\nArtist: A\nTitle: Song A\nOK\n",
+                "listallinfo" => concat!(
+                    "directory: rock\n",
+                    "file: rock/a.flac\nArtist: A\nTitle: Song A\nduration: 120.4\n",
+                    "file: rock/b.flac\nTime: 90\n",
+                    "OK\n",
+                ),
+                "listplaylists" => "playlist: road trip\nLast-Modified: x\nOK\n",
+                "listplaylistinfo \"road trip\"" => {
+                    "file: http://radio.example/live\nName: Radio\nOK\n"
+                }
+                _ => "ACK [5@0] {} unknown command\n",
+            };
+            got.push(line);
+            out.write_all(reply.as_bytes()).unwrap();
+        }
+        got
+    }
+
+    #[test]
+    fn talking() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            fake(stream.try_clone().unwrap(), stream)
+        });
+
+        let mut mpd = Client::connect(&format!("secret@{}", addr)).unwrap();
+        let music = Path::new("/music");
+
+        let a = Link {
+            name: "a".into(),
+            file: Some("/music/rock/a.flac".into()),
+            ..Default::default()
+        };
+        assert!(!mpd.is_local());
+        mpd.add(&uri(&a, Some(music), false).unwrap()).unwrap();
+        // other files can only be played by MPD on this machine
+        let b = Link {
+            file: Some("/tmp/b.flac".into()),
+            ..a.clone()
+        };
+        assert_eq!(
+            uri(&b, Some(music), false),
+            Err("/tmp/b.flac is not in the music_dir".into())
+        );
+        assert!(uri(&a, None, false).is_err());
+        assert_eq!(uri(&b, None, true).unwrap(), "file:///tmp/b.flac");
+
+        assert_eq!(
+            mpd.status().unwrap(),
+            Some(Playing {
+                title: "A - Song A".into(),
+                pos: 61.5,
+                duration: 120.0,
+                paused: true,
+                next: 1,
+            })
+        );
+
+        let library = mpd.library().unwrap();
+        assert_eq!(
+            library,
+            [
+                Song {
+                    file: "rock/a.flac".into(),
+                    title: Some("Song A".into()),
+                    artist: Some("A".into()),
+                    duration: 120,
+                },
+                Song {
+                    file: "rock/b.flac".into(),
+                    duration: 90,
+                    ..Default::default()
+                },
+            ]
+        );
+        let link = library[1].link(Some(music));
+        assert_eq!(link.name, "b");
+        assert_eq!(link.file.as_deref(), Some("/music/rock/b.flac"));
+
+        assert_eq!(mpd.playlists().unwrap(), ["road trip"]);
+        let list = mpd.playlist("road trip").unwrap();
+        let link = list[0].link(Some(music));
+        assert_eq!(link.link, "http://radio.example/live");
+        assert_eq!(link.file, None);
+
+        drop(mpd);
+        assert_eq!(server.join().unwrap()[0], "password \"secret\"");
+
+        // MPD's socket
+        let dir = tempfile::tempdir().unwrap();
+        let sock = dir.path().join("socket");
+        let listener = UnixListener::bind(&sock).unwrap();
+        let server = std::thread::spawn(move || {
+            let mut got = Vec::new();
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().unwrap();
+                got.push(fake(stream.try_clone().unwrap(), stream));
+            }
+            got
+        });
+        let mut mpd = Client::connect(&sock.to_string_lossy()).unwrap();
+        assert!(mpd.is_local());
+        mpd.add(&uri(&b, Some(music), true).unwrap()).unwrap();
+        drop(mpd);
+
+        // what is playing is asked for in the background
+        let mut watch = Watch::new(&sock.to_string_lossy());
+        let start = std::time::Instant::now();
+        while watch.playing.is_none() {
+            assert!(start.elapsed() < std::time::Duration::from_secs(5));
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            watch.tick().unwrap();
+        }
+        assert_eq!(watch.playing.as_ref().unwrap().title, "A - Song A");
+        drop(watch);
+        let got = server.join().unwrap();
+        assert_eq!(got[0], ["add \"file:///tmp/b.flac\""]);
+        assert_eq!(got[1][..2], ["status", "currentsong"]);
+    }
+}
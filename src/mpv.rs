@@ -0,0 +1,400 @@
+//! Playing media in mpv, controlled over its JSON IPC socket.
+//!
+//! mpv is started idle the first time something is played and is given a
+//! queue of files and urls. It tells what it is playing by sending events for
+//! the properties it is asked to observe, and the links it played to the end
+//! are given back to be marked played.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::prelude::*;
+use crate::state::{Link, OpenCommand};
+
+/// The properties that are observed, by their id.
+const PROPERTIES: &[&str] = &["media-title", "time-pos", "duration", "pause", "path"];
+
+/// How long mpv is given to quit before it is killed.
+const QUIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// What is playing now.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Playing {
+    pub title: String,
+    /// Seconds into it and how long it is.
+    pub pos: f64,
+    pub duration: f64,
+    pub paused: bool,
+    /// Items queued after it.
+    pub next: usize,
+}
+
+/// A link in the queue and what of it mpv was given.
+#[derive(Debug)]
+struct Queued {
+    tabb: usize,
+    link: Link,
+    what: String,
+}
+
+#[derive(Debug)]
+pub struct Mpv {
+    sock: PathBuf,
+    /// The program and arguments to start mpv with.
+    cmd: OpenCommand,
+    child: Option<Child>,
+    stream: Option<UnixStream>,
+    events: Option<mpsc::Receiver<json::Value>>,
+    /// Commands waiting for mpv to be up.
+    pending: Vec<String>,
+    queue: Vec<Queued>,
+    /// The index in the queue of what is playing.
+    current: Option<usize>,
+    playing: Playing,
+}
+
+/// Where the socket is for this process.
+pub fn socket() -> PathBuf {
+    env::temp_dir().join(format!("luma-mpv-{}.sock", std::process::id()))
+}
+
+impl Mpv {
+    pub fn new(sock: PathBuf, cmd: OpenCommand) -> Self {
+        Self {
+            sock,
+            cmd,
+            child: None,
+            stream: None,
+            events: None,
+            pending: Vec::new(),
+            queue: Vec::new(),
+            current: None,
+            playing: Playing::default(),
+        }
+    }
+
+    /// If the program is mpv, the only player that can be controlled.
+    pub fn is_mpv(cmd: &OpenCommand) -> bool {
+        Path::new(&cmd.name).file_name().is_some_and(|n| n == "mpv")
+    }
+
+    /// Plays what of the link now, or after the rest of the queue.
+    pub fn play(&mut self, tabb: usize, link: Link, what: String, append: bool) {
+        let mode = if append { "append-play" } else { "replace" };
+        if !append {
+            self.queue.clear();
+            self.current = None;
+        }
+        self.command(json::json!(["loadfile", what, mode]));
+        self.queue.push(Queued { tabb, link, what });
+        self.start();
+    }
+
+    pub fn pause(&mut self) {
+        self.command(json::json!(["cycle", "pause"]));
+    }
+
+    pub fn next(&mut self) {
+        self.command(json::json!(["playlist-next", "force"]));
+    }
+
+    /// Moves the seconds forward, or back if negative.
+    pub fn seek(&mut self, secs: i64) {
+        self.command(json::json!(["seek", secs, "relative"]));
+    }
+
+    /// What is playing, if anything is.
+    pub fn playing(&self) -> Option<&Playing> {
+        self.current.map(|_| &self.playing)
+    }
+
+    /// If mpv is starting or playing, and should be checked on often.
+    pub fn busy(&self) -> bool {
+        (self.child.is_some() && self.stream.is_none()) || self.current.is_some()
+    }
+
+    fn command(&mut self, args: json::Value) {
+        let line = json::json!({ "command": args }).to_string();
+        let sent = match &mut self.stream {
+            Some(s) => writeln!(s, "{}", line).is_ok(),
+            None => false,
+        };
+        if !sent {
+            self.pending.push(line);
+        }
+    }
+
+    /// Starts mpv if it isn't up.
+    fn start(&mut self) {
+        if self.stream.is_some() || self.child.is_some() || self.connect() {
+            return;
+        }
+        let _ = fs::remove_file(&self.sock);
+        let child = Command::new(&self.cmd.name)
+            .args(&self.cmd.args)
+            .arg("--idle=yes")
+            .arg("--no-terminal")
+            .arg(format!("--input-ipc-server={}", self.sock.display()))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        match child {
+            Ok(c) => self.child = Some(c),
+            Err(e) => log::warn!("could not start {}: {}", self.cmd, e),
+        }
+    }
+
+    /// Connects to the socket, gives back if it did.
+    fn connect(&mut self) -> bool {
+        let Ok(mut stream) = UnixStream::connect(&self.sock) else {
+            return false;
+        };
+        let Ok(read) = stream.try_clone() else {
+            return false;
+        };
+        let mut lines: Vec<String> = (PROPERTIES.iter().enumerate())
+            .map(|(i, p)| json::json!({ "command": ["observe_property", i + 1, p] }).to_string())
+            .collect();
+        lines.append(&mut self.pending);
+        for l in lines {
+            if writeln!(stream, "{}", l).is_err() {
+                return false;
+            }
+        }
+
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(read).lines() {
+                let Ok(line) = line else { break };
+                match json::from_str(&line) {
+                    Ok(v) => {
+                        if tx.send(v).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => log::debug!("mpv sent {:?}: {}", line, e),
+                }
+            }
+        });
+        self.stream = Some(stream);
+        self.events = Some(rx);
+        true
+    }
+
+    /// Reads what mpv sent, gives back the links that were played to the end.
+    pub fn tick(&mut self) -> Vec<(usize, Link)> {
+        if self.stream.is_none() && (self.child.is_some() || !self.pending.is_empty()) {
+            self.connect();
+        }
+        if let Some(c) = &mut self.child {
+            if let Ok(Some(status)) = c.try_wait() {
+                log::info!("mpv exited with {}", status);
+                self.child = None;
+                self.stream = None;
+            }
+        }
+
+        let mut events = Vec::new();
+        let mut gone = false;
+        if let Some(rx) = &self.events {
+            loop {
+                match rx.try_recv() {
+                    Ok(e) => events.push(e),
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => {
+                        gone = true;
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut played = Vec::new();
+        for e in events {
+            self.event(&e, &mut played);
+        }
+        if gone || (self.stream.is_none() && self.events.is_some()) {
+            self.stream = None;
+            self.events = None;
+            self.queue.clear();
+            self.current = None;
+        }
+        played
+    }
+
+    fn event(&mut self, e: &json::Value, played: &mut Vec<(usize, Link)>) {
+        match e["event"].as_str() {
+            Some("property-change") => {
+                let data = &e["data"];
+                match e["name"].as_str() {
+                    Some("media-title") => {
+                        self.playing.title = data.as_str().unwrap_or_default().to_owned()
+                    }
+                    Some("time-pos") => self.playing.pos = data.as_f64().unwrap_or(0.0),
+                    Some("duration") => self.playing.duration = data.as_f64().unwrap_or(0.0),
+                    Some("pause") => self.playing.paused = data.as_bool().unwrap_or(false),
+                    Some("path") => {
+                        let path = data.as_str().unwrap_or_default();
+                        self.current = self.queue.iter().position(|q| q.what == path);
+                    }
+                    _ => {}
+                }
+            }
+            Some("end-file") => {
+                if let Some(i) = self.current.take() {
+                    let q = self.queue.remove(i);
+                    if e["reason"].as_str() == Some("eof") {
+                        played.push((q.tabb, q.link));
+                    }
+                }
+                self.playing = Playing::default();
+            }
+            Some("idle") => {
+                self.queue.clear();
+                self.current = None;
+            }
+            _ => {}
+        }
+        if let Some(i) = self.current {
+            self.playing.next = self.queue.len() - i - 1;
+        }
+    }
+}
+
+impl Drop for Mpv {
+    fn drop(&mut self) {
+        // mpv is asked to quit when it can be, and is killed when it can't or
+        // doesn't in time
+        let asked = match self.stream.take() {
+            Some(mut s) => {
+                let sent = writeln!(s, "{}", json::json!({ "command": ["quit"] })).is_ok();
+                let _ = s.shutdown(Shutdown::Both);
+                sent
+            }
+            None => false,
+        };
+        if let Some(mut c) = self.child.take() {
+            let deadline = Instant::now() + QUIT_TIMEOUT;
+            while asked && Instant::now() < deadline {
+                match c.try_wait() {
+                    Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+                    _ => break,
+                }
+            }
+            if !matches!(c.try_wait(), Ok(Some(_))) {
+                let _ = c.kill();
+            }
+            let _ = c.wait();
+        }
+        let _ = fs::remove_file(&self.sock);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixListener;
+    use std::time::{Duration, Instant};
+
+    use super::{Mpv, Playing};
+    use crate::state::{Link, OpenCommand};
+
+    #[test]
+    fn controlling() {
+        let dir = tempfile::tempdir().unwrap();
+        let sock = dir.path().join("mpv.sock");
+        let listener = UnixListener::bind(&sock).unwrap();
+
+        // a fake mpv that plays along
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut out = stream.try_clone().unwrap();
+            let mut got = Vec::new();
+            for line in BufReader::new(stream).lines() {
+                let line = line.unwrap();
+                let reply = if line.contains("loadfile") {
+                    concat!(
+                        r#"{"event":"property-change","id":5,"name":"path","data":"/a.mp3"}"#,
+                        "\n",
+                        r#"{"event":"property-change","id":1,"name":"media-title","data":"A"}"#,
+                        "\n",
+                        r#"{"event":"property-change","id":2,"name":"time-pos","data":61.5}"#,
+                        "\n",
+                        r#"{"event":"property-change","id":3,"name":"duration","data":120.0}"#,
+                        "\n",
+                    )
+                } else if line.contains("seek") {
+                    "{\"event\":\"end-file\",\"reason\":\"eof\"}\n"
+                } else {
+                    ""
+                };
+                got.push(line);
+                out.write_all(reply.as_bytes()).unwrap();
+            }
+            got
+        });
+
+        let link = Link {
+            name: "a".into(),
+            file: Some("/a.mp3".into()),
+            ..Default::default()
+        };
+        let mut mpv = Mpv::new(sock, OpenCommand::new(&["mpv"]).unwrap());
+        mpv.play(1, link.clone(), "/a.mp3".into(), true);
+        mpv.play(1, Link::default(), "/b.mp3".into(), true);
+
+        // ticks until it is done, gives back what was played meanwhile
+        let wait = |mpv: &mut Mpv, done: &dyn Fn(&Mpv) -> bool| {
+            let start = Instant::now();
+            let mut played = Vec::new();
+            while !done(mpv) {
+                assert!(
+                    start.elapsed() < Duration::from_secs(5),
+                    "mpv never answered"
+                );
+                std::thread::sleep(Duration::from_millis(5));
+                played.extend(mpv.tick());
+            }
+            played
+        };
+        wait(&mut mpv, &|m| m.playing().is_some_and(|p| p.duration > 0.0));
+        assert_eq!(
+            mpv.playing(),
+            Some(&Playing {
+                title: "A".into(),
+                pos: 61.5,
+                duration: 120.0,
+                paused: false,
+                next: 1,
+            })
+        );
+
+        mpv.pause();
+        mpv.seek(-10);
+        let played = wait(&mut mpv, &|m| m.playing().is_none());
+        assert_eq!(played, [(1, link)]);
+
+        drop(mpv);
+        let got = server.join().unwrap();
+        assert_eq!(
+            got,
+            [
+                r#"{"command":["observe_property",1,"media-title"]}"#,
+                r#"{"command":["observe_property",2,"time-pos"]}"#,
+                r#"{"command":["observe_property",3,"duration"]}"#,
+                r#"{"command":["observe_property",4,"pause"]}"#,
+                r#"{"command":["observe_property",5,"path"]}"#,
+                r#"{"command":["loadfile","/a.mp3","append-play"]}"#,
+                r#"{"command":["loadfile","/b.mp3","append-play"]}"#,
+                r#"{"command":["cycle","pause"]}"#,
+                r#"{"command":["seek",-10,"relative"]}"#,
+                r#"{"command":["quit"]}"#,
+            ]
+        );
+    }
+}
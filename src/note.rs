@@ -0,0 +1,117 @@
+//! Markdown notes kept as files in a vault directory.
+//!
+//! Links and tabs remember the path of their note in the vault, so the vault
+//! can be moved and the notes can be named however the user likes.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::prelude::*;
+
+/// Notes bigger than this are cut off when read.
+const MAX_SIZE: u64 = 256 * 1024;
+
+/// Where the note is in the vault.
+pub fn path(vault: &Path, note: &str) -> PathBuf {
+    vault.join(note)
+}
+
+/// Reads the note, `None` if it isn't there.
+pub fn read(vault: &Path, note: &str) -> Option<String> {
+    let f = fs::File::open(path(vault, note)).ok()?;
+    let mut buf = Vec::new();
+    io::Read::read_to_end(&mut io::Read::take(f, MAX_SIZE), &mut buf).ok()?;
+    // the cut can land inside a character
+    let whole = match std::str::from_utf8(&buf) {
+        Err(e) if e.error_len().is_none() => &buf[..e.valid_up_to()],
+        _ => &buf[..],
+    };
+    Some(String::from_utf8_lossy(whole).into_owned())
+}
+
+/// Notes that were read, kept until their file changes.
+#[derive(Debug, Default)]
+pub struct Notes {
+    read: HashMap<PathBuf, ((Option<SystemTime>, u64), String)>,
+}
+
+impl Notes {
+    /// Reads the note unless it was read before and hasn't changed.
+    pub fn get(&mut self, vault: &Path, note: &str) -> Option<String> {
+        let path = path(vault, note);
+        let meta = fs::metadata(&path).ok()?;
+        let key = (meta.modified().ok(), meta.len());
+        if let Some((k, text)) = self.read.get(&path) {
+            if *k == key {
+                return Some(text.clone());
+            }
+        }
+        let text = read(vault, note)?;
+        self.read.insert(path, (key, text.clone()));
+        Some(text)
+    }
+}
+
+/// Creates a note starting with the title in the folder of the vault and
+/// gives back its path in the vault.
+pub fn create(vault: &Path, folder: &str, title: &str) -> io::Result<String> {
+    let dir = slug(folder);
+    fs::create_dir_all(vault.join(&dir))?;
+
+    let base = slug(title);
+    let mut name = format!("{}/{}.md", dir, base);
+    let mut n = 1;
+    while vault.join(&name).exists() {
+        n += 1;
+        name = format!("{}/{}-{}.md", dir, base, n);
+    }
+    fs::write(vault.join(&name), format!("# {}\n\n", title))?;
+    Ok(name)
+}
+
+/// A file name made of the letters and numbers of the text.
+fn slug(text: &str) -> String {
+    let mut s = String::new();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            s.push(c);
+        } else if !s.is_empty() && !s.ends_with('-') {
+            s.push('-');
+        }
+    }
+    let s = s.trim_end_matches('-');
+    if s.is_empty() {
+        "note".to_owned()
+    } else {
+        s.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{create, read, Notes, MAX_SIZE};
+    use crate::prelude::*;
+
+    #[test]
+    fn notes() {
+        let vault = tempfile::tempdir().unwrap();
+        let a = create(vault.path(), "My Music", "Song: Live!").unwrap();
+        assert_eq!(a, "my-music/song-live.md");
+        let b = create(vault.path(), "My Music", "song live").unwrap();
+        assert_eq!(b, "my-music/song-live-2.md");
+        assert_eq!(read(vault.path(), &a).unwrap(), "# Song: Live!\n\n");
+        assert_eq!(read(vault.path(), "gone.md"), None);
+
+        let mut notes = Notes::default();
+        assert_eq!(notes.get(vault.path(), &a).unwrap(), "# Song: Live!\n\n");
+        fs::write(vault.path().join(&a), "# Song\n").unwrap();
+        assert_eq!(notes.get(vault.path(), &a).unwrap(), "# Song\n");
+
+        // a big note is cut before the character that doesn't fit
+        let mut big = "a".repeat(MAX_SIZE as usize - 1);
+        big.push('é');
+        fs::write(vault.path().join("big.md"), &big).unwrap();
+        assert_eq!(read(vault.path(), "big.md").unwrap(), big[..big.len() - 2]);
+    }
+}
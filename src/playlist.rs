@@ -0,0 +1,355 @@
+//! Tabs as playlists, written to and read from M3U8 and XSPF files.
+
+use std::io::Write;
+use std::path::Path;
+
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+
+use crate::prelude::*;
+use crate::state::Link;
+
+/// What is played for the link: its file, or else its media url.
+fn target(link: &Link) -> Option<&str> {
+    match &link.file {
+        Some(f) => Some(f.as_str()),
+        None => Some(link.media()).filter(|m| !m.is_empty()),
+    }
+}
+
+/// Writes the links as an extended M3U playlist.
+pub fn write_m3u(w: &mut impl Write, name: &str, links: &[Link]) -> io::Result<()> {
+    writeln!(w, "#EXTM3U")?;
+    writeln!(w, "#PLAYLIST:{}", name)?;
+    for l in links {
+        let Some(target) = target(l) else {
+            continue;
+        };
+        // players show the title as `artist - title`, the artist is also
+        // kept in an attribute so the title can be split again
+        let secs = if l.duration > 0 {
+            l.duration as i64
+        } else {
+            -1
+        };
+        match &l.artist {
+            Some(a) => {
+                let attr = a.replace('"', "'");
+                writeln!(w, "#EXTINF:{} artist=\"{}\",{} - {}", secs, attr, a, l.name)?
+            }
+            None => writeln!(w, "#EXTINF:{},{}", secs, l.name)?,
+        }
+        writeln!(w, "{}", target)?;
+    }
+    Ok(())
+}
+
+/// Writes the links as an XSPF playlist. Files are given as `file://` urls
+/// and a file's link goes in its `info`.
+pub fn write_xspf(w: &mut impl Write, name: &str, links: &[Link]) -> io::Result<()> {
+    writeln!(w, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        w,
+        "<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">"
+    )?;
+    writeln!(w, "  <title>{}</title>", escape(name))?;
+    writeln!(w, "  <trackList>")?;
+    for l in links {
+        let Some(target) = target(l) else {
+            continue;
+        };
+        let location = match &l.file {
+            Some(f) => url::Url::from_file_path(f).map_or_else(|_| f.clone(), String::from),
+            None => target.to_owned(),
+        };
+        let tag = |t: &str, v: &str| format!("      <{}>{}</{}>\n", t, escape(v), t);
+        let mut track = tag("location", &location);
+        track.push_str(&tag("title", &l.name));
+        if let Some(a) = &l.artist {
+            track.push_str(&tag("creator", a));
+        }
+        if let Some(d) = &l.desc {
+            track.push_str(&tag("annotation", d));
+        }
+        if l.file.is_some() && !l.link.is_empty() {
+            track.push_str(&tag("info", &l.link));
+        }
+        if l.duration > 0 {
+            track.push_str(&tag("duration", &(l.duration * 1000).to_string()));
+        }
+        write!(w, "    <track>\n{}    </track>\n", track)?;
+    }
+    writeln!(w, "  </trackList>")?;
+    writeln!(w, "</playlist>")
+}
+
+/// Reads the playlist file, an XSPF or an M3U, into the name of the tab for
+/// it and its links. Relative paths are found from the playlist's directory.
+pub fn read(path: &Path, text: &str) -> std::result::Result<(String, Vec<Link>), String> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let dir = std::path::absolute(dir).map_err(|e| e.to_string())?;
+    let xspf = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("xspf"))
+        || text.trim_start().starts_with('<');
+    let (title, links) = if xspf {
+        read_xspf(text, &dir)?
+    } else {
+        read_m3u(text, &dir)
+    };
+    if links.is_empty() {
+        return Err("no tracks were found".into());
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let title = title.filter(|t| !t.trim().is_empty());
+    Ok((title.unwrap_or_else(|| stem.into_owned()), links))
+}
+
+/// Makes a link to the location, a url or a path.
+fn track(location: &str, dir: &Path) -> Link {
+    let file = match url::Url::parse(location) {
+        Ok(u) if u.scheme() == "file" => u.to_file_path().ok(),
+        // a drive letter is not a scheme
+        Ok(u) if u.scheme().len() > 1 => None,
+        _ => Some(dir.join(location)),
+    };
+    match file {
+        Some(f) => Link {
+            name: f
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            file: Some(f.to_string_lossy().into_owned()),
+            ..Default::default()
+        },
+        None => Link {
+            name: location.to_owned(),
+            link: location.to_owned(),
+            ..Default::default()
+        },
+    }
+}
+
+/// What an `#EXTINF` line says about the track after it.
+#[derive(Debug, Default)]
+struct Info {
+    secs: u64,
+    artist: Option<String>,
+    name: String,
+}
+
+impl Info {
+    /// Reads what is after `#EXTINF:`. Attributes can come after the
+    /// duration, the title is after the first comma outside of their quotes.
+    fn parse(rest: &str) -> Info {
+        let mut quoted = false;
+        let comma = rest.char_indices().find(|&(_, c)| {
+            quoted ^= c == '"';
+            c == ',' && !quoted
+        });
+        let (head, name) = match comma {
+            Some((i, _)) => (&rest[..i], rest[i + 1..].trim()),
+            None => (rest, ""),
+        };
+        let secs = head.split_whitespace().next().unwrap_or_default();
+        let artist = head
+            .split_once("artist=\"")
+            .and_then(|(_, a)| a.split_once('"'))
+            .map(|(a, _)| a.to_owned());
+        // the title only has the artist in front when it was written so
+        let name = match &artist {
+            Some(a) => name.strip_prefix(&format!("{} - ", a)).unwrap_or(name),
+            None => name,
+        };
+        Info {
+            secs: secs.parse().unwrap_or(0),
+            artist,
+            name: name.to_owned(),
+        }
+    }
+}
+
+fn read_m3u(text: &str, dir: &Path) -> (Option<String>, Vec<Link>) {
+    let mut title = None;
+    let mut links = Vec::new();
+    // the `#EXTINF` line goes with the track after it
+    let mut info: Option<Info> = None;
+    for line in text.lines().map(str::trim) {
+        if let Some(rest) = line.strip_prefix("#EXTINF:") {
+            info = Some(Info::parse(rest));
+        } else if let Some(name) = line.strip_prefix("#PLAYLIST:") {
+            title = Some(name.trim().to_owned());
+        } else if !line.is_empty() && !line.starts_with('#') {
+            let mut link = track(line.trim_start_matches('\u{feff}'), dir);
+            if let Some(info) = info.take() {
+                link.duration = info.secs;
+                link.artist = info.artist;
+                if !info.name.is_empty() {
+                    link.name = info.name;
+                }
+            }
+            links.push(link);
+        }
+    }
+    (title, links)
+}
+
+fn read_xspf(text: &str, dir: &Path) -> std::result::Result<(Option<String>, Vec<Link>), String> {
+    let base = url::Url::from_directory_path(dir).ok();
+    let mut reader = quick_xml::Reader::from_str(text);
+    let mut title = None;
+    let mut links = Vec::new();
+    // the fields of the track being read, by the element they were in
+    let mut fields: Option<Vec<(String, String)>> = None;
+    let mut open: Vec<String> = Vec::new();
+    let mut value = String::new();
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(e) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                if name == "track" {
+                    fields = Some(Vec::new());
+                }
+                open.push(name);
+                value.clear();
+            }
+            Event::Text(t) => value.push_str(&t.decode().map_err(|e| e.to_string())?),
+            Event::CData(t) => value.push_str(&t.decode().map_err(|e| e.to_string())?),
+            Event::GeneralRef(r) => {
+                let name = r.decode().map_err(|e| e.to_string())?;
+                match r.resolve_char_ref() {
+                    Ok(Some(c)) => value.push(c),
+                    _ => value.push_str(
+                        quick_xml::escape::resolve_predefined_entity(&name).unwrap_or_default(),
+                    ),
+                }
+            }
+            Event::End(_) => {
+                let Some(name) = open.pop() else {
+                    continue;
+                };
+                let v = value.trim().to_owned();
+                value.clear();
+                match (name.as_str(), &mut fields, open.last().map(String::as_str)) {
+                    ("track", Some(f), _) => {
+                        let get = |k: &str| {
+                            (f.iter())
+                                .find(|(key, v)| key == k && !v.is_empty())
+                                .map(|(_, v)| v.clone())
+                        };
+                        let Some(location) = get("location") else {
+                            fields = None;
+                            continue;
+                        };
+                        // locations are urls, relative ones are from the
+                        // playlist's directory
+                        let location = match base.as_ref().and_then(|b| b.join(&location).ok()) {
+                            Some(u) => u.to_string(),
+                            None => location,
+                        };
+                        let mut link = track(&location, dir);
+                        if let Some(t) = get("title") {
+                            link.name = t;
+                        }
+                        link.artist = get("creator");
+                        link.desc = get("annotation");
+                        if let (Some(info), Some(_)) = (get("info"), &link.file) {
+                            link.link = info;
+                        }
+                        link.duration = get("duration")
+                            .and_then(|d| d.parse::<u64>().ok())
+                            .map_or(0, |ms| ms / 1000);
+                        links.push(link);
+                        fields = None;
+                    }
+                    (_, Some(f), Some("track")) => f.push((name, v)),
+                    ("title", None, Some("playlist")) => title = Some(v),
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok((title, links))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{read, write_m3u, write_xspf};
+    use crate::state::Link;
+
+    #[test]
+    fn playlists() {
+        let links = [
+            Link {
+                name: "Song & Dance".into(),
+                link: "https://band.example/song".into(),
+                file: Some("/music/a b.flac".into()),
+                artist: Some("Band".into()),
+                duration: 125,
+                ..Default::default()
+            },
+            Link {
+                name: "Radio".into(),
+                link: "http://radio.example/live".into(),
+                ..Default::default()
+            },
+            Link {
+                name: "page".into(),
+                link: String::new(),
+                ..Default::default()
+            },
+        ];
+
+        let mut m3u = Vec::new();
+        write_m3u(&mut m3u, "mix", &links).unwrap();
+        let m3u = String::from_utf8(m3u).unwrap();
+        assert_eq!(
+            m3u,
+            "#EXTM3U\n#PLAYLIST:mix\n\
+             #EXTINF:125 artist=\"Band\",Band - Song & Dance\n/music/a b.flac\n\
+             #EXTINF:-1,Radio\nhttp://radio.example/live\n"
+        );
+        let (name, back) = read(Path::new("/lists/mix.m3u8"), &m3u).unwrap();
+        assert_eq!(name, "mix");
+        assert_eq!(back[0].file.as_deref(), Some("/music/a b.flac"));
+        assert_eq!(back[0].artist.as_deref(), Some("Band"));
+        assert_eq!(back[0].name, "Song & Dance");
+        assert_eq!(back[0].duration, 125);
+        assert_eq!(back[1].link, "http://radio.example/live");
+        assert_eq!(back.len(), 2);
+
+        // titles are kept whole unless the artist was written apart, and
+        // commas in attributes are not the one before the title
+        let m3u = "#EXTINF:-1 tvg-name=\"A, B\",AC - DC Live\nhttp://radio.example/ac\n";
+        let (_, back) = read(Path::new("/lists/radio.m3u"), m3u).unwrap();
+        assert_eq!(back[0].name, "AC - DC Live");
+        assert_eq!(back[0].artist, None);
+
+        let mut xspf = Vec::new();
+        write_xspf(&mut xspf, "mix", &links).unwrap();
+        let xspf = String::from_utf8(xspf).unwrap();
+        assert!(xspf.contains("<location>file:///music/a%20b.flac</location>"));
+        assert!(xspf.contains("<title>Song &amp; Dance</title>"));
+        let (name, back) = read(Path::new("/lists/mix.xspf"), &xspf).unwrap();
+        assert_eq!(name, "mix");
+        assert_eq!(back[..2], links[..2]);
+
+        // relative paths are from where the playlist is
+        let (name, back) = read(Path::new("/lists/road trip.m3u"), "a.mp3\n../b.mp3\n").unwrap();
+        assert_eq!(name, "road trip");
+        assert_eq!(back[0].file.as_deref(), Some("/lists/a.mp3"));
+        assert_eq!(back[0].name, "a");
+        assert_eq!(back[1].file.as_deref(), Some("/lists/../b.mp3"));
+        let xspf = "<playlist><trackList><track><location>sub/c%20d.ogg</location>\
+                    </track></trackList></playlist>";
+        let (_, back) = read(Path::new("/lists/x.xspf"), xspf).unwrap();
+        assert_eq!(back[0].file.as_deref(), Some("/lists/sub/c d.ogg"));
+        assert_eq!(back[0].name, "c d");
+        assert!(read(Path::new("/lists/x.m3u"), "#EXTM3U\n").is_err());
+    }
+}
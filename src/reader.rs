@@ -0,0 +1,504 @@
+//! Readable copies of web pages.
+//!
+//! The page is fetched and the part holding the article is picked out the
+//! way readability does it: blocks of text score their parents by how long
+//! they are, and the parent with the best score that isn't mostly links wins.
+//! The article is kept as Markdown in a directory next to the links.
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use scraper::{ElementRef, Html, Node, Selector};
+
+use crate::prelude::*;
+use crate::state::Link;
+
+/// Pages are only read this far.
+const MAX_PAGE: u64 = 4 * 1024 * 1024;
+
+/// Classes and ids of blocks that hold the article, or that don't.
+const GOOD: &[&str] = &[
+    "article", "body", "content", "entry", "main", "page", "post", "text", "blog", "story",
+];
+const BAD: &[&str] = &[
+    "comment", "meta", "footer", "footnote", "sidebar", "widget", "nav", "menu", "share", "social",
+    "promo", "related", "banner", "combx", "popup", "sponsor", "masthead",
+];
+/// Elements that are never part of the article.
+const SKIP: &[&str] = &[
+    "script", "style", "noscript", "nav", "aside", "footer", "form", "button", "iframe", "svg",
+    "input", "select", "textarea", "template", "head",
+];
+
+/// The directory copies are kept in for the links saved at the path.
+pub fn dir(links: &Path) -> PathBuf {
+    links.with_extension("reader")
+}
+
+/// The file the copy of the link is saved as, named after a hash of its url.
+fn file_name(link: &Link) -> String {
+    let mut h = DefaultHasher::new();
+    link.link.hash(&mut h);
+    format!("{:016x}.md", h.finish())
+}
+
+/// The saved copy in the directory, if it is there.
+pub fn read(dir: &Path, name: &str) -> Option<String> {
+    fs::read_to_string(dir.join(name)).ok()
+}
+
+/// A copy being saved in the background.
+#[derive(Debug)]
+struct Saving {
+    tabb: usize,
+    link: Link,
+    /// If it is opened once it is saved.
+    open: bool,
+    thread: JoinHandle<std::result::Result<String, String>>,
+}
+
+/// A copy that finished saving, with its file name in the directory.
+pub type Saved = (usize, Link, bool, std::result::Result<String, String>);
+
+/// Copies being saved.
+#[derive(Debug, Default)]
+pub struct Copies {
+    running: Vec<Saving>,
+}
+
+impl Copies {
+    /// Starts saving a copy of the link in the directory.
+    pub fn push(&mut self, tabb: usize, link: Link, open: bool, dir: PathBuf) {
+        let l = link.clone();
+        let thread = std::thread::spawn(move || save(&l, &dir));
+        self.running.push(Saving {
+            tabb,
+            link,
+            open,
+            thread,
+        });
+    }
+
+    /// The copies that finished since the last call.
+    pub fn tick(&mut self) -> Vec<Saved> {
+        let (done, running) = std::mem::take(&mut self.running)
+            .into_iter()
+            .partition(|s| s.thread.is_finished());
+        self.running = running;
+        done.into_iter()
+            .map(|s: Saving| {
+                let res = s
+                    .thread
+                    .join()
+                    .unwrap_or_else(|_| Err("saving panicked".to_owned()));
+                (s.tabb, s.link, s.open, res)
+            })
+            .collect()
+    }
+
+    pub fn busy(&self) -> bool {
+        !self.running.is_empty()
+    }
+}
+
+fn save(link: &Link, dir: &Path) -> std::result::Result<String, String> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(10))
+        .timeout_read(Duration::from_secs(30))
+        .build();
+    let resp = agent.get(&link.link).call().map_err(|e| e.to_string())?;
+    let mut body = Vec::new();
+    resp.into_reader()
+        .take(MAX_PAGE)
+        .read_to_end(&mut body)
+        .map_err(|e| e.to_string())?;
+
+    let md = extract(&String::from_utf8_lossy(&body), &link.link);
+    let name = file_name(link);
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    fs::write(dir.join(&name), md).map_err(|e| e.to_string())?;
+    Ok(name)
+}
+
+/// The article in the page as Markdown, under its title and url.
+pub fn extract(html: &str, url: &str) -> String {
+    let doc = Html::parse_document(html);
+    let title = title(&doc);
+    let article = article(&doc).unwrap_or_else(|| doc.root_element());
+
+    let mut md = Markdown {
+        base: url::Url::parse(url).ok(),
+        title: title.clone(),
+        ..Default::default()
+    };
+    md.children(article);
+
+    let mut out = String::new();
+    if let Some(t) = &title {
+        out.push_str(&format!("# {}\n\n", t));
+    }
+    out.push_str(&format!("<{}>\n\n", url));
+    out.push_str(md.out.trim());
+    out.push('\n');
+    out
+}
+
+fn select<'a>(doc: &'a Html, css: &str) -> impl Iterator<Item = ElementRef<'a>> {
+    let sel = Selector::parse(css).expect("selectors are valid");
+    doc.select(&sel).collect::<Vec<_>>().into_iter()
+}
+
+fn title(doc: &Html) -> Option<String> {
+    let og = select(doc, r#"meta[property="og:title"]"#).find_map(|m| m.attr("content"));
+    let title = og
+        .map(str::to_owned)
+        .or_else(|| select(doc, "title").next().map(|t| t.text().collect()))
+        .or_else(|| select(doc, "h1").next().map(|t| t.text().collect()))?;
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+/// How much the class and id of the element say it is the article.
+fn class_weight(el: ElementRef<'_>) -> f64 {
+    let names = format!(
+        "{} {}",
+        el.value().attr("class").unwrap_or(""),
+        el.value().id().unwrap_or("")
+    )
+    .to_lowercase();
+    let mut w = 0.0;
+    if GOOD.iter().any(|g| names.contains(g)) {
+        w += 25.0;
+    }
+    if BAD.iter().any(|b| names.contains(b)) {
+        w -= 25.0;
+    }
+    w
+}
+
+fn skipped(el: ElementRef<'_>) -> bool {
+    match el.value().name() {
+        "html" | "body" => false,
+        name => SKIP.contains(&name) || class_weight(el) < 0.0,
+    }
+}
+
+/// How much of the element's text is in links.
+fn link_density(el: ElementRef<'_>) -> f64 {
+    let len = |e: ElementRef<'_>| e.text().map(|t| t.trim().chars().count()).sum::<usize>();
+    let all = len(el);
+    if all == 0 {
+        return 0.0;
+    }
+    let links: usize = el
+        .descendent_elements()
+        .filter(|e| e.value().name() == "a")
+        .map(len)
+        .sum();
+    links as f64 / all as f64
+}
+
+/// The element that holds the article.
+fn article(doc: &Html) -> Option<ElementRef<'_>> {
+    let mut scores = HashMap::new();
+    for p in select(doc, "p, pre, td") {
+        let parents: Vec<ElementRef<'_>> = p.ancestors().filter_map(ElementRef::wrap).collect();
+        if parents.iter().any(|&e| skipped(e)) {
+            continue;
+        }
+        let text: String = p.text().collect();
+        let len = text.trim().chars().count();
+        if len < 25 {
+            continue;
+        }
+        let score = 1.0 + text.matches(',').count() as f64 + (len as f64 / 100.0).min(3.0);
+
+        for (el, share) in parents.iter().take(2).zip([1.0, 2.0]) {
+            let start = match el.value().name() {
+                "div" | "article" | "main" | "section" => 5.0,
+                "pre" | "td" | "blockquote" => 3.0,
+                "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.0,
+                _ => 0.0,
+            } + class_weight(*el);
+            *scores.entry(el.id()).or_insert(start) += score / share;
+        }
+    }
+
+    scores
+        .into_iter()
+        .filter_map(|(id, score)| {
+            let el = ElementRef::wrap(doc.tree.get(id)?)?;
+            Some((el, score * (1.0 - link_density(el))))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(el, _)| el)
+}
+
+/// Writes elements as Markdown.
+#[derive(Default)]
+struct Markdown {
+    out: String,
+    /// Pages the links are relative to.
+    base: Option<url::Url>,
+    /// Headings that only repeat the title are left out.
+    title: Option<String>,
+    /// What new lines start with in lists.
+    indent: String,
+    /// Numbers of the open lists, `None` for bullets.
+    lists: Vec<Option<usize>>,
+}
+
+impl Markdown {
+    /// Ends the block with a blank line.
+    fn gap(&mut self) {
+        let len = self.out.trim_end().len();
+        self.out.truncate(len);
+        if !self.out.is_empty() {
+            self.out.push_str("\n\n");
+        }
+    }
+
+    fn line(&mut self) {
+        let len = self.out.trim_end_matches(' ').len();
+        self.out.truncate(len);
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        let mut words = text.split_whitespace().peekable();
+        if words.peek().is_none() {
+            if !text.is_empty() && !self.out.ends_with([' ', '\n']) && !self.out.is_empty() {
+                self.out.push(' ');
+            }
+            return;
+        }
+        if text.starts_with(char::is_whitespace) && !self.out.ends_with([' ', '\n']) {
+            self.out.push(' ');
+        }
+        let mut first = true;
+        for w in words {
+            if !first {
+                self.out.push(' ');
+            }
+            first = false;
+            for c in w.chars() {
+                if "\\*_`[]".contains(c) {
+                    self.out.push('\\');
+                }
+                self.out.push(c);
+            }
+        }
+        if text.ends_with(char::is_whitespace) {
+            self.out.push(' ');
+        }
+    }
+
+    /// Writes the inside of the element between the marks, or nothing if it
+    /// is empty.
+    fn wrapped(&mut self, el: ElementRef<'_>, open: &str, close: &str) {
+        let start = self.out.len();
+        self.out.push_str(open);
+        let inner = self.out.len();
+        self.children(el);
+        if self.out[inner..].trim().is_empty() {
+            self.out.truncate(start);
+            return;
+        }
+        // marks have to touch the text they wrap
+        let trailing = self.out.len() - self.out.trim_end().len();
+        self.out.truncate(self.out.len() - trailing);
+        self.out.push_str(close);
+        if trailing > 0 {
+            self.out.push(' ');
+        }
+    }
+
+    fn link(&self, href: &str) -> Option<String> {
+        if href.starts_with('#') || href.starts_with("javascript:") {
+            return None;
+        }
+        match &self.base {
+            Some(b) => b.join(href).ok().map(String::from),
+            None => Some(href.to_owned()),
+        }
+    }
+
+    fn children(&mut self, el: ElementRef<'_>) {
+        for child in el.children() {
+            match child.value() {
+                Node::Text(t) => self.text(t),
+                Node::Element(_) => {
+                    let child = ElementRef::wrap(child).expect("it is an element");
+                    self.element(child);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn element(&mut self, el: ElementRef<'_>) {
+        // blocks that look like they aren't the article can still hold a
+        // part of it, unless they are mostly links
+        if SKIP.contains(&el.value().name()) || (skipped(el) && link_density(el) > 0.3) {
+            return;
+        }
+        let in_list = !self.lists.is_empty();
+        match el.value().name() {
+            h @ ("h1" | "h2" | "h3" | "h4" | "h5" | "h6") => {
+                let text: String = el.text().collect::<String>();
+                let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                if text.is_empty() || self.title.as_deref() == Some(text.as_str()) {
+                    return;
+                }
+                self.gap();
+                let level: usize = h[1..].parse().unwrap_or(1);
+                self.out.push_str(&"#".repeat(level));
+                self.out.push(' ');
+                self.text(&text);
+                self.gap();
+            }
+            "p" | "div" | "section" | "article" | "main" | "figure" | "header" | "table"
+                if !in_list =>
+            {
+                self.gap();
+                self.children(el);
+                self.gap();
+            }
+            "tr" => {
+                self.line();
+                let cells: Vec<String> = el
+                    .child_elements()
+                    .map(|c| c.text().collect::<Vec<_>>().join(" "))
+                    .map(|t| t.split_whitespace().collect::<Vec<_>>().join(" "))
+                    .collect();
+                self.text(&cells.join(" | "));
+                self.line();
+            }
+            "br" => self.out.push_str("  \n"),
+            "hr" => {
+                self.gap();
+                self.out.push_str("---");
+                self.gap();
+            }
+            "ul" | "ol" => {
+                if in_list {
+                    self.line();
+                } else {
+                    self.gap();
+                }
+                let start = el.value().attr("start").and_then(|s| s.parse().ok());
+                self.lists
+                    .push((el.value().name() == "ol").then(|| start.unwrap_or(1)));
+                self.children(el);
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.gap();
+                }
+            }
+            "li" => {
+                self.line();
+                let bullet = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "- ".to_owned(),
+                };
+                self.out.push_str(&self.indent.clone());
+                self.out.push_str(&bullet);
+                let before = self.indent.len();
+                self.indent.push_str(&" ".repeat(bullet.len()));
+                self.children(el);
+                self.indent.truncate(before);
+                self.line();
+            }
+            "blockquote" => {
+                let mut inner = Markdown {
+                    base: self.base.clone(),
+                    ..Default::default()
+                };
+                inner.children(el);
+                self.gap();
+                for l in inner.out.trim().lines() {
+                    self.out.push_str("> ");
+                    self.out.push_str(l);
+                    self.out.push('\n');
+                }
+                self.gap();
+            }
+            "pre" => {
+                self.gap();
+                let code: String = el.text().collect();
+                self.out.push_str("```\n");
+                self.out.push_str(code.trim_end());
+                self.out.push_str("\n```");
+                self.gap();
+            }
+            "code" | "kbd" | "samp" => {
+                let code: String = el.text().collect();
+                let code = code.trim();
+                if !code.is_empty() {
+                    let code = if code.contains('`') {
+                        format!("`` {} ``", code)
+                    } else {
+                        format!("`{}`", code)
+                    };
+                    self.out.push_str(&code);
+                }
+            }
+            "strong" | "b" => self.wrapped(el, "**", "**"),
+            "em" | "i" => self.wrapped(el, "*", "*"),
+            "del" | "s" | "strike" => self.wrapped(el, "~~", "~~"),
+            "a" => match el.value().attr("href").and_then(|h| self.link(h)) {
+                Some(url) => self.wrapped(el, "[", &format!("]({})", url)),
+                None => self.children(el),
+            },
+            "img" => {}
+            _ => self.children(el),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::extract;
+
+    #[test]
+    fn article() {
+        let html = r#"<html><head><title>A  Post</title></head><body>
+            <nav><a href="/">Home</a> <a href="/about">About</a></nav>
+            <div class="sidebar"><p>Subscribe to the newsletter, it is good, really good.</p></div>
+            <div class="post-content">
+              <h1>A Post</h1>
+              <p>The first paragraph has <b>bold</b> and <a href="/x">a link</a>, with commas, many of them.</p>
+              <h2>Part  two</h2>
+              <ul><li>one <i>item</i></li><li>two<ol><li>inner</li></ol></li></ul>
+              <p>Some <code>code</code> inline, and some_text with marks, enough to count here.</p>
+              <pre>fn main() {
+    println!();
+}</pre>
+              <blockquote><p>a quote that goes on</p></blockquote>
+            </div>
+            <div class="comments"><p>first!!! this comment is long enough to be counted, sadly.</p></div>
+            </body></html>"#;
+        let md = extract(html, "https://blog.com/posts/1");
+        assert_eq!(
+            md,
+            "# A Post\n\n\
+             <https://blog.com/posts/1>\n\n\
+             The first paragraph has **bold** and [a link](https://blog.com/x), with commas, many of them.\n\n\
+             ## Part two\n\n\
+             - one *item*\n\
+             - two\n  1. inner\n\n\
+             Some `code` inline, and some\\_text with marks, enough to count here.\n\n\
+             ```\nfn main() {\n    println!();\n}\n```\n\n\
+             > a quote that goes on\n"
+        );
+    }
+}
@@ -0,0 +1,481 @@
+//! A full-text index over the links, their notes and the pages saved for
+//! them.
+//!
+//! The index is kept next to the links and brought up to date before each
+//! search. Every link and tab note is a document, named by a hash of what it
+//! is made from, so only the ones that changed are indexed again.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+use crate::Luma;
+
+/// Indexes written by other versions are built again.
+const VERSION: u32 = 1;
+/// Positions skipped between fields so phrases don't run across them.
+const GAP: u32 = 8;
+/// The most words a prefix is looked for as.
+const MAX_EXPANSIONS: usize = 200;
+/// BM25 parameters.
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+/// Where the index is kept for the links saved at the path.
+pub fn path(links: &Path) -> PathBuf {
+    links.with_extension("index")
+}
+
+/// What a document is the text of.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Target {
+    /// The link in the tab with the name and url.
+    Link {
+        tab: String,
+        name: String,
+        link: String,
+    },
+    /// The note of the tab.
+    Tab(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Doc {
+    target: Target,
+    /// Hash of everything the text is made from.
+    stamp: u64,
+    /// Words in the text.
+    len: u32,
+}
+
+/// Where a word is found in a document, and how much it counts there.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Posting {
+    doc: u32,
+    weight: f32,
+    pos: Vec<u32>,
+}
+
+/// A document that matched a search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub target: Target,
+    pub score: f32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Index {
+    version: u32,
+    /// Documents by id, `None` once they are removed.
+    docs: Vec<Option<Doc>>,
+    /// The documents each word is in.
+    terms: BTreeMap<String, Vec<Posting>>,
+}
+
+/// Where the text that isn't in the links is kept.
+#[derive(Debug, Clone, Copy)]
+pub struct Sources<'a> {
+    pub vault: &'a Path,
+    pub reader: &'a Path,
+}
+
+/// A document to index, its text is only read if it changed.
+struct Want<'a> {
+    target: Target,
+    stamp: u64,
+    link: Option<&'a crate::state::Link>,
+    note: Option<&'a str>,
+}
+
+impl Index {
+    /// Reads the index, an index that is missing or from another version is
+    /// empty.
+    pub fn load(path: &Path) -> Index {
+        fs::read(path)
+            .ok()
+            .and_then(|b| json::from_slice::<Index>(&b).ok())
+            .filter(|i| i.version == VERSION)
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp = path.with_extension("index.tmp");
+        fs::write(&tmp, json::to_vec(self)?)?;
+        fs::rename(tmp, path)
+    }
+
+    /// Indexes what changed since the last time, gives back if anything did.
+    pub fn update(&mut self, luma: &Luma, src: Sources<'_>) -> bool {
+        let mut want = Vec::new();
+        for (tab, links) in &luma.tabs {
+            for l in links {
+                let mut h = DefaultHasher::new();
+                (tab, l).hash(&mut h);
+                let files = [
+                    l.note.as_ref().map(|n| crate::note::path(src.vault, n)),
+                    l.reader.as_ref().map(|r| src.reader.join(r)),
+                    l.archive.as_ref().map(PathBuf::from),
+                ];
+                files
+                    .iter()
+                    .flatten()
+                    .for_each(|p| modified(p).hash(&mut h));
+                want.push(Want {
+                    target: Target::Link {
+                        tab: tab.clone(),
+                        name: l.name.clone(),
+                        link: l.link.clone(),
+                    },
+                    stamp: h.finish(),
+                    link: Some(l),
+                    note: None,
+                });
+            }
+        }
+        for (tab, note) in &luma.notes {
+            let mut h = DefaultHasher::new();
+            (tab, note, modified(&crate::note::path(src.vault, note))).hash(&mut h);
+            want.push(Want {
+                target: Target::Tab(tab.clone()),
+                stamp: h.finish(),
+                link: None,
+                note: Some(note),
+            });
+        }
+
+        let stamps: HashSet<u64> = want.iter().map(|w| w.stamp).collect();
+        let gone: HashSet<u32> = (self.docs.iter().enumerate())
+            .filter_map(|(i, d)| d.as_ref().map(|d| (i, d)))
+            .filter(|(_, d)| !stamps.contains(&d.stamp))
+            .map(|(i, _)| i as u32)
+            .collect();
+        let have: HashSet<u64> = (self.docs.iter().flatten())
+            .map(|d| d.stamp)
+            .filter(|s| stamps.contains(s))
+            .collect();
+        let new: Vec<&Want<'_>> = {
+            let mut seen = HashSet::new();
+            (want.iter())
+                .filter(|w| !have.contains(&w.stamp) && seen.insert(w.stamp))
+                .collect()
+        };
+        if gone.is_empty() && new.is_empty() && self.version == VERSION {
+            return false;
+        }
+
+        self.version = VERSION;
+        if !gone.is_empty() {
+            for id in &gone {
+                self.docs[*id as usize] = None;
+            }
+            // start again when most ids are unused
+            if self.docs.iter().filter(|d| d.is_none()).count() > self.docs.len() / 2 {
+                *self = Index {
+                    version: VERSION,
+                    ..Index::default()
+                };
+                self.update(luma, src);
+                return true;
+            }
+            for postings in self.terms.values_mut() {
+                postings.retain(|p| !gone.contains(&p.doc));
+            }
+            self.terms.retain(|_, p| !p.is_empty());
+        }
+
+        for w in new {
+            let fields = match (w.link, w.note) {
+                (Some(l), _) => link_text(l, src),
+                (None, Some(n)) => vec![(1.0, crate::note::read(src.vault, n).unwrap_or_default())],
+                (None, None) => Vec::new(),
+            };
+            self.add(w.target.clone(), w.stamp, &fields);
+        }
+        true
+    }
+
+    fn add(&mut self, target: Target, stamp: u64, fields: &[(f32, String)]) {
+        let id = self.docs.len() as u32;
+        let mut at = 0;
+        for (weight, text) in fields {
+            for word in words(text) {
+                let postings = self.terms.entry(word).or_default();
+                match postings.last_mut() {
+                    Some(p) if p.doc == id => {
+                        p.weight += weight;
+                        p.pos.push(at);
+                    }
+                    _ => postings.push(Posting {
+                        doc: id,
+                        weight: *weight,
+                        pos: vec![at],
+                    }),
+                }
+                at += 1;
+            }
+            at += GAP;
+        }
+        let len = at.saturating_sub(GAP * fields.len() as u32);
+        self.docs.push(Some(Doc { target, stamp, len }));
+    }
+
+    /// The documents with all of the query, best first. Words in quotes are
+    /// a phrase and a word ending in `*` is a prefix.
+    pub fn search(&self, query: &str) -> Vec<Hit> {
+        let clauses = parse(query);
+        if clauses.is_empty() {
+            return Vec::new();
+        }
+
+        let live: Vec<&Doc> = self.docs.iter().flatten().collect();
+        let n = live.len().max(1) as f32;
+        let avg = live.iter().map(|d| d.len as f32).sum::<f32>() / n;
+        let score = |postings: &[Posting], p: &Posting| {
+            let df = postings.len() as f32;
+            let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+            let len = self.docs[p.doc as usize]
+                .as_ref()
+                .map_or(0.0, |d| d.len as f32);
+            let norm = K1 * (1.0 - B + B * len / avg.max(1.0));
+            idf * p.weight * (K1 + 1.0) / (p.weight + norm)
+        };
+
+        let mut found: Option<HashMap<u32, f32>> = None;
+        for clause in clauses {
+            let mut scores: HashMap<u32, f32> = HashMap::new();
+            match clause {
+                Clause::Word(w) => {
+                    for p in self.terms.get(&w).into_iter().flatten() {
+                        scores.insert(p.doc, score(&self.terms[&w], p));
+                    }
+                }
+                Clause::Prefix(w) => {
+                    let terms = (self.terms.range(w.clone()..))
+                        .take_while(|(t, _)| t.starts_with(&w))
+                        .take(MAX_EXPANSIONS);
+                    for (_, postings) in terms {
+                        for p in postings {
+                            let s = scores.entry(p.doc).or_default();
+                            *s = s.max(score(postings, p));
+                        }
+                    }
+                }
+                Clause::Phrase(ws) => {
+                    let Some(lists) = (ws.iter())
+                        .map(|w| self.terms.get(w))
+                        .collect::<Option<Vec<_>>>()
+                    else {
+                        return Vec::new();
+                    };
+                    for p in lists[0] {
+                        let rest: Option<Vec<&Posting>> = (lists[1..].iter())
+                            .map(|l| l.iter().find(|q| q.doc == p.doc))
+                            .collect();
+                        let Some(rest) = rest else { continue };
+                        let follows = p.pos.iter().any(|&start| {
+                            (rest.iter().enumerate())
+                                .all(|(i, q)| q.pos.binary_search(&(start + i as u32 + 1)).is_ok())
+                        });
+                        if follows {
+                            let s = score(lists[0], p)
+                                + (rest.iter().zip(&lists[1..]))
+                                    .map(|(q, l)| score(l, q))
+                                    .sum::<f32>();
+                            scores.insert(p.doc, s);
+                        }
+                    }
+                }
+            }
+            found = Some(match found {
+                None => scores,
+                Some(f) => (f.into_iter())
+                    .filter_map(|(d, s)| scores.get(&d).map(|t| (d, s + t)))
+                    .collect(),
+            });
+        }
+
+        let mut hits: Vec<(u32, f32)> = found.unwrap_or_default().into_iter().collect();
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        hits.into_iter()
+            .filter_map(|(d, score)| {
+                let doc = self.docs[d as usize].as_ref()?;
+                Some(Hit {
+                    target: doc.target.clone(),
+                    score,
+                })
+            })
+            .collect()
+    }
+}
+
+/// The text of the link by how much it counts: its name most, then what
+/// describes it, then the rest.
+fn link_text(l: &crate::state::Link, src: Sources<'_>) -> Vec<(f32, String)> {
+    let mut fields = vec![(3.0, l.name.clone())];
+    fields.extend(l.desc.clone().map(|d| (2.0, d)));
+    fields.extend(l.artist.clone().map(|a| (2.0, a)));
+    fields.push((1.0, l.link.clone()));
+    fields.extend(l.file.clone().map(|f| (1.0, f)));
+    if let Some(n) = l
+        .note
+        .as_ref()
+        .and_then(|n| crate::note::read(src.vault, n))
+    {
+        fields.push((1.0, n));
+    }
+    if let Some(r) = l
+        .reader
+        .as_ref()
+        .and_then(|r| crate::reader::read(src.reader, r))
+    {
+        fields.push((1.0, r));
+    }
+    // a WARC is left out, its page is in the readable copy if there is one
+    let html = l.archive.as_ref().filter(|a| a.ends_with(".html"));
+    if let Some(html) = html.and_then(|a| fs::read_to_string(a).ok()) {
+        fields.push((1.0, crate::reader::extract(&html, &l.link)));
+    }
+    fields
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The words of the text in lower case.
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+}
+
+#[derive(Debug, PartialEq)]
+enum Clause {
+    Word(String),
+    Prefix(String),
+    Phrase(Vec<String>),
+}
+
+fn parse(query: &str) -> Vec<Clause> {
+    let phrase = |text: &str| {
+        let mut ws: Vec<String> = words(text).collect();
+        match ws.len() {
+            0 => None,
+            1 => ws.pop().map(Clause::Word),
+            _ => Some(Clause::Phrase(ws)),
+        }
+    };
+
+    let mut clauses = Vec::new();
+    // quotes take turns between phrases and words
+    for (i, part) in query.split('"').enumerate() {
+        if i % 2 == 1 {
+            clauses.extend(phrase(part));
+            continue;
+        }
+        for word in part.split_whitespace() {
+            match word.strip_suffix('*') {
+                Some(pre) => {
+                    let mut ws: Vec<String> = words(pre).collect();
+                    if let Some(last) = ws.pop() {
+                        clauses.extend(ws.into_iter().map(Clause::Word));
+                        clauses.push(Clause::Prefix(last));
+                    }
+                }
+                None => clauses.extend(phrase(word)),
+            }
+        }
+    }
+    clauses
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, Clause, Index, Sources, Target};
+    use crate::prelude::*;
+    use crate::state::Link;
+    use crate::Luma;
+
+    #[test]
+    fn searching() {
+        assert_eq!(
+            parse("\"Rust book\" prog* web"),
+            [
+                Clause::Phrase(vec!["rust".into(), "book".into()]),
+                Clause::Prefix("prog".into()),
+                Clause::Word("web".into()),
+            ]
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let vault = dir.path().join("vault");
+        fs::create_dir_all(&vault).unwrap();
+        fs::write(vault.join("n.md"), "notes on the borrow checker").unwrap();
+        let src = Sources {
+            vault: &vault,
+            reader: dir.path(),
+        };
+
+        let link = |name: &str, desc: &str| Link {
+            name: name.into(),
+            link: format!("https://example.com/{}", name.len()),
+            desc: (!desc.is_empty()).then(|| desc.into()),
+            ..Default::default()
+        };
+        let mut luma = Luma {
+            tabs: vec![(
+                "books".into(),
+                vec![
+                    link("The Rust Programming Language", "the book"),
+                    link("Programming Rust", "rust book from O'Reilly"),
+                    Link {
+                        note: Some("n.md".into()),
+                        ..link("Rustonomicon", "")
+                    },
+                ],
+            )],
+            ..Default::default()
+        };
+        luma.notes.insert("books".into(), "n.md".into());
+
+        let mut index = Index::default();
+        assert!(index.update(&luma, src));
+        assert!(!index.update(&luma, src));
+
+        let names = |index: &Index, q: &str| -> Vec<String> {
+            (index.search(q).into_iter())
+                .map(|h| match h.target {
+                    Target::Link { name, .. } => name,
+                    Target::Tab(t) => format!("tab {}", t),
+                })
+                .collect()
+        };
+        // the name counts the most
+        assert_eq!(
+            names(&index, "rust"),
+            ["Programming Rust", "The Rust Programming Language"]
+        );
+        assert_eq!(names(&index, "\"rust book\""), ["Programming Rust"]);
+        assert_eq!(names(&index, "\"book rust\""), Vec::<String>::new());
+        // the same note counts more in a shorter text
+        assert_eq!(names(&index, "borr*"), ["tab books", "Rustonomicon"]);
+        assert_eq!(names(&index, "rust* checker"), ["Rustonomicon"]);
+
+        // only the link that changed is indexed again
+        luma.tabs[0].1[0].desc = Some("covers borrowing".into());
+        assert!(index.update(&luma, src));
+        assert_eq!(index.docs.iter().flatten().count(), 4);
+        assert_eq!(names(&index, "covers"), ["The Rust Programming Language"]);
+        assert_eq!(names(&index, "\"the book\""), Vec::<String>::new());
+
+        let path = super::path(&dir.path().join("links.json"));
+        index.save(&path).unwrap();
+        let loaded = Index::load(&path);
+        assert_eq!(loaded.search("covers"), index.search("covers"));
+        assert_eq!(Index::load(&dir.path().join("none")).docs.len(), 0);
+    }
+}
@@ -1,25 +1,58 @@
+use std::collections::BTreeMap;
 use std::process::{Command, Stdio};
 
-use crate::{app::State, prelude::*};
+use crate::{app::State, import::ImportDir, prelude::*};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Luma {
     /// First element is the name second is the links
     pub tabs: Vec<(String, Vec<Link>)>,
+    /// Directories the files of tabs are imported from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dirs: Vec<ImportDir>,
+    /// The notes of tabs by tab name, as paths in the vault
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub notes: BTreeMap<String, String>,
+    /// The feeds tabs are subscribed to by tab name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub feeds: BTreeMap<String, Vec<crate::feed::Subscription>>,
 }
 impl Luma {
+    /// Renames the tab, along with the directories imported into it.
+    pub fn rename_tab(&mut self, tabb: usize, name: String) {
+        let Some(t) = self.tabs.get_mut(tabb) else {
+            return;
+        };
+        for d in self.dirs.iter_mut().filter(|d| d.tab == t.0) {
+            d.tab = name.clone();
+        }
+        if let Some(note) = self.notes.remove(&t.0) {
+            self.notes.insert(name.clone(), note);
+        }
+        if let Some(feeds) = self.feeds.remove(&t.0) {
+            self.feeds.insert(name.clone(), feeds);
+        }
+        t.0 = name;
+    }
+
+    /// Removes the tab and forgets the directories imported into it and the
+    /// feeds it is subscribed to. Its note is left in the vault.
+    pub fn remove_tab(&mut self, tabb: usize) {
+        if tabb >= self.tabs.len() {
+            return;
+        }
+        let (name, _) = self.tabs.remove(tabb);
+        self.dirs.retain(|d| d.tab != name);
+        self.notes.remove(&name);
+        self.feeds.remove(&name);
+    }
+
     pub fn get_selected(&self, state: &State) -> Option<&Link> {
         self.tabs
             .get(state.tabb)
             .and_then(|x| x.1.get(state.selected))
     }
-
-    pub fn get_mut_selected(&mut self, state: &State) -> Option<&mut Link> {
-        self.tabs
-            .get_mut(state.tabb)
-            .and_then(|x| x.1.get_mut(state.selected))
-    }
 }
 
 // #[derive(Default)]
@@ -29,7 +62,7 @@ impl Luma {
 //     pub unsaved_changes: bool,
 // }
 
-#[derive(Debug, Default, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Link {
     pub name: String,
     pub link: String,
@@ -45,6 +78,113 @@ pub struct Link {
     // #[serde(skip_serializing_if = "Option::is_none")]
     // #[serde(default)]
     pub color: Option<String>,
+    /// Path of its note in the vault
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// The file was gone the last time its directory was scanned
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub missing: bool,
+    /// File of its readable copy in the reader directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reader: Option<String>,
+    /// Line of the readable copy it was last read up to
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub read_at: usize,
+    /// Path of its latest snapshot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive: Option<String>,
+    /// When the latest snapshot was taken
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived: Option<String>,
+    /// The id of the feed entry it came from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guid: Option<String>,
+    /// When the feed entry was published
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published: Option<String>,
+    /// The feed entry hasn't been opened yet
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unread: bool,
+    /// Where the media of a podcast episode is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enclosure: Option<String>,
+    /// Seconds the episode lasts
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub duration: u64,
+    /// Bytes of the episode's media
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub size: u64,
+    /// The episode has been played
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub played: bool,
+}
+
+impl Link {
+    /// The url its media is at, the enclosure of an episode or else the link.
+    pub fn media(&self) -> &str {
+        self.enclosure.as_deref().unwrap_or(&self.link)
+    }
+}
+
+fn is_zero<T: Default + PartialEq>(n: &T) -> bool {
+    *n == T::default()
+}
+
+/// Checks a value that came back from the user for problems that would make it
+/// unusable.
+pub trait Validate {
+    /// Returns every problem found with the value. Empty when it is valid.
+    fn validate(&self) -> Vec<String>;
+}
+
+impl Validate for Link {
+    fn validate(&self) -> Vec<String> {
+        let mut errs = Vec::new();
+
+        if self.name.trim().is_empty() {
+            errs.push("name must not be empty".to_owned());
+        }
+
+        // links to local files don't need a url
+        if !(self.link.is_empty() && self.file.is_some()) {
+            if let Err(e) = url::Url::parse(&self.link) {
+                errs.push(format!("link {:?} is not a valid url: {}", self.link, e));
+            }
+        }
+
+        errs
+    }
+}
+
+impl Validate for String {
+    fn validate(&self) -> Vec<String> {
+        if self.trim().is_empty() {
+            vec!["name must not be empty".to_owned()]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+impl Validate for Luma {
+    fn validate(&self) -> Vec<String> {
+        let mut errs = Vec::new();
+        for (i, (name, links)) in self.tabs.iter().enumerate() {
+            errs.extend(
+                name.validate()
+                    .into_iter()
+                    .map(|e| format!("tab {}: {}", i + 1, e)),
+            );
+            for (j, l) in links.iter().enumerate() {
+                errs.extend(
+                    l.validate()
+                        .into_iter()
+                        .map(|e| format!("{} {}: {}", name, j + 1, e)),
+                );
+            }
+        }
+        errs
+    }
 }
 
 // impl Link {
@@ -60,10 +200,22 @@ pub struct Link {
 //     }
 // }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct OpenCommand {
-    pub name: &'static str,
-    pub args: &'static [&'static str],
+    pub name: String,
+    pub args: Vec<String>,
+    /// If the program needs a terminal to run in
+    pub term: bool,
+}
+
+impl fmt::Display for OpenCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)?;
+        for a in &self.args {
+            write!(f, " {}", a)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -76,38 +228,52 @@ impl fmt::Display for OpenCommandError {
 impl Context for OpenCommandError {}
 
 impl OpenCommand {
-    pub const fn new(name: &'static str, args: &'static [&'static str]) -> Self {
-        Self { name, args }
+    /// Makes a command from a program followed by its arguments. Returns
+    /// `None` if there is no program.
+    pub fn new<S: AsRef<str>>(argv: &[S]) -> Option<Self> {
+        let (name, args) = argv.split_first()?;
+        Some(Self {
+            name: name.as_ref().to_owned(),
+            args: args.iter().map(|a| a.as_ref().to_owned()).collect(),
+            term: false,
+        })
+    }
+
+    /// Makes the command run in the terminal pane.
+    pub fn in_term(self) -> Self {
+        Self { term: true, ..self }
     }
 
     pub fn run(&self, name: &str) -> Result<std::process::Child, OpenCommandError> {
         // it isn't really out concern right now how the process went
+        self.command()
+            .arg(name)
+            .stdout(Stdio::null())
+            .spawn()
+            .change_context(OpenCommandError)
+    }
 
-        let mut child = Command::new(self.name);
+    /// Runs the command without adding an argument and keeps what it writes
+    /// to stdout if `capture` is set.
+    pub fn exec(&self, capture: bool) -> Result<std::process::Child, OpenCommandError> {
+        let out = if capture {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        };
+        self.command()
+            .stdout(out)
+            .spawn()
+            .change_context(OpenCommandError)
+    }
 
+    fn command(&self) -> Command {
+        let mut child = Command::new(&self.name);
+        // no stdin so the process doesn't wait for input
         child
             .args(self.args.iter())
-            .arg(name)
             .stdin(Stdio::null())
-            .stdout(Stdio::null())
             .stderr(Stdio::null());
-
-        let mut c = child.spawn().change_context(OpenCommandError)?;
-        // drop stdin so process doesn't wait for input
-        let _ = c.stdout.take();
-        Ok(c)
-    }
-
-    pub fn spawn(&self, name: &str) -> Result<std::process::Child, OpenCommandError> {
-        // it isn't really out concern right now how the process went
-
-        let mut child = Command::new(self.name);
-
-        child.args(self.args.iter()).arg(name);
-
-        let mut c = child.spawn().change_context(OpenCommandError)?;
-        // drop stdin so process doesn't wait for input
-        let _ = c.stdout.take();
-        Ok(c)
+        child
     }
 }
@@ -1,98 +1,632 @@
-use std::{io::Write, path::PathBuf};
+//! Reads and writes the tags of audio files so they can be edited.
+//!
+//! FLAC and Ogg files keep vorbis comments and MP3 files keep ID3v2 frames.
+//! Either way the tags are edited as a map of lowercase names, the ones in
+//! [`FIELDS`] are always there so they can be filled in. Pictures are never
+//! shown and are written back as they were.
+
+use std::io::{Read, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
+use crate::state::{Link, Validate};
+
+/// The tags that are shown even when the file doesn't have them.
+pub const FIELDS: &[&str] = &[
+    "title", "artist", "album", "year", "tags", "rating", "comment", "language",
+];
+
+/// Vorbis comments that hold pictures rather than text.
+const PICTURE_KEYS: &[&str] = &["metadata_block_picture", "coverart"];
 
 #[derive(Debug)]
-pub enum Programs {
-    Editor(EditorData),
-    Tagger { editor: EditorData },
+pub enum TagError {
+    Read,
+    Write,
 }
+impl fmt::Display for TagError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagError::Read => f.write_str("could not read the tags"),
+            TagError::Write => f.write_str("could not write the tags"),
+        }
+    }
+}
+impl Context for TagError {}
 
-#[derive(Debug)]
-pub struct EditorData {
-    text: String,
-    resp: oneshot::Sender<String>,
+/// The tags of a file by name, in the order they are shown.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tags(pub Vec<(String, Vec<String>)>);
+
+impl Tags {
+    /// The first value of the tag, if it has one that isn't empty.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .filter(|(k, _)| k == key)
+            .flat_map(|(_, v)| v)
+            .map(|v| v.trim())
+            .find(|v| !v.is_empty())
+    }
+
+    fn push(&mut self, key: &str, value: String) {
+        let key = key.to_lowercase();
+        match self.0.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, values)) => values.push(value),
+            None => self.0.push((key, vec![value])),
+        }
+    }
+
+    /// Puts the [`FIELDS`] first, empty if the file didn't have them.
+    fn with_fields(self) -> Self {
+        let (mut known, rest): (Vec<_>, Vec<_>) = self
+            .0
+            .into_iter()
+            .partition(|(k, _)| FIELDS.contains(&k.as_str()));
+        let fields = FIELDS
+            .iter()
+            .map(|f| match known.iter().position(|(k, _)| k == f) {
+                Some(i) => known.swap_remove(i),
+                None => (f.to_string(), Vec::new()),
+            });
+        Self(fields.collect::<Vec<_>>().into_iter().chain(rest).collect())
+    }
+
+    /// The values to write, leaving out the empty ones.
+    fn values(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .flat_map(|(k, vs)| vs.iter().map(move |v| (k.as_str(), v.as_str())))
+            .filter(|(_, v)| !v.trim().is_empty())
+    }
+
+    /// Takes the name and artist of the link from the title and artist.
+    pub fn sync(&self, link: Link) -> Link {
+        Link {
+            name: self.get("title").map_or(link.name, str::to_owned),
+            artist: self.get("artist").map(str::to_owned).or(link.artist),
+            ..link
+        }
+    }
 }
 
-const LUMA_TEMPFILE: &str = "/tmp/luma-edit.yaml";
+impl Serialize for Tags {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
 
-impl Programs {
-    pub async fn run(self) -> Result<()> {
-        match self {
-            Self::Editor(data) => {
-                {
-                    let mut f = fs::File::create(LUMA_TEMPFILE).unwrap();
-                    f.write_all(data.text.as_bytes()).unwrap();
-                    log::debug!("wrote inital data to buffer");
+        let mut map = s.serialize_map(Some(self.0.len()))?;
+        for (k, vs) in &self.0 {
+            match vs.as_slice() {
+                [] => map.serialize_entry(k, "")?,
+                [v] => map.serialize_entry(k, v)?,
+                _ => map.serialize_entry(k, vs)?,
+            }
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Tags {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> std::result::Result<Self, D::Error> {
+        use serde::de::Error;
+
+        fn scalar(v: yaml::Value) -> Option<String> {
+            match v {
+                yaml::Value::Null => Some(String::new()),
+                yaml::Value::Bool(b) => Some(b.to_string()),
+                yaml::Value::Number(n) => Some(n.to_string()),
+                yaml::Value::String(s) => Some(s),
+                _ => None,
+            }
+        }
+
+        let mut tags = Tags::default();
+        for (k, v) in yaml::Mapping::deserialize(d)? {
+            let k = scalar(k).ok_or_else(|| D::Error::custom("tag names must be text"))?;
+            let vs = match v {
+                yaml::Value::Sequence(vs) => vs.into_iter().map(scalar).collect(),
+                v => scalar(v).map(|v| vec![v]),
+            };
+            let vs = vs.ok_or_else(|| D::Error::custom(format!("{} must be text or a list", k)))?;
+            tags.0.push((k.to_lowercase(), vs));
+        }
+        Ok(tags)
+    }
+}
+
+impl Validate for Tags {
+    fn validate(&self) -> Vec<String> {
+        let mut errs = Vec::new();
+        for (k, _) in &self.0 {
+            // what vorbis comments allow, id3 is written the same way
+            let bad = k.is_empty() || k.chars().any(|c| !(' '..='}').contains(&c) || c == '=');
+            if bad {
+                errs.push(format!("{:?} is not a valid tag name", k));
+            }
+            if PICTURE_KEYS.contains(&k.as_str()) {
+                errs.push(format!("{} can't be edited", k));
+            }
+        }
+        errs
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Flac,
+    Ogg,
+    Mp3,
+}
+
+fn format(path: &Path) -> Result<Format, TagError> {
+    let mut magic = [0; 4];
+    fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .change_context(TagError::Read)
+        .attach_printable_lazy(|| format!("could not read {}", path.display()))?;
+
+    let mp3 = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("mp3"));
+    match &magic {
+        b"fLaC" => Ok(Format::Flac),
+        b"OggS" => Ok(Format::Ogg),
+        [b'I', b'D', b'3', _] => Ok(Format::Mp3),
+        _ if mp3 => Ok(Format::Mp3),
+        _ => Err(Report::new(TagError::Read))
+            .attach_printable(format!("{} is not a flac, ogg or mp3 file", path.display())),
+    }
+}
+
+/// Reads the tags of the file, with the [`FIELDS`] first.
+pub fn read(path: &Path) -> Result<Tags, TagError> {
+    let tags = match format(path)? {
+        Format::Flac => flac::read(path)?.0,
+        Format::Ogg => ogg::read(path)?.0,
+        Format::Mp3 => mp3::read(path)?,
+    };
+    Ok(tags.with_fields())
+}
+
+/// Replaces the text tags of the file, keeping its pictures.
+pub fn write(path: &Path, tags: &Tags) -> Result<(), TagError> {
+    let res = match format(path)? {
+        Format::Flac => flac::write(path, tags),
+        Format::Ogg => ogg::write(path, tags),
+        Format::Mp3 => mp3::write(path, tags),
+    };
+    res.attach_printable_lazy(|| format!("could not tag {}", path.display()))
+}
+
+/// Writes the file next to the old one and moves it in place, so a failed
+/// write can't leave a broken file.
+fn replace(
+    path: &Path,
+    f: impl FnOnce(&mut io::BufWriter<&fs::File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty());
+    let tmp = tempfile::NamedTempFile::new_in(dir.unwrap_or(Path::new(".")))?;
+    let mut w = io::BufWriter::new(tmp.as_file());
+    f(&mut w)?;
+    w.flush()?;
+    drop(w);
+    tmp.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// Vorbis comments, used by FLAC and Ogg.
+mod vorbis {
+    use super::{Tags, PICTURE_KEYS};
+
+    /// The comments of a file, with the pictures kept apart.
+    #[derive(Debug, Default)]
+    pub struct Comments {
+        pub vendor: String,
+        pub tags: Tags,
+        pub pictures: Vec<String>,
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> Option<usize> {
+        let b = data.get(at..at + 4)?;
+        Some(u32::from_le_bytes(b.try_into().unwrap()) as usize)
+    }
+
+    /// Reads the comments at the start of `data` and how many bytes they took.
+    pub fn parse(data: &[u8]) -> Option<(Comments, usize)> {
+        let mut at = 0;
+        let mut string = |data: &[u8]| {
+            let len = u32_at(data, at)?;
+            let s = data.get(at + 4..at + 4 + len)?;
+            at += 4 + len;
+            Some(String::from_utf8_lossy(s).into_owned())
+        };
+
+        let mut c = Comments {
+            vendor: string(data)?,
+            ..Default::default()
+        };
+        let count = u32_at(data, at)?;
+        at += 4;
+        for _ in 0..count {
+            let len = u32_at(data, at)?;
+            let entry = data.get(at + 4..at + 4 + len)?;
+            at += 4 + len;
+            let entry = String::from_utf8_lossy(entry).into_owned();
+
+            let Some((k, v)) = entry.split_once('=') else {
+                continue;
+            };
+            if PICTURE_KEYS.contains(&k.to_lowercase().as_str()) {
+                c.pictures.push(entry);
+            } else {
+                c.tags.push(k, v.to_owned());
+            }
+        }
+        Some((c, at))
+    }
+
+    pub fn to_bytes(c: &Comments) -> Vec<u8> {
+        let entries: Vec<String> = c
+            .tags
+            .values()
+            .map(|(k, v)| format!("{}={}", k.to_uppercase(), v))
+            .chain(c.pictures.iter().cloned())
+            .collect();
+
+        let mut out = Vec::new();
+        let mut put = |s: &[u8]| {
+            out.extend((s.len() as u32).to_le_bytes());
+            out.extend(s);
+        };
+        put(c.vendor.as_bytes());
+        out.extend((entries.len() as u32).to_le_bytes());
+        for e in &entries {
+            out.extend((e.len() as u32).to_le_bytes());
+            out.extend(e.as_bytes());
+        }
+        out
+    }
+}
+
+mod flac {
+    use std::io::Write;
+    use std::path::Path;
+
+    use super::vorbis::{self, Comments};
+    use super::{replace, TagError, Tags};
+    use crate::prelude::*;
+
+    const VORBIS_COMMENT: u8 = 4;
+
+    /// A metadata block's type and body.
+    type Block<'a> = (u8, &'a [u8]);
+
+    /// The metadata blocks by type and where the audio starts.
+    fn blocks(data: &[u8]) -> Option<(Vec<Block<'_>>, usize)> {
+        let mut blocks = Vec::new();
+        let mut at = 4;
+        loop {
+            let head = data.get(at..at + 4)?;
+            let len = u32::from_be_bytes([0, head[1], head[2], head[3]]) as usize;
+            blocks.push((head[0] & 0x7f, data.get(at + 4..at + 4 + len)?));
+            at += 4 + len;
+            if head[0] & 0x80 != 0 {
+                return Some((blocks, at));
+            }
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<(Tags, Comments), TagError> {
+        let data = fs::read(path).change_context(TagError::Read)?;
+        let (blocks, _) = blocks(&data)
+            .ok_or(TagError::Read)
+            .attach_printable("the flac metadata is cut off")?;
+        let c = match blocks.iter().find(|(t, _)| *t == VORBIS_COMMENT) {
+            Some((_, b)) => {
+                vorbis::parse(b)
+                    .ok_or(TagError::Read)
+                    .attach_printable("the vorbis comment is broken")?
+                    .0
+            }
+            None => Comments::default(),
+        };
+        Ok((c.tags.clone(), c))
+    }
+
+    pub fn write(path: &Path, tags: &Tags) -> Result<(), TagError> {
+        let (_, old) = read(path).change_context(TagError::Write)?;
+        let comment = vorbis::to_bytes(&Comments {
+            tags: tags.clone(),
+            ..old
+        });
+        if comment.len() >= 1 << 24 {
+            return Err(Report::new(TagError::Write)).attach_printable("the tags are too big");
+        }
+
+        let data = fs::read(path).change_context(TagError::Write)?;
+        let (mut blocks, audio) = blocks(&data).ok_or(TagError::Write)?;
+        match blocks.iter().position(|(t, _)| *t == VORBIS_COMMENT) {
+            Some(i) => blocks[i].1 = &comment,
+            // it goes after the stream info, which has to be first
+            None => blocks.insert(1.min(blocks.len()), (VORBIS_COMMENT, &comment)),
+        }
+
+        replace(path, |w| {
+            w.write_all(b"fLaC")?;
+            for (i, (kind, body)) in blocks.iter().enumerate() {
+                let last = if i + 1 == blocks.len() { 0x80 } else { 0 };
+                let len = (body.len() as u32).to_be_bytes();
+                w.write_all(&[kind | last, len[1], len[2], len[3]])?;
+                w.write_all(body)?;
+            }
+            w.write_all(&data[audio..])
+        })
+        .change_context(TagError::Write)
+    }
+}
+
+mod ogg {
+    use std::path::Path;
+
+    use ::ogg::{PacketReader, PacketWriteEndInfo, PacketWriter};
+
+    use super::vorbis::{self, Comments};
+    use super::{replace, TagError, Tags};
+    use crate::prelude::*;
+
+    /// What the comment packet starts with for the codecs that are known.
+    const MAGIC: &[&[u8]] = &[b"\x03vorbis", b"OpusTags"];
+
+    /// The comments in the packet, with its magic and what comes after them.
+    fn parse(packet: &[u8]) -> Option<(&[u8], Comments, &[u8])> {
+        let magic = MAGIC.iter().find(|m| packet.starts_with(m))?;
+        let (c, len) = vorbis::parse(&packet[magic.len()..])?;
+        Some((magic, c, &packet[magic.len() + len..]))
+    }
+
+    pub fn read(path: &Path) -> Result<(Tags, Comments), TagError> {
+        let file = fs::File::open(path).change_context(TagError::Read)?;
+        let mut r = PacketReader::new(io::BufReader::new(file));
+        // the comments are the second packet of the first stream
+        let mut packet = None;
+        for _ in 0..2 {
+            packet = r.read_packet().change_context(TagError::Read)?;
+        }
+        let (_, c, _) = packet
+            .as_ref()
+            .and_then(|p| parse(&p.data))
+            .ok_or(TagError::Read)
+            .attach_printable("no vorbis or opus comments were found")?;
+        Ok((c.tags.clone(), c))
+    }
+
+    pub fn write(path: &Path, tags: &Tags) -> Result<(), TagError> {
+        let file = fs::File::open(path).change_context(TagError::Write)?;
+        let mut r = PacketReader::new(io::BufReader::new(file));
+
+        replace(path, |w| {
+            let mut out = PacketWriter::new(w);
+            let mut first = None;
+            let mut seen = 0;
+            while let Some(p) = r.read_packet().map_err(io::Error::other)? {
+                let serial = p.stream_serial();
+                let first = *first.get_or_insert(serial);
+                let mut data = p.data.clone();
+                if serial == first {
+                    seen += 1;
+                    if seen == 2 {
+                        let (magic, old, rest) = parse(&p.data)
+                            .ok_or_else(|| io::Error::other("no vorbis or opus comments"))?;
+                        let c = Comments {
+                            tags: tags.clone(),
+                            ..old
+                        };
+                        data = [magic, &vorbis::to_bytes(&c), rest].concat();
+                    }
                 }
 
-                let _ = tokio::process::Command::new("nvim")
-                    .arg(LUMA_TEMPFILE)
-                    .spawn()
-                    .unwrap()
-                    .wait()
-                    .await
-                    .unwrap()
-                    .success();
+                let end = if p.last_in_stream() {
+                    PacketWriteEndInfo::EndStream
+                } else if p.last_in_page() {
+                    PacketWriteEndInfo::EndPage
+                } else {
+                    PacketWriteEndInfo::NormalPacket
+                };
+                out.write_packet(data.into_boxed_slice(), serial, end, p.absgp_page())?;
+            }
+            Ok(())
+        })
+        .change_context(TagError::Write)
+    }
+}
+
+mod mp3 {
+    use std::path::Path;
+
+    use id3::frame::{Comment, Content, ExtendedText};
+    use id3::{Frame, TagLike};
+
+    use super::{TagError, Tags};
+    use crate::prelude::*;
+
+    /// The frames that have a name in the editor.
+    const FRAMES: &[(&str, &str)] = &[
+        ("title", "TIT2"),
+        ("artist", "TPE1"),
+        ("album", "TALB"),
+        ("year", "TDRC"),
+        ("language", "TLAN"),
+        ("genre", "TCON"),
+        ("tracknumber", "TRCK"),
+        ("albumartist", "TPE2"),
+    ];
 
-                log::debug!("asked question");
+    fn tag(path: &Path) -> Result<id3::Tag, TagError> {
+        let tag = id3::no_tag_ok(id3::Tag::read_from_path(path)).change_context(TagError::Read)?;
+        Ok(tag.unwrap_or_default())
+    }
 
-                let res = fs::read(LUMA_TEMPFILE).unwrap();
-                if let Ok(s) = String::from_utf8(res) {
-                    data.resp.send(s).unwrap();
+    pub fn read(path: &Path) -> Result<Tags, TagError> {
+        let mut tags = Tags::default();
+        for f in tag(path)?.frames() {
+            match f.content() {
+                Content::ExtendedText(t) => tags.push(&t.description, t.value.clone()),
+                Content::Comment(c) => tags.push("comment", c.text.clone()),
+                Content::Text(_) => {
+                    let name = FRAMES.iter().find(|(_, id)| *id == f.id());
+                    let name = name.map_or(f.id(), |(n, _)| n);
+                    for v in f.content().text_values().into_iter().flatten() {
+                        tags.push(name, v.to_owned());
+                    }
                 }
-                Ok(())
+                _ => {}
+            }
+        }
+        Ok(tags)
+    }
+
+    pub fn write(path: &Path, tags: &Tags) -> Result<(), TagError> {
+        let mut tag = tag(path).change_context(TagError::Write)?;
+
+        // every text frame is replaced, pictures and the rest are left alone
+        let ids: Vec<String> = tag
+            .frames()
+            .map(|f| f.id().to_owned())
+            .filter(|id| id.starts_with('T') || id == "COMM")
+            .collect();
+        for id in ids {
+            tag.remove(id);
+        }
+
+        for (k, vs) in &tags.0 {
+            let vs: Vec<&str> = vs
+                .iter()
+                .map(|v| v.as_str())
+                .filter(|v| !v.trim().is_empty())
+                .collect();
+            if vs.is_empty() {
+                continue;
             }
-            Self::Tagger { editor } => {
-                Err(format!("Unimplliented handle of tagger: {:?}", editor).into())
+            let id = FRAMES
+                .iter()
+                .find(|(n, _)| n == k)
+                .map(|(_, id)| id.to_string());
+            // frames without a name are shown by their id
+            let id = id.or_else(|| {
+                let raw = k.to_uppercase();
+                (raw.len() == 4 && raw.starts_with('T') && raw != "TXXX").then_some(raw)
+            });
+            match id {
+                Some(id) => {
+                    tag.add_frame(Frame::text(id, vs.join("\0")));
+                }
+                None if k == "comment" => {
+                    tag.add_frame(Comment {
+                        lang: "eng".into(),
+                        description: String::new(),
+                        text: vs.join("\n"),
+                    });
+                }
+                None => {
+                    tag.add_frame(ExtendedText {
+                        description: k.clone(),
+                        value: vs.join("\0"),
+                    });
+                }
             }
         }
+
+        tag.write_to_path(path, id3::Version::Id3v24)
+            .change_context(TagError::Write)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use id3::TagLike;
+
+    use super::{read, write, Tags};
+    use crate::prelude::*;
+    use crate::state::Link;
+
+    fn edit(path: &Path) {
+        let mut tags = read(path).unwrap();
+        assert_eq!(tags.0[0], ("title".to_owned(), vec!["old".to_owned()]));
+        tags.0[0].1 = vec!["song".into()];
+        tags.0[1].1 = vec!["a".into(), "b".into()];
+        write(path, &tags).unwrap();
+
+        let tags = read(path).unwrap();
+        assert_eq!(tags.get("title"), Some("song"));
+        assert_eq!(tags.0[1].1, ["a", "b"]);
+        assert_eq!(tags.get("album"), None);
     }
 
-    pub async fn editor(text: String, resp: oneshot::Sender<String>) -> Programs {
-        Programs::Editor(EditorData { text, resp })
-    }
-
-    pub fn tagger(_file: impl AsRef<PathBuf>) -> Programs {
-        todo!()
-        // err() { echo "Usage:
-        //     tag [OPTIONS] file
-        // Options:
-        //     -I: dont hide the image
-        // " && exit 1 ;
-        // }
-        //
-        // file="$1"
-        //
-        // temp="$(mktemp "/tmp/$1.XXXXXX")"
-        // trap 'rm -f $temp' HUP INT QUIT TERM EXIT
-        //
-        // [ ! -f "$file" ] && echo 'Provide file to tag.' && exit 1
-        //
-        // vorbiscomment -l "$file" -c "$temp"
-        //
-        // if [ ! -n "$donthide" ]; then
-        //     image="$(grep "METADATA_BLOCK_PICTURE" "$temp")"
-        //     sed -i -e '/METADATA_BLOCK_PICTURE/d' "$temp"
-        // fi
-        //
-        // grep "language=" $temp || echo "language=" >> "$temp"
-        // grep "title=" $temp || echo "title=" >> "$temp"
-        // grep "artist=" $temp || echo "artist=" >> "$temp"
-        // grep "album=" $temp || echo "album=" >> "$temp"
-        // # grep "TRACKNUMBER=" $temp || echo "TRACKNUMBER=" >> $temp
-        // # grep "total=" $temp || echo "total=" >> $temp
-        // grep "year=" $temp || echo "year=" >> "$temp"
-        // grep "tags=" $temp || echo "tags=" >> "$temp"
-        // grep "rating=" $temp || echo "rating=" >> "$temp"
-        // grep "comment=" $temp || echo "comment=" >> "$temp"
-        //
-        // $EDITOR $temp
-        //
-        // [ -n "$image" ] && echo "$image" >> "$temp"
-        // vorbiscomment -w "$file" -c "$temp"
-        //
-        // rm "$temp"
-        //
+    #[test]
+    fn formats() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // stream info, a picture and then the audio
+        let path = dir.path().join("a.flac");
+        let mut flac = b"fLaC\x00\x00\x00\x22".to_vec();
+        flac.extend([0; 34]);
+        flac.extend(b"\x86\x00\x00\x03pic");
+        flac.extend(b"audio");
+        fs::write(&path, &flac).unwrap();
+        let mut tags = Tags::default();
+        tags.push("TITLE", "old".into());
+        write(&path, &tags).unwrap();
+        edit(&path);
+        let data = fs::read(&path).unwrap();
+        assert!(data.ends_with(b"\x86\x00\x00\x03picaudio"));
+
+        let path = dir.path().join("a.ogg");
+        let mut out = ::ogg::PacketWriter::new(Vec::new());
+        let comment = [
+            &b"\x03vorbis\x01\x00\x00\x00v\x02\x00\x00\x00\x09\x00\x00\x00TITLE=old"[..],
+            b"\x1a\x00\x00\x00METADATA_BLOCK_PICTURE=pic\x01",
+        ]
+        .concat();
+        let packets = [&b"\x01vorbis"[..], &comment, b"\x05vorbis", b"audio"];
+        for (i, p) in packets.iter().enumerate() {
+            let end = match i {
+                1 => ::ogg::PacketWriteEndInfo::NormalPacket,
+                3 => ::ogg::PacketWriteEndInfo::EndStream,
+                _ => ::ogg::PacketWriteEndInfo::EndPage,
+            };
+            out.write_packet(p.to_vec().into(), 7, end, 0).unwrap();
+        }
+        fs::write(&path, out.into_inner()).unwrap();
+        edit(&path);
+        let (_, c) = super::ogg::read(&path).unwrap();
+        assert_eq!(c.pictures, ["METADATA_BLOCK_PICTURE=pic"]);
+
+        let path = dir.path().join("a.mp3");
+        let mut tag = id3::Tag::new();
+        tag.set_title("old");
+        tag.add_frame(id3::frame::Picture {
+            mime_type: "image/png".into(),
+            picture_type: id3::frame::PictureType::CoverFront,
+            description: String::new(),
+            data: b"pic".to_vec(),
+        });
+        fs::write(&path, b"audio").unwrap();
+        tag.write_to_path(&path, id3::Version::Id3v24).unwrap();
+        edit(&path);
+        let tag = id3::Tag::read_from_path(&path).unwrap();
+        assert_eq!(tag.pictures().count(), 1);
+
+        let link = read(&path).unwrap().sync(Link {
+            name: "x".into(),
+            ..Default::default()
+        });
+        assert_eq!(
+            (link.name.as_str(), link.artist.as_deref()),
+            ("song", Some("a"))
+        );
     }
 }
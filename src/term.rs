@@ -0,0 +1,164 @@
+//! A program running inside of a pseudo-terminal so it can be drawn in a pane.
+
+use std::io::{Read, Write};
+use std::process::{Child, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::prelude::*;
+use crate::state::OpenCommand;
+
+#[derive(Debug)]
+pub struct TermError;
+impl fmt::Display for TermError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("terminal pane failed")
+    }
+}
+impl Context for TermError {}
+
+pub struct Term {
+    /// Name of the program, used as the title of the pane.
+    pub name: String,
+    pty: Arc<pty_process::blocking::Pty>,
+    child: Child,
+    /// The screen of the program as it would be shown by a real terminal.
+    parser: Arc<Mutex<vt100::Parser>>,
+    /// Set by the reader thread when there is new output to draw.
+    dirty: Arc<AtomicBool>,
+    /// The last size given to the pty as (rows, cols).
+    size: Mutex<(u16, u16)>,
+}
+
+impl Term {
+    /// Runs the command with the argument, if given, in a new pty of the given
+    /// size.
+    pub fn spawn(
+        cmd: &OpenCommand,
+        arg: Option<&str>,
+        (rows, cols): (u16, u16),
+    ) -> Result<Self, TermError> {
+        let pty = pty_process::blocking::Pty::new()
+            .change_context(TermError)
+            .attach_printable("could not allocate a pty")?;
+        pty.resize(pty_process::Size::new(rows, cols))
+            .change_context(TermError)?;
+
+        let pts = pty.pts().change_context(TermError)?;
+        let child = pty_process::blocking::Command::new(&cmd.name)
+            .args(cmd.args.iter())
+            .args(arg)
+            .env("TERM", "xterm-256color")
+            .spawn(&pts)
+            .change_context(TermError)
+            .attach_printable_lazy(|| format!("could not spawn {}", cmd.name))?;
+
+        let pty = Arc::new(pty);
+        let parser = Arc::new(Mutex::new(vt100::Parser::new(rows, cols, 0)));
+        let dirty = Arc::new(AtomicBool::new(true));
+
+        {
+            let pty = Arc::clone(&pty);
+            let parser = Arc::clone(&parser);
+            let dirty = Arc::clone(&dirty);
+            std::thread::spawn(move || {
+                let mut buf = [0u8; 4096];
+                loop {
+                    // the read fails once the program exits and closes the pts
+                    match (&*pty).read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => {
+                            parser.lock().unwrap().process(&buf[..n]);
+                            dirty.store(true, Ordering::Release);
+                        }
+                    }
+                }
+                log::debug!("pty reader finished");
+            });
+        }
+
+        Ok(Self {
+            name: cmd.name.clone(),
+            pty,
+            child,
+            parser,
+            dirty,
+            size: Mutex::new((rows, cols)),
+        })
+    }
+
+    /// Sends input to the program.
+    pub fn write(&self, bytes: &[u8]) -> Result<(), TermError> {
+        (&*self.pty).write_all(bytes).change_context(TermError)
+    }
+
+    /// Changes the size of the pty, does nothing if it is already that size.
+    pub fn resize(&self, rows: u16, cols: u16) {
+        let mut size = self.size.lock().unwrap();
+        if *size == (rows, cols) {
+            return;
+        }
+        *size = (rows, cols);
+
+        self.parser.lock().unwrap().set_size(rows, cols);
+        if let Err(e) = self.pty.resize(pty_process::Size::new(rows, cols)) {
+            log::warn!("could not resize pty: {}", e);
+        }
+    }
+
+    /// Returns if there has been output since the last call.
+    pub fn take_dirty(&self) -> bool {
+        self.dirty.swap(false, Ordering::AcqRel)
+    }
+
+    /// Returns the exit status of the program if it has finished.
+    pub fn try_wait(&mut self) -> Option<ExitStatus> {
+        self.child.try_wait().ok().flatten()
+    }
+
+    pub fn parser(&self) -> MutexGuard<'_, vt100::Parser> {
+        self.parser.lock().unwrap()
+    }
+}
+
+impl Drop for Term {
+    fn drop(&mut self) {
+        if self.try_wait().is_none() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+impl fmt::Debug for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Term")
+            .field("name", &self.name)
+            .field("child", &self.child.id())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::Term;
+    use crate::state::OpenCommand;
+
+    #[test]
+    fn output_reaches_screen() {
+        let echo = OpenCommand::new(&["echo"]).unwrap().in_term();
+        let term = Term::spawn(&echo, Some("hello pty"), (5, 20)).unwrap();
+
+        let start = Instant::now();
+        while !term.parser().screen().contents().contains("hello pty") {
+            assert!(start.elapsed() < Duration::from_secs(5), "no output");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(term.take_dirty());
+
+        term.resize(10, 40);
+        assert_eq!(term.parser().screen().size(), (10, 40));
+    }
+}
@@ -0,0 +1,348 @@
+//! Pictures shown in the preview of links.
+//!
+//! A link whose file is an image is shown as it is. Other links get a
+//! thumbnail in the cache, a frame of a video file or the `og:image` of a web
+//! page, which is made in the background once the link has been shown for a
+//! moment. An empty thumbnail means the link has no picture, so it isn't looked for
+//! again.
+
+use std::collections::{HashSet, VecDeque};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::prelude::*;
+use crate::state::Link;
+
+const IMAGES: &[&str] = &["png", "jpg", "jpeg", "gif", "webp"];
+const VIDEOS: &[&str] = &["mp4", "mkv", "webm", "mov", "avi", "m4v"];
+
+/// Pages are only read this far looking for the picture.
+const MAX_PAGE: u64 = 1024 * 1024;
+/// Pictures bigger than this aren't cached.
+const MAX_IMAGE: u64 = 16 * 1024 * 1024;
+
+/// How long a link has to stay selected before its thumbnail is made.
+const SETTLE: Duration = Duration::from_millis(300);
+/// How many thumbnails are made at once, and how many can wait for that.
+const JOBS: usize = 2;
+const QUEUE: usize = 8;
+
+/// Where a link's picture comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// The link's file is the picture.
+    Image(PathBuf),
+    /// A frame of the link's video file.
+    Video(PathBuf),
+    /// The picture a web page gives for itself.
+    Page(String),
+}
+
+impl Source {
+    pub fn of(link: &Link) -> Option<Source> {
+        if let Some(file) = &link.file {
+            let path = PathBuf::from(file);
+            let ext = path.extension().unwrap_or_default().to_string_lossy();
+            let is = |exts: &[&str]| exts.iter().any(|e| ext.eq_ignore_ascii_case(e));
+            if is(IMAGES) {
+                return Some(Source::Image(path));
+            }
+            if is(VIDEOS) {
+                return Some(Source::Video(path));
+            }
+        }
+        let web = link.link.starts_with("http://") || link.link.starts_with("https://");
+        web.then(|| Source::Page(link.link.clone()))
+    }
+
+    /// Where the picture is. Thumbnails are kept in the directory, named
+    /// after a hash of the file or page that is the same on every run.
+    fn path(&self, dir: &Path) -> PathBuf {
+        let key = match self {
+            Source::Image(p) => return p.clone(),
+            Source::Video(p) => p.as_os_str().as_encoded_bytes(),
+            Source::Page(u) => u.as_bytes(),
+        };
+        dir.join(format!("{:016x}", fnv(key)))
+    }
+}
+
+/// The 64 bit FNV-1a hash of the bytes, the same on every run.
+pub fn fnv(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The directory thumbnails are cached in.
+pub fn dir() -> PathBuf {
+    crate::config::cache_dir().join("thumbs")
+}
+
+/// The picture of the link, if there is one yet.
+pub fn find(dir: &Path, link: &Link) -> Option<PathBuf> {
+    let path = Source::of(link)?.path(dir);
+    let len = fs::metadata(&path).ok()?.len();
+    (len > 0).then_some(path)
+}
+
+/// A thumbnail to make, where it comes from and where it goes.
+#[derive(Debug)]
+struct Job {
+    source: Source,
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+/// Thumbnails being made in the background.
+#[derive(Debug, Default)]
+pub struct Thumbs {
+    /// Thumbnails that were asked for this run, so failures aren't retried.
+    tried: HashSet<PathBuf>,
+    /// The thumbnail of the selected link and since when it was selected.
+    wanted: Option<(Job, Instant)>,
+    queued: VecDeque<Job>,
+    running: Vec<JoinHandle<()>>,
+}
+
+impl Thumbs {
+    /// Asks for the selected link's thumbnail if it isn't cached. It is made
+    /// once the link has stayed selected for a moment.
+    pub fn fetch(&mut self, dir: &Path, link: &Link) {
+        let source = Source::of(link).filter(|s| !matches!(s, Source::Image(_)));
+        let Some(source) = source else {
+            self.wanted = None;
+            return;
+        };
+        let path = source.path(dir);
+        if path.exists() || self.tried.contains(&path) {
+            self.wanted = None;
+            return;
+        }
+        if self.wanted.as_ref().is_some_and(|w| w.0.path == path) {
+            return;
+        }
+        let job = Job {
+            source,
+            dir: dir.to_owned(),
+            path,
+        };
+        self.wanted = Some((job, Instant::now()));
+    }
+
+    /// Starts the thumbnails that are due, gives back if any finished since
+    /// the last call.
+    pub fn tick(&mut self) -> bool {
+        if self
+            .wanted
+            .as_ref()
+            .is_some_and(|w| w.1.elapsed() >= SETTLE)
+        {
+            let (job, _) = self.wanted.take().expect("it was just looked at");
+            self.tried.insert(job.path.clone());
+            self.queued.push_back(job);
+            if self.queued.len() > QUEUE {
+                // the oldest can be asked for again when it is shown again
+                let old = self.queued.pop_front().expect("the queue is full");
+                self.tried.remove(&old.path);
+            }
+        }
+
+        let before = self.running.len();
+        self.running.retain(|t| !t.is_finished());
+        let finished = self.running.len() != before;
+        while self.running.len() < JOBS {
+            let Some(job) = self.queued.pop_back() else {
+                break;
+            };
+            self.running.push(std::thread::spawn(move || {
+                if let Err(e) = make(&job.source, &job.dir, &job.path) {
+                    log::info!("no thumbnail for {:?}: {}", job.source, e);
+                }
+            }));
+        }
+        finished
+    }
+
+    pub fn busy(&self) -> bool {
+        self.wanted.is_some() || !self.queued.is_empty() || !self.running.is_empty()
+    }
+}
+
+/// Makes the thumbnail at the path. An empty file is left when there is
+/// none to be had.
+fn make(source: &Source, dir: &Path, path: &Path) -> std::result::Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    let part = PathBuf::from(part);
+
+    let res = match source {
+        Source::Image(_) => unreachable!("images are their own thumbnail"),
+        Source::Video(file) => frame(file, &part),
+        Source::Page(url) => page(url, &part),
+    };
+    match &res {
+        Ok(()) => fs::rename(&part, path),
+        Err(_) => {
+            let _ = fs::remove_file(&part);
+            fs::write(path, b"")
+        }
+    }
+    .map_err(|e| e.to_string())?;
+    res
+}
+
+/// Saves a frame of the video that shows what it is about with ffmpeg.
+fn frame(file: &Path, to: &Path) -> std::result::Result<(), String> {
+    let status = Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-i"])
+        .arg(file)
+        .args(["-vf", "thumbnail,scale=512:-2", "-frames:v", "1"])
+        .args(["-f", "image2", "-c:v", "png"])
+        .arg(to)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| format!("could not run ffmpeg: {}", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("ffmpeg failed with {}", status))
+    }
+}
+
+/// Saves the picture named in the page's `og:image` meta tag.
+fn page(url: &str, to: &Path) -> std::result::Result<(), String> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(10))
+        .timeout_read(Duration::from_secs(20))
+        .build();
+    let get = |url: &str, max: u64| -> std::result::Result<Vec<u8>, String> {
+        let resp = agent.get(url).call().map_err(|e| e.to_string())?;
+        let mut body = Vec::new();
+        resp.into_reader()
+            .take(max)
+            .read_to_end(&mut body)
+            .map_err(|e| e.to_string())?;
+        Ok(body)
+    };
+
+    let html = get(url, MAX_PAGE)?;
+    let image = og_image(&String::from_utf8_lossy(&html)).ok_or("the page names no picture")?;
+    let image = url::Url::parse(url)
+        .and_then(|base| base.join(&image))
+        .map_err(|e| e.to_string())?;
+    let body = get(image.as_str(), MAX_IMAGE + 1)?;
+    if body.len() as u64 > MAX_IMAGE {
+        return Err("the picture is too big".to_owned());
+    }
+    fs::write(to, body).map_err(|e| e.to_string())
+}
+
+/// The picture a page gives in its `og:image` or `twitter:image` meta tags.
+fn og_image(html: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let mut found = None;
+    for (i, _) in lower.match_indices("<meta") {
+        let end = lower[i..].find('>').map_or(lower.len(), |e| i + e);
+        let attrs = attrs(&html[i + 5..end]);
+        let get = |name: &str| {
+            attrs
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str())
+        };
+        let Some(content) = get("content").filter(|c| !c.is_empty()) else {
+            continue;
+        };
+        match get("property").or_else(|| get("name")) {
+            Some(p) if p.eq_ignore_ascii_case("og:image") => return Some(unescape(content)),
+            Some(p) if p.eq_ignore_ascii_case("twitter:image") => {
+                found.get_or_insert_with(|| unescape(content));
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+/// The `name="value"` attributes in a tag.
+fn attrs(tag: &str) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    let mut rest = tag;
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq].split_whitespace().last().unwrap_or("");
+        let after = rest[eq + 1..].trim_start();
+        let (value, len) = match after.chars().next() {
+            Some(q @ ('"' | '\'')) => match after[1..].find(q) {
+                Some(e) => (&after[1..e + 1], e + 2),
+                None => (&after[1..], after.len()),
+            },
+            _ => {
+                let e = after.find(char::is_whitespace).unwrap_or(after.len());
+                (&after[..e], e)
+            }
+        };
+        attrs.push((name.to_owned(), value.to_owned()));
+        rest = &after[len..];
+    }
+    attrs
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find, og_image, Source, Thumbs};
+    use crate::prelude::*;
+    use crate::state::Link;
+
+    #[test]
+    fn pictures() {
+        let html = r#"<head><meta name="twitter:image" content="/t.png">
+            <META content='https://x.com/a.jpg?w=1&amp;h=2' property=og:image /></head>"#;
+        assert_eq!(og_image(html).unwrap(), "https://x.com/a.jpg?w=1&h=2");
+        assert_eq!(og_image(&html[..60]).unwrap(), "/t.png");
+        assert_eq!(og_image("<meta charset=utf-8>"), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut link = Link {
+            link: "https://x.com/page".into(),
+            ..Default::default()
+        };
+        assert_eq!(find(dir.path(), &link), None);
+        let cached = Source::of(&link).unwrap().path(dir.path());
+        // the name is the same on every run
+        assert_eq!(cached, dir.path().join("8db5185030775c49"));
+
+        // nothing is made until the link has stayed selected
+        let mut thumbs = Thumbs::default();
+        thumbs.fetch(dir.path(), &link);
+        thumbs.tick();
+        assert!(thumbs.busy());
+        assert!(thumbs.running.is_empty() && thumbs.queued.is_empty());
+        thumbs.fetch(dir.path(), &Link::default());
+        assert!(!thumbs.busy());
+
+        fs::write(&cached, b"").unwrap();
+        assert_eq!(find(dir.path(), &link), None);
+        fs::write(&cached, b"png").unwrap();
+        assert_eq!(find(dir.path(), &link), Some(cached));
+
+        let image = dir.path().join("cover.JPG");
+        fs::write(&image, b"jpg").unwrap();
+        link.file = Some(image.to_string_lossy().into());
+        assert_eq!(find(dir.path(), &link), Some(image));
+    }
+}